@@ -0,0 +1,112 @@
+//! Generates a world, or builds one from a heightmap, and writes it to a
+//! binary glTF file for Blender and other tools.
+//!
+//! `vox2-export [--seed <seed>] <out.glb>`
+//!
+//! `vox2-export --heightmap <png> [--materials <png>] [--scale <blocks>]
+//! [--sea-level <y>] <out.glb>`
+//!
+//! Either can take `--structure <template> <x> <z>`, more than once, to stand
+//! saved templates on the ground.
+
+use std::{path::PathBuf, process::ExitCode};
+
+use vox23::{
+    export,
+    heightmap::{self, HeightmapOptions},
+    schematic::Schematic,
+    worldgen,
+};
+
+fn usage() -> ExitCode {
+    eprintln!("usage: vox2-export [--seed <seed>] <out.glb>");
+    eprintln!(
+        "       vox2-export --heightmap <png> [--materials <png>] [--scale <blocks>] \
+         [--sea-level <y>] <out.glb>"
+    );
+    eprintln!("       either with [--structure <template> <x> <z>]...");
+    ExitCode::FAILURE
+}
+
+fn main() -> ExitCode {
+    let mut seed = worldgen::DEFAULT_SEED;
+    let mut heightmap = None;
+    let mut materials: Option<PathBuf> = None;
+    let mut options = HeightmapOptions::default();
+    let mut structures = vec![];
+    let mut out = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => match args.next().and_then(|s| s.parse().ok()) {
+                Some(s) => seed = s,
+                None => return usage(),
+            },
+            "--heightmap" => match args.next() {
+                Some(path) => heightmap = Some(path),
+                None => return usage(),
+            },
+            "--materials" => match args.next() {
+                Some(path) => materials = Some(path.into()),
+                None => return usage(),
+            },
+            "--scale" => match args.next().and_then(|s| s.parse().ok()) {
+                Some(s) => options.scale = s,
+                None => return usage(),
+            },
+            "--sea-level" => match args.next().and_then(|s| s.parse().ok()) {
+                Some(y) => options.sea_level = Some(y),
+                None => return usage(),
+            },
+            "--structure" => {
+                let path = args.next();
+                let x = args.next().and_then(|s| s.parse::<i64>().ok());
+                let z = args.next().and_then(|s| s.parse::<i64>().ok());
+                match (path, x, z) {
+                    (Some(path), Some(x), Some(z)) => structures.push((path, x, z)),
+                    _ => return usage(),
+                }
+            }
+            "-h" | "--help" => return usage(),
+            _ if out.is_none() => out = Some(arg),
+            _ => return usage(),
+        }
+    }
+    let out = match out {
+        Some(out) => out,
+        None => return usage(),
+    };
+
+    let mut chunks = match heightmap {
+        Some(path) => match heightmap::load(&path, materials.as_deref(), &options) {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                eprintln!("Couldn't build terrain from {}: {}", path, e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => worldgen::generate(seed),
+    };
+    for (path, x, z) in structures {
+        let template = match Schematic::load(&path) {
+            Ok(template) => template,
+            Err(e) => {
+                eprintln!("Couldn't load {}: {}", path, e);
+                return ExitCode::FAILURE;
+            }
+        };
+        if !worldgen::place_structure(&mut chunks, &template, x, z) {
+            eprintln!("No ground at {}, {} for {}", x, z, path);
+        }
+    }
+    match export::save_glb(&chunks, &out) {
+        Ok(()) => {
+            println!("Wrote {} chunks to {}", chunks.len(), out);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Couldn't write {}: {}", out, e);
+            ExitCode::FAILURE
+        }
+    }
+}
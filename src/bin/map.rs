@@ -0,0 +1,81 @@
+//! Draws a top-down map of a world to a PNG without a window or GPU.
+//!
+//! `vox2-map [--seed <seed>] <out.png>`
+//!
+//! `vox2-map --vox <file.vox> [--mapping <json>] <out.png>`
+
+use std::process::ExitCode;
+
+use vox23::{
+    map,
+    vox::{self, VoxFile, VoxMapping},
+    worldgen,
+};
+
+fn usage() -> ExitCode {
+    eprintln!("usage: vox2-map [--seed <seed>] <out.png>");
+    eprintln!("       vox2-map --vox <file.vox> [--mapping <json>] <out.png>");
+    ExitCode::FAILURE
+}
+
+fn main() -> ExitCode {
+    let mut seed = worldgen::DEFAULT_SEED;
+    let mut vox_path = None;
+    let mut mapping_path = None;
+    let mut out = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => match args.next().and_then(|s| s.parse().ok()) {
+                Some(s) => seed = s,
+                None => return usage(),
+            },
+            "--vox" => match args.next() {
+                Some(path) => vox_path = Some(path),
+                None => return usage(),
+            },
+            "--mapping" => match args.next() {
+                Some(path) => mapping_path = Some(path),
+                None => return usage(),
+            },
+            "-h" | "--help" => return usage(),
+            _ if out.is_none() => out = Some(arg),
+            _ => return usage(),
+        }
+    }
+    let out = match out {
+        Some(out) => out,
+        None => return usage(),
+    };
+
+    let chunks = match vox_path {
+        Some(path) => {
+            let mapping = match mapping_path.map(VoxMapping::load) {
+                Some(Ok(mapping)) => mapping,
+                Some(Err(e)) => {
+                    eprintln!("Couldn't read the mapping: {}", e);
+                    return ExitCode::FAILURE;
+                }
+                None => VoxMapping::default(),
+            };
+            match VoxFile::load(&path) {
+                Ok(file) => vox::import(&file, &mapping, (0, 0, 0)),
+                Err(e) => {
+                    eprintln!("Couldn't read {}: {}", path, e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        None => worldgen::generate(seed),
+    };
+    match map::save_png(&chunks, &out) {
+        Ok(()) => {
+            println!("Drew {} chunks to {}", chunks.len(), out);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Couldn't write {}: {}", out, e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use crate::{
+    chunk::{BlockKind, Transparency},
+    falling, fluid,
+    state::Property,
+    tick::{offset, Position, TickContext},
+};
+
+pub type TickFn = fn(&mut TickContext, Position);
+
+/// How a kind of block behaves in the simulation.
+pub struct BlockDefinition {
+    pub name: &'static str,
+    /// Average color of the texture, for palettes and maps
+    pub color: [u8; 3],
+    /// State the block keeps, see `state::BlockState`
+    pub properties: &'static [Property],
+    /// JSON model for blocks that aren't full cubes, see `shape::Shape`
+    pub model: Option<&'static str>,
+    /// Ticks between the block or a neighbour changing and its scheduled
+    /// tick. `None` if it doesn't react to changes.
+    pub tick_delay: Option<u64>,
+    pub on_scheduled_tick: Option<TickFn>,
+    /// Run when the block is picked at random, see `tick::BlockTicks`
+    pub on_random_tick: Option<TickFn>,
+}
+
+impl BlockDefinition {
+    const fn inert(name: &'static str, color: [u8; 3]) -> Self {
+        Self {
+            name,
+            color,
+            properties: &[],
+            model: None,
+            tick_delay: None,
+            on_scheduled_tick: None,
+            on_random_tick: None,
+        }
+    }
+}
+
+const NONE: BlockDefinition = BlockDefinition::inert("none", [0, 0, 0]);
+const AIR: BlockDefinition = BlockDefinition::inert("air", [0, 0, 0]);
+const STONE: BlockDefinition = BlockDefinition::inert("stone", [39, 41, 42]);
+const DIRT: BlockDefinition = BlockDefinition::inert("dirt", [78, 59, 49]);
+const GLASS: BlockDefinition = BlockDefinition::inert("glass", [209, 228, 238]);
+const LEAVES: BlockDefinition = BlockDefinition::inert("leaves", [55, 125, 37]);
+const LOG: BlockDefinition = BlockDefinition {
+    properties: &[Property::Axis],
+    ..BlockDefinition::inert("log", [96, 70, 40])
+};
+const STONE_SLAB: BlockDefinition = BlockDefinition {
+    properties: &[Property::Half],
+    model: Some(include_str!("../models/slab.json")),
+    ..BlockDefinition::inert("stone_slab", [39, 41, 42])
+};
+const STONE_STAIRS: BlockDefinition = BlockDefinition {
+    properties: &[Property::Facing, Property::Half],
+    model: Some(include_str!("../models/stairs.json")),
+    ..BlockDefinition::inert("stone_stairs", [39, 41, 42])
+};
+const FLOWER: BlockDefinition = BlockDefinition {
+    model: Some(include_str!("../models/flower.json")),
+    ..BlockDefinition::inert("flower", [134, 96, 43])
+};
+const WATER: BlockDefinition = BlockDefinition {
+    name: "water",
+    color: [39, 89, 199],
+    properties: &[Property::Level],
+    model: None,
+    tick_delay: Some(fluid::WATER.tick_delay),
+    on_scheduled_tick: Some(fluid::flow),
+    on_random_tick: None,
+};
+const LAVA: BlockDefinition = BlockDefinition {
+    name: "lava",
+    color: [220, 105, 18],
+    properties: &[Property::Level],
+    model: None,
+    tick_delay: Some(fluid::LAVA.tick_delay),
+    on_scheduled_tick: Some(fluid::flow),
+    on_random_tick: None,
+};
+const SAND: BlockDefinition = BlockDefinition {
+    name: "sand",
+    color: [221, 201, 141],
+    properties: &[],
+    model: None,
+    tick_delay: Some(falling::FALL_DELAY),
+    on_scheduled_tick: Some(falling::start_falling),
+    on_random_tick: None,
+};
+const GRAVEL: BlockDefinition = BlockDefinition {
+    name: "gravel",
+    color: [123, 119, 115],
+    properties: &[],
+    model: None,
+    tick_delay: Some(falling::FALL_DELAY),
+    on_scheduled_tick: Some(falling::start_falling),
+    on_random_tick: None,
+};
+const GRASS: BlockDefinition = BlockDefinition {
+    name: "grass",
+    color: [76, 156, 48],
+    properties: &[],
+    model: None,
+    tick_delay: None,
+    on_scheduled_tick: None,
+    on_random_tick: Some(grass_random_tick),
+};
+
+/// Every kind that can be placed in a chunk.
+pub const KINDS: [BlockKind; 14] = [
+    BlockKind::Air,
+    BlockKind::Stone,
+    BlockKind::Dirt,
+    BlockKind::Glass,
+    BlockKind::Water,
+    BlockKind::Leaves,
+    BlockKind::Lava,
+    BlockKind::Grass,
+    BlockKind::Sand,
+    BlockKind::Gravel,
+    BlockKind::Log,
+    BlockKind::StoneSlab,
+    BlockKind::StoneStairs,
+    BlockKind::Flower,
+];
+
+/// The kind whose definition has this name.
+pub fn by_name(name: &str) -> Option<BlockKind> {
+    KINDS.into_iter().find(|k| definition(*k).name == name)
+}
+
+/// The full cube kind other than air whose average color is closest to
+/// `color`.
+pub fn by_color(color: [u8; 3]) -> BlockKind {
+    let distance = |kind: &BlockKind| {
+        let c = definition(*kind).color;
+        (0..3)
+            .map(|i| (c[i] as i32 - color[i] as i32).pow(2))
+            .sum::<i32>()
+    };
+    KINDS
+        .into_iter()
+        .filter(|k| *k != BlockKind::Air && definition(*k).model.is_none())
+        .min_by_key(distance)
+        .unwrap_or(BlockKind::Stone)
+}
+
+pub fn definition(kind: BlockKind) -> &'static BlockDefinition {
+    match kind {
+        BlockKind::None => &NONE,
+        BlockKind::Air => &AIR,
+        BlockKind::Stone => &STONE,
+        BlockKind::Dirt => &DIRT,
+        BlockKind::Glass => &GLASS,
+        BlockKind::Water => &WATER,
+        BlockKind::Leaves => &LEAVES,
+        BlockKind::Lava => &LAVA,
+        BlockKind::Grass => &GRASS,
+        BlockKind::Sand => &SAND,
+        BlockKind::Gravel => &GRAVEL,
+        BlockKind::Log => &LOG,
+        BlockKind::StoneSlab => &STONE_SLAB,
+        BlockKind::StoneStairs => &STONE_STAIRS,
+        BlockKind::Flower => &FLOWER,
+    }
+}
+
+/// Grass needs light: it dies under opaque blocks and fluids, and otherwise
+/// spreads to a random dirt block nearby that has light itself.
+fn grass_random_tick(ctx: &mut TickContext, pos: Position) {
+    if !lets_grass_grow(ctx, offset(pos, (0, 1, 0))) {
+        ctx.set(pos, BlockKind::Dirt, 0);
+        return;
+    }
+    let target = offset(
+        pos,
+        (
+            ctx.random(3) as i64 - 1,
+            ctx.random(5) as i64 - 3,
+            ctx.random(3) as i64 - 1,
+        ),
+    );
+    if ctx.kind_at(target) == BlockKind::Dirt && lets_grass_grow(ctx, offset(target, (0, 1, 0)))
+    {
+        ctx.set(target, BlockKind::Grass, 0);
+    }
+}
+
+fn lets_grass_grow(ctx: &TickContext, above: Position) -> bool {
+    let kind = ctx.kind_at(above);
+    !kind.is_fluid() && kind.transparency() != Transparency::Opaque
+}
@@ -0,0 +1,233 @@
+use noise::{Fbm, NoiseFn, Seedable};
+
+use crate::{
+    chunk::{self, BlockKind, Chunk, WorldPosition},
+    editor::Edit,
+};
+
+/// The volume a brush covers around its center.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrushShape {
+    Sphere,
+    Cube,
+    /// Upright, as tall as it is wide
+    Cylinder,
+}
+
+/// What a brush does to the blocks it covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrushMode {
+    /// Fills air with the brush block
+    Add,
+    /// Clears solid blocks
+    Subtract,
+    /// Fills hollows and clears bumps, following the majority of each
+    /// block's neighbours
+    Smooth,
+    /// Levels the ground to the height of the brush center
+    Flatten,
+    /// Paints the brush block over the surface in noisy patches
+    Noise,
+}
+
+impl BrushMode {
+    pub const ALL: [BrushMode; 5] = [
+        BrushMode::Add,
+        BrushMode::Subtract,
+        BrushMode::Smooth,
+        BrushMode::Flatten,
+        BrushMode::Noise,
+    ];
+    pub fn name(self) -> &'static str {
+        match self {
+            BrushMode::Add => "Add",
+            BrushMode::Subtract => "Subtract",
+            BrushMode::Smooth => "Smooth",
+            BrushMode::Flatten => "Flatten",
+            BrushMode::Noise => "Noise paint",
+        }
+    }
+}
+
+/// A sculpting brush. How `strength`, from 0 to 1, is used depends on the
+/// mode: Add and Subtract ignore it, Smooth needs a clearer majority as it
+/// drops, Flatten reaches less far from the center height and Noise covers
+/// less of the surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Brush {
+    pub shape: BrushShape,
+    pub mode: BrushMode,
+    /// In blocks from the center
+    pub radius: f32,
+    pub strength: f32,
+    /// Block added, or painted
+    pub kind: BlockKind,
+    /// Seed of the Noise pattern
+    pub seed: u32,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            shape: BrushShape::Sphere,
+            mode: BrushMode::Add,
+            radius: 3.0,
+            strength: 1.0,
+            kind: BlockKind::Stone,
+            seed: 0,
+        }
+    }
+}
+
+impl BrushShape {
+    pub const ALL: [BrushShape; 3] = [BrushShape::Sphere, BrushShape::Cube, BrushShape::Cylinder];
+    pub fn name(self) -> &'static str {
+        match self {
+            BrushShape::Sphere => "Sphere",
+            BrushShape::Cube => "Cube",
+            BrushShape::Cylinder => "Cylinder",
+        }
+    }
+    /// Whether an offset from the center is inside the shape.
+    pub fn contains(&self, radius: f32, (x, y, z): (i64, i64, i64)) -> bool {
+        let (x, y, z) = (x as f32, y as f32, z as f32);
+        match self {
+            BrushShape::Sphere => x * x + y * y + z * z <= radius * radius,
+            BrushShape::Cube => x.abs().max(y.abs()).max(z.abs()) <= radius,
+            BrushShape::Cylinder => x * x + z * z <= radius * radius && y.abs() <= radius,
+        }
+    }
+    /// Every position inside the shape around `center`, x first, then z,
+    /// then y.
+    pub fn positions(&self, center: WorldPosition, radius: f32) -> Vec<WorldPosition> {
+        let reach = radius.max(0.0).floor() as i64;
+        let mut positions = vec![];
+        for y in -reach..=reach {
+            for z in -reach..=reach {
+                for x in -reach..=reach {
+                    if self.contains(radius, (x, y, z)) {
+                        positions.push((center.0 + x, center.1 + y, center.2 + z));
+                    }
+                }
+            }
+        }
+        positions
+    }
+}
+
+/// Blocks that count as ground. Fluids don't, so brushes reshape the terrain
+/// under lakes without pouring or draining them.
+fn solid(kind: BlockKind) -> bool {
+    !matches!(
+        kind,
+        BlockKind::None | BlockKind::Air | BlockKind::Water | BlockKind::Lava
+    )
+}
+
+/// The edits one stroke of the brush makes around `center`. Reads the world
+/// as it was before the stroke, so the result doesn't depend on the order
+/// blocks are visited in.
+pub fn stroke(chunks: &[Chunk], brush: &Brush, center: WorldPosition) -> Vec<Edit> {
+    let positions = brush.shape.positions(center, brush.radius);
+    let kind_at = |pos| chunk::world_block(chunks, pos);
+    match brush.mode {
+        BrushMode::Add => positions
+            .into_iter()
+            .filter(|pos| matches!(kind_at(*pos), BlockKind::None | BlockKind::Air))
+            .map(|pos| (pos, brush.kind.into()))
+            .collect(),
+        BrushMode::Subtract => positions
+            .into_iter()
+            .filter(|pos| solid(kind_at(*pos)))
+            .map(|pos| (pos, BlockKind::Air.into()))
+            .collect(),
+        BrushMode::Smooth => smooth(chunks, brush, positions),
+        BrushMode::Flatten => {
+            let reach = brush.strength.clamp(0.0, 1.0) * brush.radius;
+            positions
+                .into_iter()
+                .filter(|pos| ((pos.1 - center.1) as f32).abs() <= reach)
+                .filter_map(|pos| {
+                    let kind = kind_at(pos);
+                    if pos.1 > center.1 && solid(kind) {
+                        Some((pos, BlockKind::Air.into()))
+                    } else if pos.1 <= center.1 && !solid(kind) && kind != BlockKind::Water {
+                        Some((pos, brush.kind.into()))
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        }
+        BrushMode::Noise => {
+            let fbm = Fbm::new().set_seed(brush.seed);
+            // Noise runs from about -1 to 1
+            let cutoff = brush.strength.clamp(0.0, 1.0) as f64 * 2.0 - 1.0;
+            positions
+                .into_iter()
+                .filter(|pos| solid(kind_at(*pos)) && kind_at(*pos) != brush.kind)
+                .filter(|pos| exposed(chunks, *pos))
+                .filter(|pos| {
+                    let p = [pos.0 as f64 / 8.0, pos.1 as f64 / 8.0, pos.2 as f64 / 8.0];
+                    fbm.get(p) < cutoff
+                })
+                .map(|pos| (pos, brush.kind.into()))
+                .collect()
+        }
+    }
+}
+
+const SIDES: [(i64, i64, i64); 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+/// Whether any side of the block touches something that isn't ground.
+fn exposed(chunks: &[Chunk], pos: WorldPosition) -> bool {
+    SIDES.iter().any(|d| {
+        !solid(chunk::world_block(
+            chunks,
+            (pos.0 + d.0, pos.1 + d.1, pos.2 + d.2),
+        ))
+    })
+}
+
+/// Sets each block to what most of the 27 blocks around it, itself
+/// included, are. Filled blocks take the most common neighbouring kind.
+fn smooth(chunks: &[Chunk], brush: &Brush, positions: Vec<WorldPosition>) -> Vec<Edit> {
+    // At full strength a bare majority flips a block, at none nothing does
+    let margin = (1.0 - brush.strength.clamp(0.0, 1.0)) * 13.5;
+    let mut edits = vec![];
+    for pos in positions {
+        let mut counts: Vec<(BlockKind, usize)> = vec![];
+        let mut filled = 0;
+        for dy in -1..=1 {
+            for dz in -1..=1 {
+                for dx in -1..=1 {
+                    let kind = chunk::world_block(chunks, (pos.0 + dx, pos.1 + dy, pos.2 + dz));
+                    if !solid(kind) {
+                        continue;
+                    }
+                    filled += 1;
+                    match counts.iter_mut().find(|(k, _)| *k == kind) {
+                        Some((_, n)) => *n += 1,
+                        None => counts.push((kind, 1)),
+                    }
+                }
+            }
+        }
+        let kind = chunk::world_block(chunks, pos);
+        let filled = filled as f32;
+        if solid(kind) && filled < 13.5 - margin {
+            edits.push((pos, BlockKind::Air.into()));
+        } else if !solid(kind) && kind != BlockKind::Water && filled > 13.5 + margin {
+            let most = counts.iter().max_by_key(|(_, n)| *n).map(|(k, _)| *k);
+            edits.push((pos, most.unwrap_or(brush.kind).into()));
+        }
+    }
+    edits
+}
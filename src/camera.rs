@@ -1,8 +1,10 @@
+#[cfg(feature = "app")]
 use winit::{
     dpi::PhysicalPosition,
     event::{ElementState, KeyboardInput, TouchPhase, VirtualKeyCode, WindowEvent},
 };
 
+#[derive(Debug, Clone, Copy)]
 pub struct Camera {
     pub eye: cgmath::Point3<f32>,
     pub target: cgmath::Point3<f32>,
@@ -31,6 +33,7 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     0.0, 0.0, 0.5, 0.0,
     0.0, 0.0, 0.5, 1.0,
 );
+#[cfg(feature = "app")]
 pub struct CameraController {
     pub speed: f32,
     is_forward_pressed: bool,
@@ -40,6 +43,7 @@ pub struct CameraController {
     last_position: Option<PhysicalPosition<f64>>,
 }
 
+#[cfg(feature = "app")]
 impl CameraController {
     pub fn new(speed: f32) -> Self {
         Self {
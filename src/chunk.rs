@@ -1,19 +1,124 @@
 use std::{collections::HashMap, sync::Arc};
 
-use cgmath::{vec3, Vector3, Vector2, vec2};
+use cgmath::{vec3, Vector3, Vector2, vec2, Matrix3, SquareMatrix};
 use wgpu::{BindGroup, Device};
 
-use crate::model::{Faces, Model, ModelData};
+use crate::{
+    fluid,
+    model::{Faces, Model, ModelData},
+    shape::{self, Shape, ShapeFace},
+    state::{BlockState, Facing},
+    vertex::Vertex,
+};
 
 pub const WIDTH: i64 = 16;
 pub const HEIGHT: i64 = 16;
 pub const LENGTH: i64 = 16;
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+/// Block coordinates in the world, across chunks.
+pub type WorldPosition = (i64, i64, i64);
+
+/// The chunk holding a world position and the local position inside it.
+pub fn split_world_position(pos: WorldPosition) -> ((i64, i64, i64), (i64, i64, i64)) {
+    (
+        (pos.0.div_euclid(WIDTH), pos.1.div_euclid(HEIGHT), pos.2.div_euclid(LENGTH)),
+        (pos.0.rem_euclid(WIDTH), pos.1.rem_euclid(HEIGHT), pos.2.rem_euclid(LENGTH)),
+    )
+}
+
+/// The block at a world position, `BlockKind::None` where there is no chunk.
+pub fn world_block(chunks: &[Chunk], pos: WorldPosition) -> BlockKind {
+    let (key, (x, y, z)) = split_world_position(pos);
+    chunks
+        .iter()
+        .find(|c| c.position() == key)
+        .and_then(|c| c.get_block(x, y, z))
+        .map(|b| b.kind)
+        .unwrap_or(BlockKind::None)
+}
+
+/// Sets the block at a world position, adding an empty chunk for it if
+/// needed.
+pub fn set_world_block(chunks: &mut Vec<Chunk>, kind: BlockKind, pos: WorldPosition) {
+    set_world_block_data(chunks, kind.into(), pos);
+}
+
+/// The whole block at a world position, `BlockKind::None` where there is no
+/// chunk.
+pub fn world_block_data(chunks: &[Chunk], pos: WorldPosition) -> BlockData {
+    let (key, (x, y, z)) = split_world_position(pos);
+    chunks
+        .iter()
+        .find(|c| c.position() == key)
+        .and_then(|c| c.get_block(x, y, z))
+        .map(Block::data)
+        .unwrap_or_else(|| BlockKind::None.into())
+}
+
+/// Sets the whole block at a world position, adding an empty chunk for it
+/// if needed.
+pub fn set_world_block_data(chunks: &mut Vec<Chunk>, data: BlockData, pos: WorldPosition) {
+    let (key, (x, y, z)) = split_world_position(pos);
+    let index = match chunks.iter().position(|c| c.position() == key) {
+        Some(index) => index,
+        None => {
+            chunks.push(Chunk::new(key.0, key.1, key.2));
+            chunks.len() - 1
+        }
+    };
+    chunks[index].set_block(Block::with_data(data, x, y, z));
+}
+
+/// The state of the block at a world position, the default where there is
+/// no chunk.
+pub fn world_state(chunks: &[Chunk], pos: WorldPosition) -> BlockState {
+    let (key, (x, y, z)) = split_world_position(pos);
+    chunks
+        .iter()
+        .find(|c| c.position() == key)
+        .and_then(|c| c.get_block(x, y, z))
+        .map(|b| b.state)
+        .unwrap_or_default()
+}
+
+/// Sets the state of the block at a world position, keeping its kind. Does
+/// nothing where there is no chunk.
+pub fn set_world_state(chunks: &mut [Chunk], state: BlockState, pos: WorldPosition) {
+    let (key, (x, y, z)) = split_world_position(pos);
+    if let Some(chunk) = chunks.iter_mut().find(|c| c.position() == key) {
+        if let Some(block) = chunk.get_block(x, y, z).copied() {
+            chunk.set_block(Block { state, ..block });
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BlockKind {
     None,
     Air,
     Stone,
-    Dirt
+    Dirt,
+    Glass,
+    Water,
+    Leaves,
+    Lava,
+    Grass,
+    Sand,
+    Gravel,
+    Log,
+    StoneSlab,
+    StoneStairs,
+    Flower,
+}
+/// How a block lets light through, which decides its pipeline and which of
+/// its neighbours' faces stay visible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transparency {
+    Opaque,
+    /// Either fully solid or fully clear per texel, drawn with an alpha test
+    Cutout,
+    /// Blended over what's behind it, drawn last and back to front
+    Translucent,
 }
 #[derive(Debug, Clone, Copy)]
 pub struct Block {
@@ -21,6 +126,87 @@ pub struct Block {
     pub x: i64,
     pub y: i64,
     pub z: i64,
+    /// Flow level of fluids, see `fluid::SOURCE_LEVEL`. Unused otherwise.
+    pub level: u8,
+    /// Facing, axis and half, for kinds that declare them
+    pub state: BlockState,
+}
+impl Block {
+    pub fn new(kind: BlockKind, x: i64, y: i64, z: i64) -> Self {
+        Self {
+            kind,
+            x,
+            y,
+            z,
+            level: 0,
+            state: BlockState::default(),
+        }
+    }
+    pub fn with_data(data: BlockData, x: i64, y: i64, z: i64) -> Self {
+        Self {
+            kind: data.kind,
+            x,
+            y,
+            z,
+            level: data.level,
+            state: data.state,
+        }
+    }
+    /// Everything about the block but where it is.
+    pub fn data(&self) -> BlockData {
+        BlockData {
+            kind: self.kind,
+            state: self.state,
+            level: self.level,
+        }
+    }
+}
+
+/// A block without its position, as kept by edits, undo and the clipboard
+/// so turned blocks and flowing fluids come back as they were.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockData {
+    pub kind: BlockKind,
+    pub state: BlockState,
+    /// See `Block::level`
+    pub level: u8,
+}
+
+/// The kind in its default state, as placed by hand.
+impl From<BlockKind> for BlockData {
+    fn from(kind: BlockKind) -> Self {
+        Self {
+            kind,
+            state: BlockState::default(),
+            level: 0,
+        }
+    }
+}
+
+/// Vertices and indexes of a model that isn't instanced cubes.
+pub type Geometry = (Vec<Vertex>, Vec<u16>);
+
+/// Everything `Chunk::models` uploads, in world coordinates.
+pub struct ChunkGeometry {
+    /// Opaque and cutout cubes, by the faces they show
+    pub instances: HashMap<(Transparency, Faces), Vec<ModelData>>,
+    /// Opaque and cutout blocks that aren't cubes, split where their
+    /// vertices would outgrow u16 indices
+    pub shaped: HashMap<(Transparency, BlockKind), Vec<Geometry>>,
+    /// Quads of every translucent face, see `Model::sorted`
+    pub translucent: Vec<Vertex>,
+    /// Middle of the translucent blocks
+    pub translucent_center: Vector3<f32>,
+}
+
+/// GPU models of one chunk, split by transparency class.
+pub struct ChunkMesh {
+    pub opaque: Vec<Model>,
+    pub cutout: Vec<Model>,
+    /// Every translucent face, sorted with `Model::sort_back_to_front`
+    pub translucent: Option<Model>,
+    /// Middle of the translucent blocks, used to sort chunks back to front
+    pub translucent_center: Vector3<f32>,
 }
 
 pub struct Chunk {
@@ -36,12 +222,7 @@ impl Chunk {
             blocks: (0..(WIDTH * HEIGHT * LENGTH))
                 .map(|i| {
                     let (bx, by, bz) = Self::unflatten(i);
-                    Block {
-                        kind: BlockKind::Air,
-                        x: bx,
-                        y: by,
-                        z: bz,
-                    }
+                    Block::new(BlockKind::Air, bx, by, bz)
                 })
                 .collect::<Vec<Block>>(),
             chunk_x: x,
@@ -55,6 +236,26 @@ impl Chunk {
     pub fn set_block(&mut self, b: Block) {
         self.blocks[Self::flatten(b.x, b.y, b.z)] = b;
     }
+    /// Whether local coordinates fall inside the chunk. `get_block` doesn't
+    /// check each axis, so out of range coordinates can wrap to another block.
+    pub fn contains(x: i64, y: i64, z: i64) -> bool {
+        (0..WIDTH).contains(&x) && (0..HEIGHT).contains(&y) && (0..LENGTH).contains(&z)
+    }
+    /// Chunk coordinates, in chunks rather than blocks.
+    pub fn position(&self) -> (i64, i64, i64) {
+        (self.chunk_x, self.chunk_y, self.chunk_z)
+    }
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.iter()
+    }
+    /// World position of the chunk's first block.
+    pub fn origin(&self) -> Vector3<f32> {
+        vec3(
+            (self.chunk_x * WIDTH) as f32,
+            (self.chunk_y * HEIGHT) as f32,
+            (self.chunk_z * LENGTH) as f32,
+        )
+    }
     pub fn get_block_kinds(&self) -> Vec<BlockKind> {
         self.blocks.iter().map(|b| b.kind).collect()
     }
@@ -69,67 +270,140 @@ impl Chunk {
         let x = idx % WIDTH;
         (x, y, z)
     }
-    fn block_north_of(&self, b: &Block) -> BlockKind {
-        self.get_block(b.x, b.y, b.z + 1)
+    fn block_above(&self, b: &Block) -> BlockKind {
+        self.neighbour(b, Facing::Up)
             .map(|s| s.kind)
             .unwrap_or(BlockKind::None)
     }
-    fn block_south_of(&self, b: &Block) -> BlockKind {
-        self.get_block(b.x, b.y, b.z - 1)
-            .map(|s| s.kind)
-            .unwrap_or(BlockKind::None)
+    /// The block next to `block` on its `side`, `None` past the edge of the
+    /// chunk rather than a block wrapped around from another row.
+    fn neighbour(&self, block: &Block, side: Facing) -> Option<&Block> {
+        let (x, y, z) = side.offset();
+        let (x, y, z) = (block.x + x, block.y + y, block.z + z);
+        if Self::contains(x, y, z) {
+            self.get_block(x, y, z)
+        } else {
+            None
+        }
     }
-    fn block_east_of(&self, b: &Block) -> BlockKind {
-        self.get_block(b.x + 1, b.y, b.z)
-            .map(|s| s.kind)
-            .unwrap_or(BlockKind::None)
+    /// Whether `area` of the block's `side` is hidden by the neighbour there,
+    /// see `ShapeFace::area`. The neighbour's kind has to hide the block's
+    /// faces and its own faces have to cover all of the area.
+    fn hides(&self, block: &Block, side: Facing, area: [f32; 4]) -> bool {
+        match self.neighbour(block, side) {
+            Some(n) => {
+                !block.kind.shows_face_towards(n.kind)
+                    && shape::covers(n.kind, n.state, side.opposite(), area)
+                    // Fluid lower than the block leaves the top of its sides
+                    // showing, but covers what's under it
+                    && (side == Facing::Up || self.block_height(n) >= self.block_height(block))
+            }
+            None => !block.kind.shows_face_towards(BlockKind::None),
+        }
     }
-    fn block_west_of(&self, b: &Block) -> BlockKind {
-        self.get_block(b.x - 1, b.y, b.z)
-            .map(|s| s.kind)
-            .unwrap_or(BlockKind::None)
+    /// Faces of the block that aren't hidden by its neighbours.
+    pub(crate) fn visible_faces(&self, block: &Block) -> Faces {
+        let shown = |side| !self.hides(block, side, shape::WHOLE_SIDE);
+        Faces {
+            north: shown(Facing::North),
+            south: shown(Facing::South),
+            east: shown(Facing::East),
+            west: shown(Facing::West),
+            top: shown(Facing::Up),
+            bottom: shown(Facing::Down),
+        }
     }
-    fn block_above(&self, b: &Block) -> BlockKind {
-        self.get_block(b.x, b.y + 1, b.z)
-            .map(|s| s.kind)
-            .unwrap_or(BlockKind::None)
+    /// Faces of a block that isn't a full cube that aren't hidden by its
+    /// neighbours. Faces inside the block are always kept.
+    pub(crate) fn visible_shape_faces(&self, block: &Block, shape: &Shape) -> Vec<ShapeFace> {
+        shape
+            .faces(block.state)
+            .into_iter()
+            .filter(|face| match face.area() {
+                Some((side, area)) => !self.hides(block, side, area),
+                None => true,
+            })
+            .collect()
     }
-    fn block_below(&self, b: &Block) -> BlockKind {
-        self.get_block(b.x, b.y - 1, b.z)
-            .map(|s| s.kind)
-            .unwrap_or(BlockKind::None)
+    /// How far up the block reaches, less than 1 for fluids that aren't
+    /// full.
+    pub fn block_height(&self, block: &Block) -> f32 {
+        if block.kind.is_fluid() {
+            fluid::surface_height(block.level, self.block_above(block) == block.kind)
+        } else {
+            1.0
+        }
     }
-    pub fn models(&self, device: &Device, bind_group: Arc<BindGroup>) -> Vec<Model> {
+    /// The chunk's blocks as instanced cubes and meshes, placed at the
+    /// chunk's origin.
+    pub fn geometry(&self) -> ChunkGeometry {
+        let origin = self.origin();
         // let mut models = vec![];
-        let mut hm: HashMap<Faces, Vec<ModelData>> = HashMap::new();
+        let mut hm: HashMap<(Transparency, Faces), Vec<ModelData>> = HashMap::new();
+        // Blocks that aren't cubes are meshed in place rather than instanced
+        let mut shaped: HashMap<(Transparency, BlockKind), Vec<Geometry>> = HashMap::new();
+        // Translucent faces of every kind go in one model per chunk, so they
+        // can be sorted back to front together
+        let mut translucent: Vec<Vertex> = vec![];
+        let mut translucent_sum = vec3(0.0, 0.0, 0.0);
+        let mut translucent_count = 0;
 
         for block in &self.blocks {
             if let BlockKind::Air = block.kind {
-            } else {
-                let north = self.block_north_of(block) == BlockKind::Air;
-                let south = self.block_south_of(block) == BlockKind::Air;
-                let east = self.block_east_of(block) == BlockKind::Air;
-                let west = self.block_west_of(block) == BlockKind::Air;
-                let above = self.block_above(block) == BlockKind::Air;
-                let below = self.block_below(block) == BlockKind::Air;
-                let f = Faces {
-                    north,
-                    south,
-                    east,
-                    west,
-                    bottom: below,
-                    top: above,
+                continue;
+            }
+            let pos = origin + vec3(block.x as f32, block.y as f32, block.z as f32);
+            let transparency = block.kind.transparency();
+            if transparency == Transparency::Translucent {
+                translucent_sum += pos;
+                translucent_count += 1;
+                let faces = match shape::of(block.kind) {
+                    Some(shape) => self.visible_shape_faces(block, &shape),
+                    None => shape::cube_faces(
+                        &block.state.model_faces(&self.visible_faces(block)),
+                        self.block_height(block),
+                        block.state.rotation(),
+                    ),
                 };
+                let tile = block.kind.get_tex_coords();
+                for face in faces {
+                    for (p, tc) in face.positions.iter().zip(face.tex_coords) {
+                        translucent.push(Vertex::new(
+                            ((pos + p) * 2.0).into(),
+                            [tc[0] + tile.x, tc[1] + tile.y],
+                            face.normal.into(),
+                        ));
+                    }
+                }
+            } else if let Some(shape) = shape::of(block.kind) {
+                let parts = shaped.entry((transparency, block.kind)).or_default();
+                for face in self.visible_shape_faces(block, &shape) {
+                    match parts.last() {
+                        Some((verts, _)) if verts.len() + 4 <= u16::MAX as usize => {}
+                        _ => parts.push((vec![], vec![])),
+                    }
+                    let (verts, indexes) = parts.last_mut().unwrap();
+                    let first = verts.len() as u16;
+                    for (p, tc) in face.positions.iter().zip(face.tex_coords) {
+                        // Models are drawn at half size, see `Model`
+                        verts.push(Vertex::new(((pos + p) * 2.0).into(), tc, face.normal.into()));
+                    }
+                    indexes.extend([0, 1, 2, 2, 3, 0].map(|i| first + i));
+                }
+            } else {
+                let f = block.state.model_faces(&self.visible_faces(block));
                 // let m = Model::new(
                 //     &device,
                 //     &f,
                 //     vec![vec3(block.x as f32, block.y as f32, block.z as f32)],
                 //     bind_group.clone(),
                 // );
-                let pos = vec3(block.x as f32 , block.y as f32, block.z as f32);
-                hm.entry(f).or_insert(vec![]).push(ModelData {
+                let height = self.block_height(block);
+                hm.entry((transparency, f)).or_insert(vec![]).push(ModelData {
                     position: pos,
-                    kind: block.kind
+                    kind: block.kind,
+                    size: vec3(1.0, height, 1.0),
+                    rotation: block.state.rotation(),
                 });
                 // hm.insert(f, );
                 // models.push(Model::new(
@@ -140,14 +414,59 @@ impl Chunk {
                 // ));
             }
         }
-        let mut moleds = vec![];
-        println!("{} unique faces", hm.keys().len());
+        ChunkGeometry {
+            instances: hm,
+            shaped,
+            translucent,
+            translucent_center: translucent_sum / translucent_count.max(1) as f32,
+        }
+    }
+    pub fn models(&self, device: &Device, bind_group: Arc<BindGroup>) -> ChunkMesh {
+        let geometry = self.geometry();
+        let mut mesh = ChunkMesh {
+            opaque: vec![],
+            cutout: vec![],
+            translucent: None,
+            translucent_center: geometry.translucent_center,
+        };
+        if !geometry.translucent.is_empty() {
+            mesh.translucent = Some(Model::sorted(
+                device,
+                &geometry.translucent,
+                bind_group.clone(),
+            ));
+        }
+        println!("{} unique faces", geometry.instances.keys().len());
         // let kinds = self.get_block_kinds();
-        for (faces, position) in hm {
-            moleds.push(Model::new(&device, &faces, position, bind_group.clone()))
+        for ((transparency, faces), position) in geometry.instances {
+            let model = Model::new(&device, &faces, position, bind_group.clone());
+            match transparency {
+                Transparency::Opaque => mesh.opaque.push(model),
+                _ => mesh.cutout.push(model),
+            }
+        }
+        for ((transparency, kind), parts) in geometry.shaped {
+            for (verts, indexes) in parts {
+                let model = Model::with_geometry(
+                    device,
+                    &verts,
+                    &indexes,
+                    vec![ModelData {
+                        position: vec3(0.0, 0.0, 0.0),
+                        kind,
+                        size: vec3(1.0, 1.0, 1.0),
+                        rotation: Matrix3::identity(),
+                    }],
+                    bind_group.clone(),
+                );
+                match transparency {
+                    Transparency::Opaque => mesh.opaque.push(model),
+                    _ => mesh.cutout.push(model),
+                }
+            }
         }
 
-        moleds
+        mesh
     }
 }
 impl BlockKind {
@@ -156,7 +475,55 @@ impl BlockKind {
                 BlockKind::None => todo!(),
                 BlockKind::Air => todo!(),
                 BlockKind::Stone => vec2(0.0, 0.0),
-                BlockKind::Dirt => vec2(1.0, 0.0)
+                BlockKind::Dirt => vec2(1.0, 0.0),
+                BlockKind::Glass => vec2(2.0, 0.0),
+                BlockKind::Water => vec2(3.0, 0.0),
+                BlockKind::Leaves => vec2(4.0, 0.0),
+                BlockKind::Lava => vec2(5.0, 0.0),
+                BlockKind::Grass => vec2(6.0, 0.0),
+                BlockKind::Sand => vec2(7.0, 0.0),
+                BlockKind::Gravel => vec2(8.0, 0.0),
+                BlockKind::Log => vec2(9.0, 0.0),
+                BlockKind::StoneSlab | BlockKind::StoneStairs => vec2(0.0, 0.0),
+                BlockKind::Flower => vec2(10.0, 0.0),
             }
     }
+    pub fn transparency(&self) -> Transparency {
+        match self {
+            // Never meshed, but see-through as far as neighbours are concerned
+            BlockKind::None | BlockKind::Air => Transparency::Translucent,
+            BlockKind::Stone
+            | BlockKind::Dirt
+            | BlockKind::Grass
+            | BlockKind::Sand
+            | BlockKind::Gravel
+            | BlockKind::Log
+            | BlockKind::StoneSlab
+            | BlockKind::StoneStairs => Transparency::Opaque,
+            BlockKind::Leaves | BlockKind::Flower => Transparency::Cutout,
+            // Flowing lava is shorter than a block, but still opaque, see
+            // `Chunk::hides`
+            BlockKind::Lava => Transparency::Opaque,
+            BlockKind::Glass | BlockKind::Water => Transparency::Translucent,
+        }
+    }
+    pub fn is_fluid(&self) -> bool {
+        matches!(self, BlockKind::Water | BlockKind::Lava)
+    }
+    /// Whether the face of this block touching `neighbour` is drawn. Opaque
+    /// neighbours hide it, cutout ones never do, and translucent ones only
+    /// hide faces between blocks of the same kind, so water doesn't draw the
+    /// walls between its own blocks.
+    pub fn shows_face_towards(&self, neighbour: BlockKind) -> bool {
+        match neighbour {
+            // Outside the chunk
+            BlockKind::None => false,
+            BlockKind::Air => true,
+            _ => match neighbour.transparency() {
+                Transparency::Opaque => false,
+                Transparency::Cutout => true,
+                Transparency::Translucent => *self != neighbour,
+            },
+        }
+    }
 }
\ No newline at end of file
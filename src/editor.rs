@@ -0,0 +1,259 @@
+use crate::{
+    chunk::{self, BlockData, BlockKind, Chunk, WorldPosition},
+    journal,
+    state::{Axis, Facing},
+};
+
+/// A block to set and the position to set it at.
+pub type Edit = (WorldPosition, BlockData);
+
+/// A box of blocks between two corners, both included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    pub min: WorldPosition,
+    pub max: WorldPosition,
+}
+
+impl Selection {
+    /// The box spanned by two opposite corners, in any order.
+    pub fn new(a: WorldPosition, b: WorldPosition) -> Self {
+        Self {
+            min: (a.0.min(b.0), a.1.min(b.1), a.2.min(b.2)),
+            max: (a.0.max(b.0), a.1.max(b.1), a.2.max(b.2)),
+        }
+    }
+    /// Blocks along x, y and z.
+    pub fn size(&self) -> (i64, i64, i64) {
+        (
+            self.max.0 - self.min.0 + 1,
+            self.max.1 - self.min.1 + 1,
+            self.max.2 - self.min.2 + 1,
+        )
+    }
+    pub fn volume(&self) -> i64 {
+        let (x, y, z) = self.size();
+        x * y * z
+    }
+    pub fn contains(&self, pos: WorldPosition) -> bool {
+        (self.min.0..=self.max.0).contains(&pos.0)
+            && (self.min.1..=self.max.1).contains(&pos.1)
+            && (self.min.2..=self.max.2).contains(&pos.2)
+    }
+    /// Every position inside, x first, then z, then y.
+    pub fn positions(&self) -> impl Iterator<Item = WorldPosition> {
+        let (min, max) = (self.min, self.max);
+        (min.1..=max.1).flat_map(move |y| {
+            (min.2..=max.2).flat_map(move |z| (min.0..=max.0).map(move |x| (x, y, z)))
+        })
+    }
+    /// How many blocks inside are of `kind`.
+    pub fn count(&self, chunks: &[Chunk], kind: BlockKind) -> usize {
+        self.positions()
+            .filter(|p| chunk::world_block(chunks, *p) == kind)
+            .count()
+    }
+}
+
+/// Quarter turns around the y axis, then mirroring, applied when pasting.
+/// Mirroring is done in the pasted frame, after the turns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Transform {
+    /// Counter-clockwise seen from above, with north at +z like `Facing`,
+    /// so one turn takes east to north. 0 to 3
+    pub quarter_turns: u8,
+    pub mirror_x: bool,
+    pub mirror_z: bool,
+}
+
+impl Transform {
+    /// Where a direction along x and z points afterwards.
+    fn direction(&self, (mut dx, mut dz): (i64, i64)) -> (i64, i64) {
+        for _ in 0..self.quarter_turns % 4 {
+            // +x to +z, the same turn as positions in `Clipboard::transformed`
+            let turned = (-dz, dx);
+            dx = turned.0;
+            dz = turned.1;
+        }
+        if self.mirror_x {
+            dx = -dx;
+        }
+        if self.mirror_z {
+            dz = -dz;
+        }
+        (dx, dz)
+    }
+    /// The block turned and mirrored along with its position, so it still
+    /// faces the same way relative to the rest of the clipboard.
+    fn block(&self, data: BlockData) -> BlockData {
+        let facing = data.state.facing();
+        let (x, _, z) = facing.offset();
+        let (x, z) = self.direction((x, z));
+        let facing = Facing::ALL
+            .into_iter()
+            .find(|f| f.offset() == (x, 0, z))
+            .unwrap_or(facing);
+        let axis = match (data.state.axis(), self.quarter_turns % 2) {
+            (Axis::X, 1) => Axis::Z,
+            (Axis::Z, 1) => Axis::X,
+            (axis, _) => axis,
+        };
+        BlockData {
+            state: data.state.with_facing(facing).with_axis(axis),
+            ..data
+        }
+    }
+}
+
+/// Blocks copied out of the world, air included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clipboard {
+    pub size: (i64, i64, i64),
+    /// x first, then z, then y, like `Selection::positions`
+    pub blocks: Vec<BlockData>,
+}
+
+impl Clipboard {
+    fn index(&self, x: i64, y: i64, z: i64) -> usize {
+        ((y * self.size.2 + z) * self.size.0 + x) as usize
+    }
+    /// The block at a position relative to the clipboard's lowest corner.
+    pub fn get(&self, x: i64, y: i64, z: i64) -> BlockData {
+        let (sx, sy, sz) = self.size;
+        if (0..sx).contains(&x) && (0..sy).contains(&y) && (0..sz).contains(&z) {
+            self.blocks[self.index(x, y, z)]
+        } else {
+            BlockKind::None.into()
+        }
+    }
+    /// A copy turned and mirrored, with its lowest corner still at zero.
+    /// Blocks that face a way or run along an axis are turned too.
+    pub fn transformed(&self, transform: Transform) -> Clipboard {
+        let turns = transform.quarter_turns % 4;
+        let (sx, sy, sz) = self.size;
+        let size = if turns % 2 == 1 {
+            (sz, sy, sx)
+        } else {
+            self.size
+        };
+        let mut out = Clipboard {
+            size,
+            blocks: vec![BlockKind::Air.into(); self.blocks.len()],
+        };
+        for y in 0..sy {
+            for z in 0..sz {
+                for x in 0..sx {
+                    // Counter-clockwise from above, with x east and z north,
+                    // takes east to north
+                    let (mut tx, mut tz) = match turns {
+                        0 => (x, z),
+                        1 => (sz - 1 - z, x),
+                        2 => (sx - 1 - x, sz - 1 - z),
+                        _ => (z, sx - 1 - x),
+                    };
+                    if transform.mirror_x {
+                        tx = size.0 - 1 - tx;
+                    }
+                    if transform.mirror_z {
+                        tz = size.2 - 1 - tz;
+                    }
+                    let index = out.index(tx, y, tz);
+                    out.blocks[index] = transform.block(self.get(x, y, z));
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Sets every block of the selection to `kind`.
+pub fn fill(selection: &Selection, kind: BlockKind) -> Vec<Edit> {
+    selection
+        .positions()
+        .map(|pos| (pos, kind.into()))
+        .collect()
+}
+
+/// Turns blocks of one kind in the selection into another.
+pub fn replace(
+    chunks: &[Chunk],
+    selection: &Selection,
+    from: BlockKind,
+    to: BlockKind,
+) -> Vec<Edit> {
+    selection
+        .positions()
+        .filter(|pos| chunk::world_block(chunks, *pos) == from)
+        .map(|pos| (pos, to.into()))
+        .collect()
+}
+
+/// Clears blocks in the selection that are buried on all six sides, leaving
+/// a shell one block thick around anything solid.
+pub fn hollow(chunks: &[Chunk], selection: &Selection) -> Vec<Edit> {
+    const SIDES: [(i64, i64, i64); 6] = [
+        (1, 0, 0),
+        (-1, 0, 0),
+        (0, 1, 0),
+        (0, -1, 0),
+        (0, 0, 1),
+        (0, 0, -1),
+    ];
+    let filled = |pos: WorldPosition| {
+        !matches!(
+            chunk::world_block(chunks, pos),
+            BlockKind::Air | BlockKind::None
+        )
+    };
+    selection
+        .positions()
+        .filter(|pos| {
+            filled(*pos)
+                && SIDES
+                    .iter()
+                    .all(|d| filled((pos.0 + d.0, pos.1 + d.1, pos.2 + d.2)))
+        })
+        .map(|pos| (pos, BlockKind::Air.into()))
+        .collect()
+}
+
+/// Copies the selection. Positions without a chunk are copied as air.
+pub fn copy(chunks: &[Chunk], selection: &Selection) -> Clipboard {
+    let blocks = selection
+        .positions()
+        .map(|pos| match chunk::world_block_data(chunks, pos) {
+            data if data.kind == BlockKind::None => BlockKind::Air.into(),
+            data => data,
+        })
+        .collect();
+    Clipboard {
+        size: selection.size(),
+        blocks,
+    }
+}
+
+/// Places the clipboard with its lowest corner at `origin`. Air in the
+/// clipboard is skipped unless `with_air` is set.
+pub fn paste(
+    clipboard: &Clipboard,
+    origin: WorldPosition,
+    transform: Transform,
+    with_air: bool,
+) -> Vec<Edit> {
+    let clipboard = clipboard.transformed(transform);
+    let (sx, sy, sz) = clipboard.size;
+    let selection = Selection::new(
+        origin,
+        (origin.0 + sx - 1, origin.1 + sy - 1, origin.2 + sz - 1),
+    );
+    selection
+        .positions()
+        .zip(clipboard.blocks)
+        .filter(|(_, data)| with_air || data.kind != BlockKind::Air)
+        .collect()
+}
+
+/// Writes the edits into the chunks without recording them for undo, see
+/// `journal::write_edits`. Returns how many blocks actually changed.
+pub fn apply(chunks: &mut Vec<Chunk>, edits: &[Edit]) -> usize {
+    journal::write_edits(chunks, edits, None).blocks.len()
+}
@@ -0,0 +1,213 @@
+use std::collections::{BTreeMap, HashMap};
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::{chunk::BlockKind, obj::ObjModelId};
+
+/// Entities are filed into cubes of this many blocks, the size of a chunk.
+pub const REGION_SIZE: f32 = 16.0;
+
+pub type RegionKey = (i64, i64, i64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EntityId(u64);
+
+/// Axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Self {
+        Self { min, max }
+    }
+    pub fn from_center(center: Vector3<f32>, half_extents: Vector3<f32>) -> Self {
+        Self {
+            min: center - half_extents,
+            max: center + half_extents,
+        }
+    }
+    /// Touching boxes count as intersecting.
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+            && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+    /// Squared distance from `point` to the nearest point of the box, 0
+    /// inside it.
+    pub fn distance2(&self, point: Vector3<f32>) -> f32 {
+        let clamped = Vector3::new(
+            point.x.clamp(self.min.x, self.max.x),
+            point.y.clamp(self.min.y, self.max.y),
+            point.z.clamp(self.min.z, self.max.z),
+        );
+        (point - clamped).magnitude2()
+    }
+}
+
+/// What an entity looks like.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntityMesh {
+    /// A box the size of the entity, textured like a block
+    Block(BlockKind),
+    /// An OBJ model, standing on the bottom of the box
+    Obj(ObjModelId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Entity {
+    pub id: EntityId,
+    /// Center of the bounding box
+    pub position: Vector3<f32>,
+    /// Blocks per second
+    pub velocity: Vector3<f32>,
+    pub half_extents: Vector3<f32>,
+    pub mesh: EntityMesh,
+}
+
+impl Entity {
+    pub fn aabb(&self) -> Aabb {
+        Aabb::from_center(self.position, self.half_extents)
+    }
+}
+
+pub fn region_of(position: Vector3<f32>) -> RegionKey {
+    (
+        (position.x / REGION_SIZE).floor() as i64,
+        (position.y / REGION_SIZE).floor() as i64,
+        (position.z / REGION_SIZE).floor() as i64,
+    )
+}
+
+/// All entities of the world, filed by the region their center is in so
+/// box and radius queries only look at nearby regions.
+#[derive(Debug, Default)]
+pub struct Entities {
+    next_id: u64,
+    regions: BTreeMap<RegionKey, Vec<Entity>>,
+    lookup: HashMap<EntityId, RegionKey>,
+    /// Largest half extent spawned so far. Entities can stick out of their
+    /// region by this much, so queries look that much further.
+    max_half_extent: f32,
+}
+
+impl Entities {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn spawn(
+        &mut self,
+        position: Vector3<f32>,
+        velocity: Vector3<f32>,
+        half_extents: Vector3<f32>,
+        mesh: EntityMesh,
+    ) -> EntityId {
+        let id = EntityId(self.next_id);
+        self.next_id += 1;
+        self.max_half_extent = self
+            .max_half_extent
+            .max(half_extents.x)
+            .max(half_extents.y)
+            .max(half_extents.z);
+        self.file(Entity {
+            id,
+            position,
+            velocity,
+            half_extents,
+            mesh,
+        });
+        id
+    }
+    fn file(&mut self, entity: Entity) {
+        let key = region_of(entity.position);
+        self.lookup.insert(entity.id, key);
+        self.regions.entry(key).or_default().push(entity);
+    }
+    pub fn despawn(&mut self, id: EntityId) -> Option<Entity> {
+        let key = self.lookup.remove(&id)?;
+        let region = self.regions.get_mut(&key)?;
+        let index = region.iter().position(|e| e.id == id)?;
+        let entity = region.remove(index);
+        if region.is_empty() {
+            self.regions.remove(&key);
+        }
+        Some(entity)
+    }
+    pub fn clear(&mut self) {
+        self.regions.clear();
+        self.lookup.clear();
+    }
+    pub fn get(&self, id: EntityId) -> Option<&Entity> {
+        let key = self.lookup.get(&id)?;
+        self.regions.get(key)?.iter().find(|e| e.id == id)
+    }
+    /// Moving an entity this way doesn't refile it until the next `update`.
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        let key = self.lookup.get(&id)?;
+        self.regions.get_mut(key)?.iter_mut().find(|e| e.id == id)
+    }
+    pub fn len(&self) -> usize {
+        self.lookup.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.lookup.is_empty()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &Entity> {
+        self.regions.values().flatten()
+    }
+    /// Entities whose center is in the region.
+    pub fn region(&self, key: RegionKey) -> &[Entity] {
+        self.regions.get(&key).map(Vec::as_slice).unwrap_or(&[])
+    }
+    /// Moves every entity by its velocity and refiles the ones that crossed
+    /// into another region.
+    pub fn update(&mut self, seconds: f32) {
+        let mut moved = vec![];
+        for (key, region) in &mut self.regions {
+            region.retain_mut(|entity| {
+                entity.position += entity.velocity * seconds;
+                if region_of(entity.position) == *key {
+                    true
+                } else {
+                    moved.push(*entity);
+                    false
+                }
+            });
+        }
+        self.regions.retain(|_, region| !region.is_empty());
+        for entity in moved {
+            self.file(entity);
+        }
+    }
+    /// Entities whose bounding box touches `aabb`.
+    pub fn query_box(&self, aabb: &Aabb) -> Vec<&Entity> {
+        let margin = Vector3::new(1.0, 1.0, 1.0) * self.max_half_extent;
+        let (min, max) = (region_of(aabb.min - margin), region_of(aabb.max + margin));
+        let mut found = vec![];
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                for z in min.2..=max.2 {
+                    found.extend(
+                        self.region((x, y, z))
+                            .iter()
+                            .filter(|e| e.aabb().intersects(aabb)),
+                    );
+                }
+            }
+        }
+        found
+    }
+    /// Entities whose bounding box is within `radius` of `center`.
+    pub fn query_radius(&self, center: Vector3<f32>, radius: f32) -> Vec<&Entity> {
+        let reach = Vector3::new(radius, radius, radius);
+        self.query_box(&Aabb::from_center(center, reach))
+            .into_iter()
+            .filter(|e| e.aabb().distance2(center) <= radius * radius)
+            .collect()
+    }
+}
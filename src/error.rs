@@ -0,0 +1,36 @@
+use std::fmt;
+
+/// Reasons the renderer can fail to start or has to stop.
+#[derive(Debug)]
+pub enum RendererError {
+    /// No adapter matched the requested options or surface.
+    NoAdapter,
+    RequestDevice(wgpu::RequestDeviceError),
+    /// The GPU ran out of memory while acquiring a frame.
+    OutOfMemory,
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererError::NoAdapter => write!(f, "no suitable graphics adapter found"),
+            RendererError::RequestDevice(e) => write!(f, "failed to create device: {}", e),
+            RendererError::OutOfMemory => write!(f, "out of GPU memory"),
+        }
+    }
+}
+
+impl std::error::Error for RendererError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RendererError::RequestDevice(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for RendererError {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        RendererError::RequestDevice(e)
+    }
+}
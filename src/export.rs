@@ -0,0 +1,220 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    blocks,
+    chunk::{BlockKind, Chunk, Transparency},
+    mesh::{self, BlockMesh},
+    shape,
+};
+
+const ATLAS_PNG: &[u8] = include_bytes!("../atlas.png");
+
+const GLB_MAGIC: u32 = 0x4654_6c67;
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: u32 = 0x4e4f_534a;
+const CHUNK_BIN: u32 = 0x004e_4942;
+
+// glTF enums
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const NEAREST: u32 = 9728;
+const CLAMP_TO_EDGE: u32 = 33071;
+
+/// Accessors and buffer views over one binary buffer.
+#[derive(Default)]
+struct GlbBuffer {
+    bin: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl GlbBuffer {
+    fn push_view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.bin.len(),
+            "byteLength": bytes.len(),
+        });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        self.bin.extend_from_slice(bytes);
+        // Every view starts 4 byte aligned
+        while self.bin.len() % 4 != 0 {
+            self.bin.push(0);
+        }
+        self.views.push(view);
+        self.views.len() - 1
+    }
+    fn push_accessor(&mut self, mut accessor: Value, bytes: &[u8], target: u32) -> usize {
+        accessor["bufferView"] = json!(self.push_view(bytes, Some(target)));
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+    /// Adds the mesh as a primitive using `material`.
+    fn push_primitive(&mut self, mesh: &BlockMesh, material: usize) -> Value {
+        let (min, max) = mesh.bounds();
+        let count = mesh.positions.len();
+        let position = self.push_accessor(
+            json!({"componentType": FLOAT, "count": count, "type": "VEC3", "min": min, "max": max}),
+            bytemuck::cast_slice(&mesh.positions),
+            ARRAY_BUFFER,
+        );
+        let normal = self.push_accessor(
+            json!({"componentType": FLOAT, "count": count, "type": "VEC3"}),
+            bytemuck::cast_slice(&mesh.normals),
+            ARRAY_BUFFER,
+        );
+        let tex_coord = self.push_accessor(
+            json!({"componentType": FLOAT, "count": count, "type": "VEC2"}),
+            bytemuck::cast_slice(&mesh.tex_coords),
+            ARRAY_BUFFER,
+        );
+        let indices = self.push_accessor(
+            json!({"componentType": UNSIGNED_INT, "count": mesh.indices.len(), "type": "SCALAR"}),
+            bytemuck::cast_slice(&mesh.indices),
+            ELEMENT_ARRAY_BUFFER,
+        );
+        json!({
+            "attributes": {"POSITION": position, "NORMAL": normal, "TEXCOORD_0": tex_coord},
+            "indices": indices,
+            "material": material,
+        })
+    }
+}
+
+/// Every kind shares the atlas, they differ in name, how they blend and
+/// whether they're seen from behind.
+fn material(kind: BlockKind) -> Value {
+    let mut material = json!({
+        "name": blocks::definition(kind).name,
+        "pbrMetallicRoughness": {
+            "baseColorTexture": {"index": 0},
+            "metallicFactor": 0.0,
+            "roughnessFactor": 1.0,
+        },
+    });
+    match kind.transparency() {
+        Transparency::Opaque => {}
+        Transparency::Cutout => {
+            material["alphaMode"] = json!("MASK");
+            material["alphaCutoff"] = json!(0.5);
+        }
+        Transparency::Translucent => material["alphaMode"] = json!("BLEND"),
+    }
+    if let Some(shape) = shape::of(kind) {
+        if shape.quads.iter().any(|q| q.double_sided) {
+            material["doubleSided"] = json!(true);
+        }
+    }
+    material
+}
+
+/// Writes the chunks as a binary glTF 2.0 file: one node per chunk placed at
+/// its origin, one primitive per block kind and one material per kind, all
+/// textured with the atlas embedded in the file.
+pub fn write_glb(chunks: &[Chunk], out: &mut impl Write) -> io::Result<()> {
+    let mut buffer = GlbBuffer::default();
+    let atlas_view = buffer.push_view(ATLAS_PNG, None);
+
+    let mut kinds: Vec<BlockKind> = vec![];
+    let mut meshes = vec![];
+    let mut nodes = vec![];
+    for chunk in chunks {
+        let block_meshes = mesh::chunk_meshes(chunk);
+        if block_meshes.is_empty() {
+            continue;
+        }
+        let primitives: Vec<Value> = block_meshes
+            .iter()
+            .map(|m| {
+                let material = match kinds.iter().position(|k| *k == m.kind) {
+                    Some(material) => material,
+                    None => {
+                        kinds.push(m.kind);
+                        kinds.len() - 1
+                    }
+                };
+                buffer.push_primitive(m, material)
+            })
+            .collect();
+        let name = format!("chunk {} {} {}", chunk.chunk_x, chunk.chunk_y, chunk.chunk_z);
+        meshes.push(json!({"name": name, "primitives": primitives}));
+        let origin: [f32; 3] = chunk.origin().into();
+        nodes.push(json!({"name": name, "mesh": meshes.len() - 1, "translation": origin}));
+    }
+
+    let nodes_empty = nodes.is_empty();
+    let mut gltf = json!({
+        "asset": {"version": "2.0", "generator": "vox23"},
+        "scene": 0,
+        "scenes": [{"nodes": (0..nodes.len()).collect::<Vec<_>>()}],
+        "nodes": nodes,
+        "meshes": meshes,
+        "materials": kinds.iter().map(|k| material(*k)).collect::<Vec<_>>(),
+        "textures": [{"sampler": 0, "source": 0}],
+        "images": [{"name": "atlas", "bufferView": atlas_view, "mimeType": "image/png"}],
+        "samplers": [{
+            "magFilter": NEAREST,
+            "minFilter": NEAREST,
+            "wrapS": CLAMP_TO_EDGE,
+            "wrapT": CLAMP_TO_EDGE,
+        }],
+        "buffers": [{"byteLength": buffer.bin.len()}],
+        "bufferViews": buffer.views,
+        "accessors": buffer.accessors,
+    });
+    // glTF doesn't allow empty arrays, an empty world has no scene at all
+    if let Some(object) = gltf.as_object_mut() {
+        if nodes_empty {
+            object.remove("scene");
+            object.remove("scenes");
+        }
+        object.retain(|_, v| !matches!(v, Value::Array(a) if a.is_empty()));
+    }
+
+    let mut json = serde_json::to_vec(&gltf)?;
+    while json.len() % 4 != 0 {
+        json.push(b' ');
+    }
+    let length = 12 + 8 + json.len() + 8 + buffer.bin.len();
+    for word in [GLB_MAGIC, GLB_VERSION, length as u32] {
+        out.write_all(&word.to_le_bytes())?;
+    }
+    out.write_all(&(json.len() as u32).to_le_bytes())?;
+    out.write_all(&CHUNK_JSON.to_le_bytes())?;
+    out.write_all(&json)?;
+    out.write_all(&(buffer.bin.len() as u32).to_le_bytes())?;
+    out.write_all(&CHUNK_BIN.to_le_bytes())?;
+    out.write_all(&buffer.bin)?;
+    Ok(())
+}
+
+pub fn save_glb(chunks: &[Chunk], path: impl AsRef<Path>) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_glb(chunks, &mut out)?;
+    out.flush()
+}
+
+/// Saves to `world_<time>.glb` in the working directory.
+pub fn save_timestamped(chunks: &[Chunk]) -> io::Result<PathBuf> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let path = PathBuf::from(format!(
+        "world_{}_{:03}.glb",
+        now.as_secs(),
+        now.subsec_millis()
+    ));
+    save_glb(chunks, &path)?;
+    Ok(path)
+}
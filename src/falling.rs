@@ -0,0 +1,65 @@
+use crate::{
+    chunk::BlockKind,
+    tick::{offset, Position, TickContext},
+};
+
+/// Ticks between a sand or gravel block losing its support and falling.
+pub const FALL_DELAY: u64 = 2;
+/// Added to the fall speed every tick, in blocks per tick.
+pub const GRAVITY: f32 = 0.04;
+pub const TERMINAL_VELOCITY: f32 = 2.0;
+
+/// A block that lost its support and is on its way down. `y` is the
+/// height of its center, `x` and `z` stay on the column it fell from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FallingBlock {
+    pub kind: BlockKind,
+    pub x: i64,
+    pub y: f32,
+    pub z: i64,
+    /// Blocks per tick, positive downwards
+    pub velocity: f32,
+}
+
+/// Whether a block can rest on `kind`. Falling blocks replace air and
+/// fluids, and the bottom of the chunk holds them up.
+pub fn supports(kind: BlockKind) -> bool {
+    !matches!(kind, BlockKind::Air) && !kind.is_fluid()
+}
+
+/// Scheduled tick of sand and gravel: turn into a falling block if there's
+/// nothing underneath.
+pub fn start_falling(ctx: &mut TickContext, pos: Position) {
+    if supports(ctx.kind_at(offset(pos, (0, -1, 0)))) {
+        return;
+    }
+    let kind = ctx.kind_at(pos);
+    ctx.set(pos, BlockKind::Air, 0);
+    ctx.spawn_falling(FallingBlock {
+        kind,
+        x: pos.0,
+        y: pos.1 as f32,
+        z: pos.2,
+        velocity: 0.0,
+    });
+}
+
+/// Moves a falling block one tick down. Returns true once it has landed and
+/// been placed back into the chunk.
+pub fn step(ctx: &mut TickContext, block: &mut FallingBlock) -> bool {
+    block.velocity = (block.velocity + GRAVITY).min(TERMINAL_VELOCITY);
+    let next_y = block.y - block.velocity;
+
+    // Highest supporting cell under the block, the chunk floor at worst
+    let mut floor = block.y.round() as i64 - 1;
+    while !supports(ctx.kind_at((block.x, floor, block.z))) {
+        floor -= 1;
+    }
+    if next_y <= (floor + 1) as f32 {
+        ctx.set((block.x, floor + 1, block.z), block.kind, 0);
+        true
+    } else {
+        block.y = next_y;
+        false
+    }
+}
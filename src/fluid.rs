@@ -0,0 +1,164 @@
+use crate::{
+    chunk::BlockKind,
+    tick::{offset, Position, TickContext},
+};
+
+/// Level of a fluid source. Flowing fluid counts up from here with the
+/// distance to the block feeding it.
+pub const SOURCE_LEVEL: u8 = 0;
+/// Set on fluid pouring down from the block above. It's drawn at full height
+/// and spreads as if it were a source once it lands.
+pub const FALLING: u8 = 8;
+
+const HORIZONTAL: [Position; 4] = [(1, 0, 0), (-1, 0, 0), (0, 0, 1), (0, 0, -1)];
+
+/// How a fluid flows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FluidProperties {
+    /// Furthest flowing level from a source before the fluid stops
+    pub max_level: u8,
+    /// Ticks between a change and the fluid reacting to it
+    pub tick_delay: u64,
+    /// Whether two sources next to each other turn flowing fluid between
+    /// them into a new source
+    pub renews: bool,
+}
+
+pub const WATER: FluidProperties = FluidProperties {
+    max_level: 7,
+    tick_delay: 5,
+    renews: true,
+};
+pub const LAVA: FluidProperties = FluidProperties {
+    max_level: 3,
+    tick_delay: 30,
+    renews: false,
+};
+
+pub fn properties(kind: BlockKind) -> Option<FluidProperties> {
+    match kind {
+        BlockKind::Water => Some(WATER),
+        BlockKind::Lava => Some(LAVA),
+        _ => None,
+    }
+}
+
+/// Height of a fluid block's surface as a fraction of a block. Sources sit
+/// a little below the top so flowing fluid can slope away from them, and
+/// fluid with more of itself above fills the whole block.
+pub fn surface_height(level: u8, same_fluid_above: bool) -> f32 {
+    if same_fluid_above || level & FALLING != 0 {
+        1.0
+    } else {
+        (8 - level.min(7)) as f32 / 9.0
+    }
+}
+
+/// Scheduled tick of water and lava: dry up or change level to match the
+/// neighbours, then fall, or spread sideways once resting on something.
+pub fn flow(ctx: &mut TickContext, pos: Position) {
+    let kind = ctx.kind_at(pos);
+    let props = match properties(kind) {
+        Some(props) => props,
+        None => return,
+    };
+    let mut level = ctx.level_at(pos);
+
+    if level != SOURCE_LEVEL {
+        match fed_level(ctx, pos, kind, props) {
+            None => {
+                ctx.set(pos, BlockKind::Air, 0);
+                return;
+            }
+            Some(fed) if fed != level => {
+                level = fed;
+                ctx.set(pos, kind, level);
+            }
+            Some(_) => {}
+        }
+    }
+
+    let below = offset(pos, (0, -1, 0));
+    if can_flow_into(ctx, below, kind, FALLING) {
+        ctx.set(below, kind, FALLING);
+        return;
+    }
+    if ctx.kind_at(below) == kind {
+        return;
+    }
+
+    let next = if level & FALLING != 0 { 1 } else { level + 1 };
+    if next > props.max_level {
+        return;
+    }
+    for h in HORIZONTAL {
+        let side = offset(pos, h);
+        if can_flow_into(ctx, side, kind, next) {
+            ctx.set(side, kind, next);
+        }
+    }
+}
+
+/// Whether fluid of `kind` at `level` may replace the block at `pos`.
+fn can_flow_into(ctx: &TickContext, pos: Position, kind: BlockKind, level: u8) -> bool {
+    match ctx.kind_at(pos) {
+        BlockKind::Air => true,
+        k if k == kind => {
+            let existing = ctx.level_at(pos);
+            if existing == SOURCE_LEVEL {
+                false
+            } else if level == FALLING {
+                existing != FALLING
+            } else {
+                existing & FALLING == 0 && existing > level
+            }
+        }
+        _ => false,
+    }
+}
+
+/// The level a flowing block should have given its neighbours, or `None` if
+/// nothing feeds it anymore.
+fn fed_level(
+    ctx: &TickContext,
+    pos: Position,
+    kind: BlockKind,
+    props: FluidProperties,
+) -> Option<u8> {
+    if ctx.kind_at(offset(pos, (0, 1, 0))) == kind {
+        return Some(FALLING);
+    }
+    let mut sources = 0;
+    let mut nearest: Option<u8> = None;
+    for h in HORIZONTAL {
+        let side = offset(pos, h);
+        if ctx.kind_at(side) != kind {
+            continue;
+        }
+        let side_level = ctx.level_at(side);
+        let distance = if side_level == SOURCE_LEVEL {
+            sources += 1;
+            0
+        } else if side_level & FALLING != 0 {
+            // Falling fluid only feeds its sides once it has landed
+            let below_side = ctx.kind_at(offset(side, (0, -1, 0)));
+            if below_side == BlockKind::Air || below_side == kind {
+                continue;
+            }
+            0
+        } else {
+            side_level
+        };
+        nearest = Some(nearest.map_or(distance, |n| n.min(distance)));
+    }
+    let below = offset(pos, (0, -1, 0));
+    let below_kind = ctx.kind_at(below);
+    let on_solid = below_kind != BlockKind::Air
+        && (below_kind != kind || ctx.level_at(below) == SOURCE_LEVEL);
+    if props.renews && sources >= 2 && on_solid {
+        return Some(SOURCE_LEVEL);
+    }
+    nearest
+        .map(|n| n + 1)
+        .filter(|level| *level <= props.max_level)
+}
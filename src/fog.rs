@@ -0,0 +1,84 @@
+use crate::{camera::Camera, time_of_day::Sky};
+
+/// How fog builds up with distance. Kept in sync with the cases of
+/// `fog_factor` in shader.wgsl.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FogMode {
+    Off,
+    /// Ramps from nothing at `start` to full fog at `end`
+    Linear,
+    /// Thickens with `density` per block of view depth
+    Exponential,
+    /// Exponential fog that thins out above `base_height`
+    Height,
+}
+
+impl FogMode {
+    pub const ALL: [FogMode; 4] = [
+        FogMode::Off,
+        FogMode::Linear,
+        FogMode::Exponential,
+        FogMode::Height,
+    ];
+    pub fn name(self) -> &'static str {
+        match self {
+            FogMode::Off => "Off",
+            FogMode::Linear => "Linear",
+            FogMode::Exponential => "Exponential",
+            FogMode::Height => "Height",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FogSettings {
+    pub mode: FogMode,
+    pub start: f32,
+    pub end: f32,
+    pub density: f32,
+    /// How quickly height fog thins out per block above `base_height`
+    pub height_falloff: f32,
+    pub base_height: f32,
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        Self {
+            mode: FogMode::Linear,
+            start: 48.0,
+            end: 192.0,
+            density: 0.015,
+            height_falloff: 0.15,
+            base_height: 8.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FogUniform {
+    color: [f32; 3],
+    mode: u32,
+    eye: [f32; 3],
+    start: f32,
+    end: f32,
+    density: f32,
+    height_falloff: f32,
+    base_height: f32,
+}
+
+impl FogUniform {
+    /// Fog takes the horizon color so distant blocks blend into the sky.
+    pub fn new(settings: &FogSettings, camera: &Camera, sky: &Sky) -> Self {
+        Self {
+            color: sky.horizon,
+            mode: settings.mode as u32,
+            eye: camera.eye.into(),
+            start: settings.start,
+            end: settings.end.max(settings.start + 0.001),
+            density: settings.density,
+            height_falloff: settings.height_falloff,
+            base_height: settings.base_height,
+        }
+    }
+}
@@ -1,12 +1,337 @@
-use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
 
-use egui::Slider;
+use egui::{epaint::Mesh, pos2, vec2, Color32, FullOutput, Rect, Sense, Shape, Slider, Stroke};
+use egui_wgpu_backend::ScreenDescriptor;
+use egui_winit_platform::{Platform, PlatformDescriptor};
 use epi::App;
+use instant::Instant;
+use wgpu::{CommandEncoder, Device, Queue, TextureFormat, TextureView};
+use winit::{dpi::PhysicalSize, event_loop::EventLoopProxy, window::Window};
+
+use crate::{
+    blocks,
+    brush::{Brush, BrushMode, BrushShape},
+    chunk::{BlockKind, WorldPosition},
+    editor::Transform,
+    map::MapView,
+    renderer::Event,
+    shadow::{ShadowSettings, CASCADE_DEBUG_COLORS, MAX_CASCADES},
+    fog::{FogMode, FogSettings},
+    tick::DEFAULT_RANDOM_TICK_SPEED,
+    time_of_day::TimeOfDay,
+};
+
+const SHADOW_RESOLUTIONS: [u32; 4] = [512, 1024, 2048, 4096];
+/// Size of the minimap in points
+pub const MINIMAP_SIZE: f32 = 256.0;
+/// Range of the minimap zoom, in blocks across
+const MINIMAP_SPANS: std::ops::RangeInclusive<u32> = 16..=512;
+
+/// Something for the renderer to do to the world from the editor panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditCommand {
+    /// Set a corner to the block in the middle of the view
+    PickCorner(usize),
+    /// Put one block in front of the one in the middle of the view, turned
+    /// to suit the view
+    Place,
+    Fill,
+    Replace,
+    Hollow,
+    Copy,
+    Paste,
+    /// Write the clipboard to `template_path`
+    SaveTemplate,
+    /// Read `template_path` into the clipboard
+    LoadTemplate,
+    /// Apply the brush where the middle of the view hits
+    Sculpt,
+    Undo,
+    Redo,
+}
+
+/// The editor window. Corners are typed in or picked from the view, the
+/// renderer carries out `command` and resets it.
+pub struct EditorPanel {
+    pub open: bool,
+    pub corners: [[i64; 3]; 2],
+    /// Block to fill with, or to replace with
+    pub kind: BlockKind,
+    pub replace_from: BlockKind,
+    pub transform: Transform,
+    /// Paste air over what's there instead of skipping it
+    pub paste_air: bool,
+    /// Template file the clipboard is saved to and loaded from
+    pub template_path: String,
+    pub brush: Brush,
+    pub command: Option<EditCommand>,
+    /// Size of what was copied, filled in by the renderer
+    pub clipboard_size: Option<(i64, i64, i64)>,
+    /// Names of the edits undo and redo would change, filled in by the
+    /// renderer
+    pub next_undo: Option<String>,
+    pub next_redo: Option<String>,
+}
+
+fn block_combo(ui: &mut egui::Ui, label: &str, kind: &mut BlockKind) {
+    egui::ComboBox::from_label(label)
+        .selected_text(blocks::definition(*kind).name)
+        .show_ui(ui, |ui| {
+            for k in blocks::KINDS {
+                ui.selectable_value(kind, k, blocks::definition(k).name);
+            }
+        });
+}
+
+impl EditorPanel {
+    fn new() -> Self {
+        Self {
+            open: false,
+            corners: [[0, 0, 0], [3, 3, 3]],
+            kind: BlockKind::Stone,
+            replace_from: BlockKind::Dirt,
+            transform: Transform::default(),
+            paste_air: false,
+            template_path: "template.vxsc".to_string(),
+            brush: Brush::default(),
+            command: None,
+            clipboard_size: None,
+            next_undo: None,
+            next_redo: None,
+        }
+    }
+    pub fn corner(&self, i: usize) -> WorldPosition {
+        let [x, y, z] = self.corners[i];
+        (x, y, z)
+    }
+    fn show(&mut self, ui: &mut egui::Ui) {
+        for (i, name) in ["A", "B"].into_iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(name);
+                for c in &mut self.corners[i] {
+                    ui.add(egui::DragValue::new(c));
+                }
+                if ui.button("Pick").on_hover_text("Block in the middle of the view").clicked() {
+                    self.command = Some(EditCommand::PickCorner(i));
+                }
+            });
+        }
+        let [a, b] = self.corners;
+        let size: Vec<_> = (0..3).map(|i| (a[i] - b[i]).abs() + 1).collect();
+        ui.label(format!("{} x {} x {}", size[0], size[1], size[2]));
+        ui.separator();
+
+        block_combo(ui, "Block", &mut self.kind);
+        ui.horizontal(|ui| {
+            if ui.button("Place").on_hover_text("In front of the block in view").clicked() {
+                self.command = Some(EditCommand::Place);
+            }
+            if ui.button("Fill").clicked() {
+                self.command = Some(EditCommand::Fill);
+            }
+            if ui.button("Hollow").clicked() {
+                self.command = Some(EditCommand::Hollow);
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Replace").clicked() {
+                self.command = Some(EditCommand::Replace);
+            }
+            // Reads "Replace <from> blocks with Block", the kind picked above
+            block_combo(ui, "blocks with Block", &mut self.replace_from);
+        });
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            if ui.button("Copy").clicked() {
+                self.command = Some(EditCommand::Copy);
+            }
+            let paste = egui::Button::new("Paste at A");
+            if ui.add_enabled(self.clipboard_size.is_some(), paste).clicked() {
+                self.command = Some(EditCommand::Paste);
+            }
+        });
+        if let Some((x, y, z)) = self.clipboard_size {
+            ui.label(format!("Clipboard: {} x {} x {}", x, y, z));
+        }
+        ui.horizontal(|ui| {
+            ui.label("Turns");
+            ui.add(Slider::new(&mut self.transform.quarter_turns, 0..=3));
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.transform.mirror_x, "Mirror x");
+            ui.checkbox(&mut self.transform.mirror_z, "Mirror z");
+            ui.checkbox(&mut self.paste_air, "Paste air");
+        });
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.template_path);
+            let save = egui::Button::new("Save");
+            if ui.add_enabled(self.clipboard_size.is_some(), save).clicked() {
+                self.command = Some(EditCommand::SaveTemplate);
+            }
+            if ui.button("Load").clicked() {
+                self.command = Some(EditCommand::LoadTemplate);
+            }
+        });
+        ui.separator();
+
+        self.brush_controls(ui);
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            let undo = egui::Button::new("Undo");
+            let response = ui.add_enabled(self.next_undo.is_some(), undo);
+            if let Some(name) = &self.next_undo {
+                if response.on_hover_text(name).clicked() {
+                    self.command = Some(EditCommand::Undo);
+                }
+            }
+            let redo = egui::Button::new("Redo");
+            let response = ui.add_enabled(self.next_redo.is_some(), redo);
+            if let Some(name) = &self.next_redo {
+                if response.on_hover_text(name).clicked() {
+                    self.command = Some(EditCommand::Redo);
+                }
+            }
+        });
+    }
+}
+
+impl EditorPanel {
+    fn brush_controls(&mut self, ui: &mut egui::Ui) {
+        let brush = &mut self.brush;
+        egui::ComboBox::from_label("Brush")
+            .selected_text(brush.shape.name())
+            .show_ui(ui, |ui| {
+                for shape in BrushShape::ALL {
+                    ui.selectable_value(&mut brush.shape, shape, shape.name());
+                }
+            });
+        egui::ComboBox::from_label("Brush mode")
+            .selected_text(brush.mode.name())
+            .show_ui(ui, |ui| {
+                for mode in BrushMode::ALL {
+                    ui.selectable_value(&mut brush.mode, mode, mode.name());
+                }
+            });
+        ui.add(Slider::new(&mut brush.radius, 0.0f32..=16.0f32).text("Radius"));
+        ui.add(Slider::new(&mut brush.strength, 0.0f32..=1.0f32).text("Strength"));
+        if matches!(brush.mode, BrushMode::Add | BrushMode::Flatten | BrushMode::Noise) {
+            block_combo(ui, "Brush block", &mut brush.kind);
+        }
+        if ui.button("Sculpt").on_hover_text("Where the middle of the view hits").clicked() {
+            self.command = Some(EditCommand::Sculpt);
+        }
+    }
+}
+
+/// The minimap window. The renderer draws the map and fills in the markers,
+/// and moves the camera when `teleport` is set.
+pub struct Minimap {
+    pub open: bool,
+    /// Blocks across the map
+    pub span: u32,
+    /// A newly drawn map, uploaded by the GUI on its next frame
+    pub image: Option<egui::ColorImage>,
+    texture: Option<egui::TextureHandle>,
+    /// Area the current image covers
+    pub view: MapView,
+    /// Camera x and z
+    pub player: (f32, f32),
+    /// Camera view direction along x and z
+    pub heading: (f32, f32),
+    /// World x and z clicked on the map
+    pub teleport: Option<(f32, f32)>,
+}
+
+impl Minimap {
+    fn new() -> Self {
+        Self {
+            open: true,
+            span: 64,
+            image: None,
+            texture: None,
+            view: MapView {
+                center: (0.0, 0.0),
+                span: 64,
+            },
+            player: (0.0, 0.0),
+            heading: (0.0, -1.0),
+            teleport: None,
+        }
+    }
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.add(
+            Slider::new(&mut self.span, MINIMAP_SPANS)
+                .logarithmic(true)
+                .text("Blocks across"),
+        );
+        if let Some(image) = self.image.take() {
+            match &mut self.texture {
+                Some(texture) => texture.set(image),
+                None => self.texture = Some(ui.ctx().load_texture("minimap", image)),
+            }
+        }
+
+        let (response, painter) =
+            ui.allocate_painter(vec2(MINIMAP_SIZE, MINIMAP_SIZE), Sense::click());
+        let rect = response.rect;
+        painter.rect_filled(rect, 0.0, Color32::BLACK);
+        if let Some(texture) = &self.texture {
+            let mut mesh = Mesh::with_texture(texture.id());
+            let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+            mesh.add_rect_with_uv(rect, uv, Color32::WHITE);
+            painter.add(Shape::mesh(mesh));
+        }
+        let (u, v) = self.view.to_map(self.player.0, self.player.1);
+        let at = rect.min + vec2(u, v) * rect.size();
+        let heading = vec2(self.heading.0, self.heading.1);
+        if heading.length() > 0.0 {
+            painter.arrow(at, heading.normalized() * 14.0, Stroke::new(2.0, Color32::WHITE));
+        }
+        painter.circle_filled(at, 4.0, Color32::RED);
+
+        if response.hovered() {
+            // Scrolling up zooms in
+            let scroll = ui.input().scroll_delta.y;
+            if scroll != 0.0 {
+                let span = self.span as f32 * (1.0 - scroll * 0.002).clamp(0.5, 2.0);
+                self.span = (span.round() as u32)
+                    .clamp(*MINIMAP_SPANS.start(), *MINIMAP_SPANS.end());
+            }
+        }
+        if let Some(pos) = response.interact_pointer_pos().filter(|_| response.clicked()) {
+            let uv = (pos - rect.min) / rect.size();
+            self.teleport = Some(self.view.to_world(uv.x, uv.y));
+        }
+        ui.label("Click to teleport");
+    }
+}
 
 pub struct TropicGui {
     pub wireframe: bool,
-    pub camera_speed: f32 
-
+    pub camera_speed: f32,
+    /// Heading of the sun's path in degrees, see `TimeOfDay::sun_azimuth`
+    pub sun_azimuth: f32,
+    /// Hour of the day, advanced by the renderer and editable here
+    pub time_of_day: f32,
+    /// How fast the day passes, 0 stops the clock
+    pub time_scale: f32,
+    pub shadows: ShadowSettings,
+    pub fog: FogSettings,
+    /// Blocks picked for a random tick per chunk section per tick
+    pub random_tick_speed: u32,
+    /// Filled in by the renderer
+    pub entity_count: usize,
+    /// Set by the buttons, the renderer acts on them and resets them
+    pub spawn_entity: bool,
+    pub clear_entities: bool,
+    /// Write the world to a .glb file, reset by the renderer
+    pub export_gltf: bool,
+    /// Far distance of each cascade, filled in by the renderer
+    pub shadow_splits: Vec<f32>,
+    pub minimap: Minimap,
+    pub editor: EditorPanel,
 }
 impl App for TropicGui {
     fn update(&mut self, ctx: &egui::Context, frame: &epi::Frame) {
@@ -17,8 +342,40 @@ impl App for TropicGui {
             ui.checkbox(&mut self.wireframe, "Wireframe Rendering");
             // ui.slider
             ui.add(Slider::new(&mut self.camera_speed, 0.0f32..=5.0f32));
+            ui.separator();
+            ui.label("Sun");
+            ui.add(Slider::new(&mut self.time_of_day, 0.0f32..=24.0f32).text("Time of day"));
+            ui.add(Slider::new(&mut self.time_scale, 0.0f32..=100.0f32).logarithmic(true).text("Time scale"));
+            ui.add(Slider::new(&mut self.sun_azimuth, 0.0f32..=360.0f32).text("Azimuth"));
+            ui.collapsing("Shadows", |ui| self.shadow_controls(ui));
+            ui.collapsing("Fog", |ui| self.fog_controls(ui));
+            ui.add(Slider::new(&mut self.random_tick_speed, 0..=64).text("Random tick speed"));
+            ui.collapsing("Entities", |ui| {
+                ui.label(format!("{} entities", self.entity_count));
+                ui.horizontal(|ui| {
+                    self.spawn_entity |= ui.button("Spawn").clicked();
+                    self.clear_entities |= ui.button("Clear").clicked();
+                });
+            });
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                self.export_gltf |= ui.button("Export glTF").clicked();
+            }
+            ui.checkbox(&mut self.minimap.open, "Minimap");
+            ui.checkbox(&mut self.editor.open, "Editor");
 
         });
+        let mut minimap_open = self.minimap.open;
+        egui::Window::new("Minimap")
+            .open(&mut minimap_open)
+            .resizable(false)
+            .show(ctx, |ui| self.minimap.show(ui));
+        self.minimap.open = minimap_open;
+        let mut editor_open = self.editor.open;
+        egui::Window::new("Editor")
+            .open(&mut editor_open)
+            .show(ctx, |ui| self.editor.show(ui));
+        self.editor.open = editor_open;
         
         
     }
@@ -26,4 +383,180 @@ impl App for TropicGui {
     fn name(&self) -> &str {
         "Tropic"
     }
-}
\ No newline at end of file
+}
+impl TropicGui {
+    fn fog_controls(&mut self, ui: &mut egui::Ui) {
+        egui::ComboBox::from_label("Mode")
+            .selected_text(self.fog.mode.name())
+            .show_ui(ui, |ui| {
+                for mode in FogMode::ALL {
+                    ui.selectable_value(&mut self.fog.mode, mode, mode.name());
+                }
+            });
+        match self.fog.mode {
+            FogMode::Off => {}
+            FogMode::Linear => {
+                ui.add(Slider::new(&mut self.fog.start, 0.0f32..=512.0f32).text("Start"));
+                ui.add(Slider::new(&mut self.fog.end, 0.0f32..=1024.0f32).text("End"));
+            }
+            FogMode::Exponential => {
+                ui.add(Slider::new(&mut self.fog.density, 0.0f32..=0.1f32).text("Density"));
+            }
+            FogMode::Height => {
+                ui.add(Slider::new(&mut self.fog.density, 0.0f32..=0.1f32).text("Density"));
+                ui.add(Slider::new(&mut self.fog.height_falloff, 0.01f32..=1.0f32).text("Falloff"));
+                ui.add(Slider::new(&mut self.fog.base_height, -32.0f32..=64.0f32).text("Base height"));
+            }
+        }
+    }
+    fn shadow_controls(&mut self, ui: &mut egui::Ui) {
+        ui.add(Slider::new(&mut self.shadows.cascade_count, 1..=MAX_CASCADES).text("Cascades"));
+        egui::ComboBox::from_label("Resolution")
+            .selected_text(self.shadows.resolution.to_string())
+            .show_ui(ui, |ui| {
+                for resolution in SHADOW_RESOLUTIONS {
+                    ui.selectable_value(
+                        &mut self.shadows.resolution,
+                        resolution,
+                        resolution.to_string(),
+                    );
+                }
+            });
+        ui.add(Slider::new(&mut self.shadows.distance, 16.0f32..=512.0f32).text("Distance"));
+        ui.checkbox(&mut self.shadows.debug_cascades, "Show cascades");
+        if self.shadows.debug_cascades {
+            let mut near = 0.0;
+            for (i, far) in self.shadow_splits.iter().enumerate() {
+                let [r, g, b] = CASCADE_DEBUG_COLORS[i];
+                let color = egui::Color32::from_rgb(
+                    (r * 255.0) as u8,
+                    (g * 255.0) as u8,
+                    (b * 255.0) as u8,
+                );
+                ui.colored_label(color, format!("Cascade {}: {:.1} - {:.1}", i, near, far));
+                near = *far;
+            }
+        }
+    }
+}
+
+/// egui on top of the world: the winit platform, the wgpu render pass and
+/// the `TropicGui` app itself.
+pub struct GuiLayer {
+    pub platform: Platform,
+    egui_rpass: egui_wgpu_backend::RenderPass,
+    pub tropic_gui: TropicGui,
+    start_time: Instant,
+    previous_frame_time: Option<f32>,
+    repaint_signal: Arc<ExampleRepaintSignal>,
+}
+
+impl GuiLayer {
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        window: &Window,
+        event_loop_proxy: EventLoopProxy<Event>,
+    ) -> Self {
+        let time_of_day = TimeOfDay::default();
+        let tropic_gui = TropicGui {
+            wireframe: false,
+            camera_speed: 0.2,
+            sun_azimuth: time_of_day.sun_azimuth,
+            time_of_day: time_of_day.hours(),
+            time_scale: time_of_day.time_scale,
+            shadows: ShadowSettings::default(),
+            fog: FogSettings::default(),
+            random_tick_speed: DEFAULT_RANDOM_TICK_SPEED,
+            entity_count: 0,
+            spawn_entity: false,
+            clear_entities: false,
+            export_gltf: false,
+            shadow_splits: vec![],
+            minimap: Minimap::new(),
+            editor: EditorPanel::new(),
+        };
+        Self {
+            platform: Self::setup_egui(window, &window.inner_size()),
+            egui_rpass: egui_wgpu_backend::RenderPass::new(device, format, 1),
+            tropic_gui,
+            start_time: Instant::now(),
+            previous_frame_time: None,
+            repaint_signal: Arc::new(ExampleRepaintSignal(Mutex::new(event_loop_proxy))),
+        }
+    }
+    pub fn setup_egui(window: &Window, size: &PhysicalSize<u32>) -> Platform {
+        Platform::new(PlatformDescriptor {
+            physical_width: size.width as u32,
+            physical_height: size.height as u32,
+            scale_factor: window.scale_factor(),
+            font_definitions: egui::FontDefinitions::default(),
+            style: Default::default(),
+        })
+    }
+    /// Runs the `TropicGui` app for this frame and paints it over `view`.
+    pub fn render(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        window: &Window,
+        size: PhysicalSize<u32>,
+    ) {
+        self.platform
+            .update_time(self.start_time.elapsed().as_secs_f64());
+        let egui_start = Instant::now();
+        self.platform.begin_frame();
+        let app_output = epi::backend::AppOutput::default();
+        let mut frame = epi::Frame::new(epi::backend::FrameData {
+            info: epi::IntegrationInfo {
+                name: "egui_example",
+                web_info: None,
+                cpu_usage: self.previous_frame_time,
+                native_pixels_per_point: Some(window.scale_factor() as _),
+                prefer_dark_mode: None,
+            },
+            output: app_output,
+            repaint_signal: self.repaint_signal.clone(),
+        });
+        self.tropic_gui.update(&self.platform.context(), &mut frame);
+
+        let FullOutput {
+            platform_output: _output,
+            shapes: paint_commands,
+            textures_delta,
+            ..
+        } = self.platform.end_frame(Some(&window));
+
+        let paint_jobs = self.platform.context().tessellate(paint_commands);
+        let frame_time = (Instant::now() - egui_start).as_secs_f64() as f32;
+        self.previous_frame_time = Some(frame_time);
+
+        let screen_descriptor = ScreenDescriptor {
+            physical_width: size.width,
+            physical_height: size.height,
+            scale_factor: window.scale_factor() as f32,
+        };
+        self.egui_rpass
+            .add_textures(device, queue, &textures_delta)
+            .expect("Couldn't add textures to EGUI");
+        self.egui_rpass
+            .update_buffers(device, queue, &paint_jobs, &screen_descriptor);
+
+        self.egui_rpass
+            .execute(encoder, view, &paint_jobs, &screen_descriptor, None)
+            .unwrap();
+    }
+}
+
+struct ExampleRepaintSignal(std::sync::Mutex<winit::event_loop::EventLoopProxy<Event>>);
+
+impl epi::backend::RepaintSignal for ExampleRepaintSignal {
+    fn request_repaint(&self) {
+        self.0.lock().unwrap().send_event(Event::RequestRedraw).ok();
+    }
+}
+
+unsafe impl Sync for ExampleRepaintSignal {}
+unsafe impl Send for ExampleRepaintSignal {}
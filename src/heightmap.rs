@@ -0,0 +1,173 @@
+use std::{collections::HashMap, fmt, path::Path};
+
+use image::{ImageBuffer, ImageError, Luma, RgbImage};
+
+use crate::{
+    blocks,
+    chunk::{self, Block, BlockKind, Chunk, WorldPosition},
+};
+
+/// Grayscale with 16 bits per pixel, so tall terrain doesn't step.
+pub type HeightImage = ImageBuffer<Luma<u16>, Vec<u16>>;
+
+/// Blocks of topsoil between a grass surface and the stone below.
+const SOIL_DEPTH: i64 = 3;
+
+#[derive(Debug)]
+pub enum HeightmapError {
+    Image(ImageError),
+    /// The material map has to cover the heightmap pixel for pixel.
+    SizeMismatch {
+        heightmap: (u32, u32),
+        materials: (u32, u32),
+    },
+}
+
+impl fmt::Display for HeightmapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeightmapError::Image(e) => write!(f, "{}", e),
+            HeightmapError::SizeMismatch {
+                heightmap,
+                materials,
+            } => write!(
+                f,
+                "material map is {}x{} but the heightmap is {}x{}",
+                materials.0, materials.1, heightmap.0, heightmap.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HeightmapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HeightmapError::Image(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ImageError> for HeightmapError {
+    fn from(e: ImageError) -> Self {
+        HeightmapError::Image(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeightmapOptions {
+    /// Where pixel (0, 0) of a black heightmap ends up. Image x runs along
+    /// world x and image y along world z.
+    pub origin: WorldPosition,
+    /// Blocks between black and white
+    pub scale: f32,
+    /// Columns lower than this are flooded with water up to it
+    pub sea_level: Option<i64>,
+}
+
+impl Default for HeightmapOptions {
+    fn default() -> Self {
+        Self {
+            origin: (0, 0, 0),
+            scale: 32.0,
+            sea_level: None,
+        }
+    }
+}
+
+/// Chunks filled in column by column, looked up by position.
+#[derive(Default)]
+struct ChunkSet {
+    chunks: Vec<Chunk>,
+    index: HashMap<(i64, i64, i64), usize>,
+}
+
+impl ChunkSet {
+    fn set(&mut self, kind: BlockKind, pos: WorldPosition) {
+        let (key, (x, y, z)) = chunk::split_world_position(pos);
+        let chunks = &mut self.chunks;
+        let index = *self.index.entry(key).or_insert_with(|| {
+            chunks.push(Chunk::new(key.0, key.1, key.2));
+            chunks.len() - 1
+        });
+        chunks[index].set_block(Block::new(kind, x, y, z));
+    }
+}
+
+/// Top of the column from the brightness of a heightmap pixel.
+pub fn column_height(luma: u16, options: &HeightmapOptions) -> i64 {
+    options.origin.1 + (luma as f32 / u16::MAX as f32 * options.scale).round() as i64
+}
+
+/// Kinds from the top of a column down: the surface, then soil, then stone.
+/// Without a material the surface is grass, or sand under the sea.
+fn column_kind(surface: Option<BlockKind>, depth: i64, underwater: bool) -> BlockKind {
+    let surface = surface.unwrap_or(if underwater {
+        BlockKind::Sand
+    } else {
+        BlockKind::Grass
+    });
+    match (depth, surface) {
+        (0, kind) => kind,
+        (d, BlockKind::Grass) if d <= SOIL_DEPTH => BlockKind::Dirt,
+        (d, kind) if d <= SOIL_DEPTH => kind,
+        _ => BlockKind::Stone,
+    }
+}
+
+/// Builds terrain with a column per heightmap pixel, from `origin` up to its
+/// height. Each pixel of the material map picks the surface block of its
+/// column by the nearest block color.
+pub fn build(
+    heightmap: &HeightImage,
+    materials: Option<&RgbImage>,
+    options: &HeightmapOptions,
+) -> Result<Vec<Chunk>, HeightmapError> {
+    if let Some(materials) = materials {
+        if materials.dimensions() != heightmap.dimensions() {
+            return Err(HeightmapError::SizeMismatch {
+                heightmap: heightmap.dimensions(),
+                materials: materials.dimensions(),
+            });
+        }
+    }
+
+    let (ox, oy, oz) = options.origin;
+    let mut chunks = ChunkSet::default();
+    let mut surfaces: HashMap<[u8; 3], BlockKind> = HashMap::new();
+    for (px, pz, luma) in heightmap.enumerate_pixels() {
+        let x = ox + px as i64;
+        let z = oz + pz as i64;
+        let top = column_height(luma.0[0], options);
+        let underwater = matches!(options.sea_level, Some(sea) if top < sea);
+        let surface = materials.map(|m| {
+            let color = m.get_pixel(px, pz).0;
+            *surfaces
+                .entry(color)
+                .or_insert_with(|| blocks::by_color(color))
+        });
+        for y in oy..=top {
+            chunks.set(column_kind(surface, top - y, underwater), (x, y, z));
+        }
+        if let Some(sea) = options.sea_level {
+            for y in top + 1..=sea {
+                chunks.set(BlockKind::Water, (x, y, z));
+            }
+        }
+    }
+    Ok(chunks.chunks)
+}
+
+/// Loads the images and builds terrain from them, see `build`.
+pub fn load(
+    heightmap: impl AsRef<Path>,
+    materials: Option<&Path>,
+    options: &HeightmapOptions,
+) -> Result<Vec<Chunk>, HeightmapError> {
+    let heightmap = image::open(heightmap)?.to_luma16();
+    let materials = match materials {
+        Some(path) => Some(image::open(path)?.to_rgb8()),
+        None => None,
+    };
+    build(&heightmap, materials.as_ref(), options)
+}
@@ -0,0 +1,94 @@
+use futures::{
+    executor::{LocalPool, LocalSpawner},
+    task::SpawnExt,
+};
+use instant::Instant;
+use wgpu::{util::StagingBelt, CommandEncoder, Device, TextureFormat, TextureView};
+use wgpu_glyph::{GlyphBrush, GlyphBrushBuilder, Section, Text};
+
+const HUD_MESSAGE_SECONDS: f32 = 3.0;
+
+/// Text overlay drawn on top of the world with wgpu_glyph.
+pub struct HudLayer {
+    font_brush: GlyphBrush<()>,
+    staging_belt: StagingBelt,
+    local_pool: LocalPool,
+    local_spawner: LocalSpawner,
+    message: Option<(String, Instant)>,
+}
+
+impl HudLayer {
+    pub fn new(device: &Device, format: TextureFormat) -> Self {
+        let inconsolata = wgpu_glyph::ab_glyph::FontArc::try_from_slice(include_bytes!(
+            "Inconsolata-Regular.ttf"
+        ))
+        .unwrap();
+        let font_brush = GlyphBrushBuilder::using_font(inconsolata).build(&device, format);
+        let local_pool = futures::executor::LocalPool::new();
+        let local_spawner = local_pool.spawner();
+
+        Self {
+            font_brush,
+            staging_belt: wgpu::util::StagingBelt::new(1024),
+            local_pool,
+            local_spawner,
+            message: None,
+        }
+    }
+    pub fn draw_text(&mut self, content: &str, x: f32, y: f32, color: u32, bounds: (f32, f32)) {
+        let r = (color & 0xFF000000) >> 24;
+        let g = (color & 0x00FF0000) >> 16;
+        let b = (color & 0x000000FF) >> 8;
+        let a = color & 0x000000FF;
+
+        self.font_brush.queue(Section {
+            screen_position: (x, y),
+            bounds,
+            text: vec![Text::new(content)
+                .with_color([
+                    r as f32 / 255.0,
+                    g as f32 / 255.0,
+                    b as f32 / 255.0,
+                    a as f32 / 255.0,
+                ])
+                .with_scale(20.0)],
+            ..Section::default()
+        });
+    }
+    /// Shows `message` below the FPS counter for a few seconds.
+    pub fn show_message(&mut self, message: String) {
+        self.message = Some((message, Instant::now()));
+    }
+    /// Queues the standard HUD lines.
+    pub fn draw_hud(&mut self, fps: f32, bounds: (f32, f32)) {
+        self.draw_text(&format!("FPS {}", fps), 1.0, 1.0, 0xff00ffff, bounds);
+        if let Some((message, shown_at)) = self.message.clone() {
+            if shown_at.elapsed().as_secs_f32() < HUD_MESSAGE_SECONDS {
+                self.draw_text(&message, 1.0, 24.0, 0xffffffff, bounds);
+            } else {
+                self.message = None;
+            }
+        }
+    }
+    pub fn render(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        width: u32,
+        height: u32,
+    ) {
+        self.font_brush
+            .draw_queued(device, &mut self.staging_belt, encoder, view, width, height)
+            .expect("Draw queued");
+        self.staging_belt.finish();
+    }
+    /// Call once the frame using the HUD has been submitted.
+    pub fn recall(&mut self) {
+        self.local_spawner
+            .spawn(self.staging_belt.recall())
+            .expect("Recall staging belt");
+
+        self.local_pool.run_until_stalled();
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use crate::{
+    chunk::{self, BlockData, BlockKind, Chunk, WorldPosition},
+    editor::Edit,
+};
+
+/// Changes kept for undo by default, 32 bytes each.
+pub const DEFAULT_MAX_CHANGES: usize = 1 << 20;
+
+/// Chunks touched by an edit, by chunk coordinates, so only those are
+/// remeshed.
+pub type DirtyChunks = BTreeSet<(i64, i64, i64)>;
+
+/// What applying, undoing or redoing a transaction wrote.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Changed {
+    /// Every block that changed, in the order they were written, so the
+    /// blocks around them can react
+    pub blocks: Vec<WorldPosition>,
+    pub chunks: DirtyChunks,
+}
+
+impl Changed {
+    fn push(&mut self, position: WorldPosition) {
+        self.blocks.push(position);
+        self.chunks.insert(chunk::split_world_position(position).0);
+    }
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+/// One block that changed and what it was before, with its state and
+/// fluid level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockChange {
+    pub position: WorldPosition,
+    pub old: BlockData,
+    pub new: BlockData,
+}
+
+/// Changes made together, undone and redone together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    /// Shown next to the undo and redo buttons
+    pub name: String,
+    pub changes: Vec<BlockChange>,
+}
+
+/// History of edits to the world. Holds at most `max_changes` block changes,
+/// forgetting the oldest transactions first.
+pub struct Journal {
+    undo: VecDeque<Transaction>,
+    redo: Vec<Transaction>,
+    max_changes: usize,
+    /// Changes held by `undo` and `redo` together
+    stored: usize,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CHANGES)
+    }
+}
+
+/// Sets each block to `new` or `old`.
+fn write(chunks: &mut Vec<Chunk>, changes: &[BlockChange], undo: bool) -> Changed {
+    let mut changed = Changed::default();
+    let mut set = |change: &BlockChange| {
+        let data = if undo { change.old } else { change.new };
+        chunk::set_world_block_data(chunks, data, change.position);
+        changed.push(change.position);
+    };
+    // Undo in reverse, in case a transaction set a position more than once
+    if undo {
+        changes.iter().rev().for_each(&mut set);
+    } else {
+        changes.iter().for_each(&mut set);
+    }
+    changed
+}
+
+/// Writes the edits that change a block, adding chunks where needed, and
+/// pushes what each block was before onto `record`. Air where there is no
+/// chunk counts as unchanged. Every edit reaches the chunks through here,
+/// recorded or not.
+pub fn write_edits(
+    chunks: &mut Vec<Chunk>,
+    edits: &[Edit],
+    mut record: Option<&mut Vec<BlockChange>>,
+) -> Changed {
+    let mut changed = Changed::default();
+    for (position, data) in edits {
+        let old = match chunk::world_block_data(chunks, *position) {
+            // Undoing puts air where there was no chunk
+            old if old.kind == BlockKind::None => BlockKind::Air.into(),
+            old => old,
+        };
+        if old != *data {
+            chunk::set_world_block_data(chunks, *data, *position);
+            changed.push(*position);
+            if let Some(record) = record.as_mut() {
+                record.push(BlockChange {
+                    position: *position,
+                    old,
+                    new: *data,
+                });
+            }
+        }
+    }
+    changed
+}
+
+impl Journal {
+    pub fn new(max_changes: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: vec![],
+            max_changes,
+            stored: 0,
+        }
+    }
+    /// Applies the edits as one transaction and clears the redo history.
+    /// Edits that leave a block as it was aren't recorded. A transaction
+    /// bigger than the whole history is applied but can't be undone.
+    pub fn apply(&mut self, chunks: &mut Vec<Chunk>, name: &str, edits: &[Edit]) -> Changed {
+        let mut changes = vec![];
+        let changed = write_edits(chunks, edits, Some(&mut changes));
+        if changes.is_empty() {
+            return changed;
+        }
+
+        self.stored -= self.redo.drain(..).map(|t| t.changes.len()).sum::<usize>();
+        if changes.len() > self.max_changes {
+            self.clear();
+            return changed;
+        }
+        self.stored += changes.len();
+        self.undo.push_back(Transaction {
+            name: name.to_string(),
+            changes,
+        });
+        while self.stored > self.max_changes {
+            match self.undo.pop_front() {
+                Some(oldest) => self.stored -= oldest.changes.len(),
+                None => break,
+            }
+        }
+        changed
+    }
+    /// Reverts the latest transaction. `None` if there is nothing to undo.
+    pub fn undo(&mut self, chunks: &mut Vec<Chunk>) -> Option<Changed> {
+        let transaction = self.undo.pop_back()?;
+        let changed = write(chunks, &transaction.changes, true);
+        self.redo.push(transaction);
+        Some(changed)
+    }
+    /// Applies the latest undone transaction again.
+    pub fn redo(&mut self, chunks: &mut Vec<Chunk>) -> Option<Changed> {
+        let transaction = self.redo.pop()?;
+        let changed = write(chunks, &transaction.changes, false);
+        self.undo.push_back(transaction);
+        Some(changed)
+    }
+    /// Name of the transaction `undo` would revert.
+    pub fn next_undo(&self) -> Option<&str> {
+        self.undo.back().map(|t| t.name.as_str())
+    }
+    /// Name of the transaction `redo` would apply.
+    pub fn next_redo(&self) -> Option<&str> {
+        self.redo.last().map(|t| t.name.as_str())
+    }
+    /// Block changes held for undo and redo.
+    pub fn stored_changes(&self) -> usize {
+        self.stored
+    }
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.stored = 0;
+    }
+}
@@ -1,25 +1,59 @@
-use chunk::{Block, Chunk};
+#[cfg(feature = "app")]
 use mobile_entry_point::mobile_entry_point;
-use noise::{Fbm, NoiseFn};
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(feature = "app", target_arch = "wasm32"))]
 use wasm_bindgen::prelude::*;
 
+#[cfg(feature = "app")]
 use winit::{
     event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::{Fullscreen, Window, WindowBuilder},
 };
 
-use crate::chunk::BlockKind;
-mod camera;
-mod chunk;
+pub mod blocks;
+pub mod brush;
+pub mod camera;
+mod camera_uniform;
+pub mod chunk;
+pub mod editor;
+pub mod entity;
+pub mod error;
+pub mod export;
+pub mod falling;
+pub mod fluid;
+pub mod fog;
+#[cfg(feature = "app")]
 mod gui;
+pub mod heightmap;
+#[cfg(feature = "app")]
+mod hud;
+pub mod journal;
+pub mod light;
+pub mod map;
+pub mod mesh;
 mod model;
+pub mod obj;
+pub mod offscreen;
+mod prop;
+pub mod raycast;
+#[cfg(feature = "app")]
 mod renderer;
+pub mod schematic;
+mod screenshot;
+pub mod shadow;
+pub mod shape;
+mod sky;
+pub mod state;
+pub mod surface;
 mod texture;
+pub mod tick;
+pub mod time_of_day;
 mod vertex;
+pub mod vox;
+pub mod world_renderer;
+pub mod worldgen;
 
-#[cfg(target_os = "android")]
+#[cfg(all(feature = "app", target_os = "android"))]
 fn init_logging() {
     android_logger::init_once(
         android_logger::Config::default()
@@ -27,54 +61,33 @@ fn init_logging() {
             .with_tag("vox3"),
     );
 }
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(feature = "app", target_arch = "wasm32"))]
 fn init_logging() {
     std::panic::set_hook(Box::new(console_error_panic_hook::hook));
     console_log::init_with_level(log::Level::Warn).expect("Could't initialize logger");
     println!("LOGGER SETUP");
 }
-#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+#[cfg(all(feature = "app", not(any(target_os = "android", target_arch = "wasm32"))))]
 fn init_logging() {
     // simple_logger::SimpleLogger::new().init().unwrap();
 }
 
+#[cfg(feature = "app")]
 fn run(event_loop: EventLoop<renderer::Event>, window: Window) {
-    let mut chunk1 = Chunk::new(1, 0, 0);
-    // let mut chunk2 = Chunk::new(0, 0, 10);
-    // let mut chunk3 = Chunk::new(1, 0, 1);
-    // let mut chunk4 = Chunk::new(0, 0, 0);
-    let mut chunks: Vec<Chunk> = vec![chunk1];
-    let fbm = Fbm::new();
-
-    // println!("{}",val);
-    for i in 0..chunk::WIDTH {
-        // for j in 0..((val.abs()*(chunk::HEIGHT as f64)) as i64) {
-        for k in 0..chunk::LENGTH {
-            let val = (fbm.get([i as f64 / 256.0, k as f64 / 256.0, 1.0]) * 16.0 + 6.0).abs();
-            println!("{}", val);
-
-            for j in 0..(val as i64) {
-                let kind = if i % 2 == 0 {BlockKind::Dirt} else {BlockKind::Stone};
-                for chunk in &mut chunks {
-                    chunk.set_block(Block {
-                        x: i,
-                        y: j,
-                        z: k,
-                        kind: kind,
-                    });
-                }
-               
-
-            }
-        }
-        // }
-    }
+    let chunks = worldgen::generate(worldgen::DEFAULT_SEED);
 
     let mut renderer =
-        renderer::TropicRenderer::new(&window, event_loop.create_proxy(), chunks);
+        match renderer::TropicRenderer::new(&window, event_loop.create_proxy(), chunks) {
+            Ok(renderer) => renderer,
+            Err(e) => {
+                log::error!("Couldn't start renderer: {}", e);
+                eprintln!("Couldn't start renderer: {}", e);
+                return;
+            }
+        };
 
     event_loop.run(move |event, _, control_flow| {
-        renderer.egui_platform.handle_event(&event);
+        renderer.gui.platform.handle_event(&event);
         *control_flow = ControlFlow::Wait;
         if let Event::WindowEvent { event, .. } = &event {
             renderer.camera_controller.process_events(&event);
@@ -102,7 +115,9 @@ fn run(event_loop: EventLoop<renderer::Event>, window: Window) {
                         state: ElementState::Released,
                         ..
                     } => {
-                        println!("TODO: screenshot")
+                        #[cfg(not(target_arch = "wasm32"))]
+                        renderer.request_screenshot();
+                        window.request_redraw();
                     }
                     _ => {}
                 },
@@ -133,7 +148,11 @@ fn run(event_loop: EventLoop<renderer::Event>, window: Window) {
             Event::RedrawRequested(_) => {
                 #[cfg(not(target_os = "android"))]
                 renderer.prepare_surface(&window);
-                renderer.render(&window);
+                if let Err(e) = renderer.render(&window) {
+                    log::error!("Rendering stopped: {}", e);
+                    eprintln!("Rendering stopped: {}", e);
+                    *control_flow = ControlFlow::Exit;
+                }
                 // for smooth fps
                 #[cfg(target_arch = "wasm32")]
                 window.request_redraw();
@@ -145,6 +164,7 @@ fn run(event_loop: EventLoop<renderer::Event>, window: Window) {
         }
     });
 }
+#[cfg(feature = "app")]
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen(start))]
 pub fn main_proxy() {
     init_logging();
@@ -170,6 +190,7 @@ pub fn main_proxy() {
 
     run(event_loop, window);
 }
+#[cfg(feature = "app")]
 #[mobile_entry_point]
 fn mobile_main() {
     init_logging();
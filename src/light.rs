@@ -0,0 +1,50 @@
+use cgmath::{InnerSpace, Vector3};
+
+/// The sun: a directional light plus a flat ambient term.
+#[derive(Debug, Clone, Copy)]
+pub struct Light {
+    /// Points from the world towards the light.
+    pub direction: Vector3<f32>,
+    pub color: [f32; 3],
+    pub ambient: f32,
+}
+
+impl Light {
+    /// Builds the sun direction from angles in degrees. Azimuth 0 points
+    /// north (+z), elevation 90 is straight up.
+    pub fn from_angles(azimuth: f32, elevation: f32) -> Vector3<f32> {
+        let (az, el) = (azimuth.to_radians(), elevation.to_radians());
+        Vector3::new(az.sin() * el.cos(), el.sin(), az.cos() * el.cos()).normalize()
+    }
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            direction: Self::from_angles(30.0, 60.0),
+            color: [1.0, 0.98, 0.9],
+            ambient: 0.35,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightUniform {
+    // vec3s are padded to 16 bytes in uniforms, the scalars fill the gap
+    direction: [f32; 3],
+    ambient: f32,
+    color: [f32; 3],
+    _padding: f32,
+}
+
+impl LightUniform {
+    pub fn new(light: &Light) -> Self {
+        Self {
+            direction: light.direction.normalize().into(),
+            ambient: light.ambient,
+            color: light.color,
+            _padding: 0.0,
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::{collections::HashMap, path::Path};
+
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    blocks,
+    chunk::{self, BlockKind, Chunk},
+};
+
+/// Brightness of a column higher than the one north of it, level with it
+/// and lower than it, so slopes read as relief.
+const SHADE_HIGHER: f32 = 1.0;
+const SHADE_LEVEL: f32 = 0.86;
+const SHADE_LOWER: f32 = 0.71;
+/// Water this deep or deeper is drawn darkest.
+const MAX_WATER_DEPTH: i64 = 8;
+
+/// The highest block of a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnTop {
+    pub y: i64,
+    pub kind: BlockKind,
+    /// Blocks of the same kind stacked under the top one, counting it
+    pub depth: i64,
+}
+
+/// Chunks grouped by column so the top block at any x and z can be found
+/// without searching every chunk.
+pub struct Columns<'a> {
+    /// Highest chunk first
+    columns: HashMap<(i64, i64), Vec<&'a Chunk>>,
+}
+
+impl<'a> Columns<'a> {
+    pub fn new(chunks: &'a [Chunk]) -> Self {
+        let mut columns: HashMap<(i64, i64), Vec<&Chunk>> = HashMap::new();
+        for chunk in chunks {
+            columns
+                .entry((chunk.chunk_x, chunk.chunk_z))
+                .or_default()
+                .push(chunk);
+        }
+        for stack in columns.values_mut() {
+            stack.sort_by_key(|c| -c.chunk_y);
+        }
+        Self { columns }
+    }
+    /// Walks down from the top of the world to the first block that isn't
+    /// air. `None` if the column is empty or has no chunks.
+    pub fn top(&self, x: i64, z: i64) -> Option<ColumnTop> {
+        let (key, (lx, _, lz)) = chunk::split_world_position((x, 0, z));
+        let stack = self.columns.get(&(key.0, key.2))?;
+        let mut blocks = stack.iter().flat_map(|chunk| {
+            (0..chunk::HEIGHT).rev().map(move |ly| {
+                let kind = chunk
+                    .get_block(lx, ly, lz)
+                    .map_or(BlockKind::None, |b| b.kind);
+                (chunk.chunk_y * chunk::HEIGHT + ly, kind)
+            })
+        });
+        let (y, kind) = blocks.find(|(_, kind)| *kind != BlockKind::Air)?;
+        let depth = 1 + blocks.take_while(|(_, k)| *k == kind).count() as i64;
+        Some(ColumnTop { y, kind, depth })
+    }
+    /// Smallest and largest block x and z inside any chunk, both included.
+    pub fn bounds(&self) -> Option<((i64, i64), (i64, i64))> {
+        let min_x = self.columns.keys().map(|k| k.0).min()?;
+        let min_z = self.columns.keys().map(|k| k.1).min()?;
+        let max_x = self.columns.keys().map(|k| k.0).max()?;
+        let max_z = self.columns.keys().map(|k| k.1).max()?;
+        Some((
+            (min_x * chunk::WIDTH, min_z * chunk::LENGTH),
+            (
+                (max_x + 1) * chunk::WIDTH - 1,
+                (max_z + 1) * chunk::LENGTH - 1,
+            ),
+        ))
+    }
+}
+
+/// Color of a column: its top block's color, lit by how it compares to the
+/// column north of it. Water is shaded by depth instead.
+pub fn column_color(top: ColumnTop, north: Option<ColumnTop>) -> Rgba<u8> {
+    let shade = if top.kind == BlockKind::Water {
+        1.0 - 0.4 * (top.depth.min(MAX_WATER_DEPTH) - 1) as f32 / (MAX_WATER_DEPTH - 1) as f32
+    } else {
+        match north {
+            Some(north) if north.y > top.y => SHADE_LOWER,
+            Some(north) if north.y < top.y => SHADE_HIGHER,
+            _ => SHADE_LEVEL,
+        }
+    };
+    let [r, g, b] = blocks::definition(top.kind)
+        .color
+        .map(|c| (c as f32 * shade).round() as u8);
+    Rgba([r, g, b, 255])
+}
+
+/// Draws `width` by `length` columns starting at `min` (x and z), one pixel
+/// per column with north at the top. Columns without blocks are transparent.
+pub fn render_area(chunks: &[Chunk], min: (i64, i64), width: u32, length: u32) -> RgbaImage {
+    let columns = Columns::new(chunks);
+    let mut image = RgbaImage::new(width, length);
+    for px in 0..width {
+        let x = min.0 + px as i64;
+        // Walking south, the column to the north is the previous one
+        let mut north = columns.top(x, min.1 - 1);
+        for pz in 0..length {
+            let top = columns.top(x, min.1 + pz as i64);
+            if let Some(top) = top {
+                image.put_pixel(px, pz, column_color(top, north));
+            }
+            north = top;
+        }
+    }
+    image
+}
+
+/// Draws every chunk, see `render_area`. `None` if there are no chunks.
+pub fn render(chunks: &[Chunk]) -> Option<RgbaImage> {
+    let (min, max) = Columns::new(chunks).bounds()?;
+    let width = (max.0 - min.0 + 1) as u32;
+    let length = (max.1 - min.1 + 1) as u32;
+    Some(render_area(chunks, min, width, length))
+}
+
+/// Renders the chunks and saves them as a PNG. An empty world gives a single
+/// transparent pixel.
+pub fn save_png(chunks: &[Chunk], path: impl AsRef<Path>) -> image::ImageResult<()> {
+    render(chunks)
+        .unwrap_or_else(|| RgbaImage::new(1, 1))
+        .save(path)
+}
+
+/// A square of columns around a point, as shown by the minimap. Blocks are
+/// centered on whole coordinates, so block 3 covers 2.5 to 3.5.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapView {
+    /// World x and z in the middle of the map
+    pub center: (f32, f32),
+    /// Columns across the map
+    pub span: u32,
+}
+
+impl MapView {
+    /// First column of the map. It only moves when the center crosses into
+    /// another block, so the image doesn't have to be redrawn every frame.
+    pub fn min(&self) -> (i64, i64) {
+        let half = self.span as i64 / 2;
+        (
+            self.center.0.round() as i64 - half,
+            self.center.1.round() as i64 - half,
+        )
+    }
+    /// Where a world x and z falls on the map, from 0 to 1 across it.
+    pub fn to_map(&self, x: f32, z: f32) -> (f32, f32) {
+        let (min_x, min_z) = self.min();
+        let span = self.span as f32;
+        (
+            (x + 0.5 - min_x as f32) / span,
+            (z + 0.5 - min_z as f32) / span,
+        )
+    }
+    /// The world x and z under a point of the map, the inverse of `to_map`.
+    pub fn to_world(&self, u: f32, v: f32) -> (f32, f32) {
+        let (min_x, min_z) = self.min();
+        let span = self.span as f32;
+        (min_x as f32 + u * span - 0.5, min_z as f32 + v * span - 0.5)
+    }
+    pub fn render(&self, chunks: &[Chunk]) -> RgbaImage {
+        render_area(chunks, self.min(), self.span, self.span)
+    }
+}
@@ -0,0 +1,99 @@
+use cgmath::{vec3, Vector3};
+
+use crate::{
+    chunk::{BlockKind, Chunk},
+    shape::{self, ShapeFace},
+};
+
+/// Tiles per side of the atlas. Keep in sync with `vs_main` in shader.wgsl.
+pub const ATLAS_TILES: f32 = 16.0;
+
+/// Triangles of every block of one kind in a chunk, built on the CPU the
+/// same way the GPU models are drawn. Positions are local to the chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockMesh {
+    pub kind: BlockKind,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    /// Into the whole atlas, with v pointing down
+    pub tex_coords: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+}
+
+impl BlockMesh {
+    fn new(kind: BlockKind) -> Self {
+        Self {
+            kind,
+            positions: vec![],
+            normals: vec![],
+            tex_coords: vec![],
+            indices: vec![],
+        }
+    }
+    /// Adds one face of a block at `position`.
+    fn push_face(&mut self, face: &ShapeFace, position: Vector3<f32>) {
+        let first = self.positions.len() as u32;
+        let tile = self.kind.get_tex_coords();
+        for (p, tc) in face.positions.iter().zip(face.tex_coords) {
+            self.positions.push((p + position).into());
+            self.normals.push(face.normal.into());
+            self.tex_coords.push([
+                (tc[0] + tile.x) / ATLAS_TILES,
+                (tc[1] + tile.y) / ATLAS_TILES,
+            ]);
+        }
+        self.indices.extend([0, 1, 2, 2, 3, 0].map(|i| first + i));
+    }
+    /// Box around every vertex as min and max corners.
+    pub fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        let mut min = [f32::INFINITY; 3];
+        let mut max = [f32::NEG_INFINITY; 3];
+        for p in &self.positions {
+            for i in 0..3 {
+                min[i] = min[i].min(p[i]);
+                max[i] = max[i].max(p[i]);
+            }
+        }
+        (min, max)
+    }
+}
+
+/// The mesh of `kind`, added at the end if there isn't one yet.
+fn mesh_of(meshes: &mut Vec<BlockMesh>, kind: BlockKind) -> &mut BlockMesh {
+    let index = match meshes.iter().position(|m| m.kind == kind) {
+        Some(index) => index,
+        None => {
+            meshes.push(BlockMesh::new(kind));
+            meshes.len() - 1
+        }
+    };
+    &mut meshes[index]
+}
+
+/// One mesh per kind of block in the chunk, in the order the kinds first
+/// appear. Only faces the renderer would draw are included.
+pub fn chunk_meshes(chunk: &Chunk) -> Vec<BlockMesh> {
+    let mut meshes: Vec<BlockMesh> = vec![];
+    for block in chunk.blocks() {
+        if block.kind == BlockKind::Air {
+            continue;
+        }
+        let position = vec3(block.x as f32, block.y as f32, block.z as f32);
+        let faces = match shape::of(block.kind) {
+            Some(shape) => chunk.visible_shape_faces(block, &shape),
+            None => shape::cube_faces(
+                &block.state.model_faces(&chunk.visible_faces(block)),
+                chunk.block_height(block),
+                block.state.rotation(),
+            ),
+        };
+        if faces.is_empty() {
+            continue;
+        }
+        let mesh = mesh_of(&mut meshes, block.kind);
+        for face in &faces {
+            mesh.push_face(face, position);
+        }
+    }
+    meshes
+}
@@ -1,9 +1,9 @@
 use std::sync::Arc;
 
-use cgmath::{Matrix3, Vector2, Vector3};
+use cgmath::{InnerSpace, Matrix3, SquareMatrix, Vector2, Vector3};
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    BindGroup, Buffer, BufferUsages, CommandEncoder, Device, RenderPass, VertexBufferLayout,
+    BindGroup, Buffer, BufferUsages, CommandEncoder, Device, Queue, RenderPass, VertexBufferLayout,
 };
 
 use crate::{
@@ -36,18 +36,43 @@ pub struct Model {
     vertex_buffer: Buffer,
     instance_buffer: Buffer,
     index_buffer: Buffer,
+    index_format: wgpu::IndexFormat,
     indexes: u32,
+    /// Middle of each quad of a `sorted` model, in the units it's drawn in
+    quads: Vec<Vector3<f32>>,
     // model_data: Vec<ModelData>,
     // position: cgmath::Vector3<f32>,
     bind_group: Arc<BindGroup>,
     instances: u32,
+    /// Instances the instance buffer has room for
+    capacity: u32,
 }
 pub struct ModelData {
     pub position: Vector3<f32>,
     //TODO: Better name
     pub kind: BlockKind,
+    /// Scale of the cube, grown or shrunk around the middle of its bottom
+    /// face so fluids sit lower than a full block
+    pub size: Vector3<f32>,
+    /// Turns the cube around its middle, see `state::BlockState::rotation`
+    pub rotation: Matrix3<f32>,
 }
 impl Model {
+    fn instance_data(model_data: &[ModelData]) -> Vec<f32> {
+        let mut xinstances: Vec<f32> = vec![];
+        for md in model_data {
+            let mat4 = cgmath::Matrix4::from_translation(
+                md.position + Vector3::new(0.0, (md.size.y - 1.0) / 2.0, 0.0),
+            ) * cgmath::Matrix4::from(md.rotation)
+                * cgmath::Matrix4::from_nonuniform_scale(md.size.x, md.size.y, md.size.z);
+            let mat4_bytes: &[[f32; 4]; 4] = &mat4.into();
+            let mat4b: &[f32] = bytemuck::cast_slice(mat4_bytes);
+            xinstances.extend(mat4b);
+            let tex_coord_bytes: &[f32; 2] = &md.kind.get_tex_coords().into();
+            xinstances.extend(tex_coord_bytes);
+        }
+        xinstances
+    }
     fn get_verts_and_indexs(f: &Faces) -> (Vec<Vertex>, Vec<u16>) {
         let mut v = vec![];
         let mut i: Vec<u16> = vec![];
@@ -96,24 +121,28 @@ impl Model {
         bind_group: Arc<BindGroup>,
     ) -> Self {
         let (verts, indexes) = Self::get_verts_and_indexs(f);
-        let mut xinstances: Vec<f32> = vec![];
-        for md in &model_data {
-            let mat4 = cgmath::Matrix4::from_translation(md.position);
-            let mat4_bytes: &[[f32; 4]; 4] = &mat4.into();
-            let mat4b: &[f32] = bytemuck::cast_slice(mat4_bytes);
-            xinstances.extend(mat4b);
-            let tex_coord_bytes: &[f32; 2] = &md.kind.get_tex_coords().into();
-            xinstances.extend(tex_coord_bytes);
-        }
+        Self::with_geometry(device, &verts, &indexes, model_data, bind_group)
+    }
+    /// A model of any triangles rather than cube faces, such as the blocks
+    /// of a chunk that aren't cubes. Positions are twice the size they're
+    /// drawn at, like the cube's.
+    pub fn with_geometry(
+        device: &Device,
+        verts: &[Vertex],
+        indexes: &[u16],
+        model_data: Vec<ModelData>,
+        bind_group: Arc<BindGroup>,
+    ) -> Self {
+        let xinstances = Self::instance_data(&model_data);
 
         let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&verts),
+            contents: bytemuck::cast_slice(verts),
             usage: BufferUsages::VERTEX,
         });
         let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Index buffer"),
-            contents: bytemuck::cast_slice::<u16, _>(&indexes),
+            contents: bytemuck::cast_slice::<u16, _>(indexes),
             usage: BufferUsages::INDEX,
         });
         let instance_buffer = device.create_buffer_init(&BufferInitDescriptor {
@@ -126,13 +155,126 @@ impl Model {
         Self {
             vertex_buffer,
             index_buffer,
+            index_format: wgpu::IndexFormat::Uint16,
             indexes: indexes.len() as u32,
+            quads: vec![],
             instances: model_data.len() as u32,
+            capacity: model_data.len() as u32,
             // model_data: positions,
             instance_buffer,
             bind_group,
         }
     }
+    /// A model with no instances yet, whose instances are rewritten in place
+    /// by `write_instances`. For things that move every frame.
+    pub fn dynamic(device: &Device, f: &Faces, capacity: u32, bind_group: Arc<BindGroup>) -> Self {
+        let (verts, indexes) = Self::get_verts_and_indexs(f);
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: bytemuck::cast_slice(&verts),
+            usage: BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Index buffer"),
+            contents: bytemuck::cast_slice::<u16, _>(&indexes),
+            usage: BufferUsages::INDEX,
+        });
+        let capacity = capacity.max(1);
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Dynamic Instance Buffer"),
+            size: (capacity as usize * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            vertex_buffer,
+            index_buffer,
+            index_format: wgpu::IndexFormat::Uint16,
+            indexes: indexes.len() as u32,
+            quads: vec![],
+            instances: 0,
+            capacity,
+            instance_buffer,
+            bind_group,
+        }
+    }
+    /// A model of quads drawn furthest first, for translucent faces that
+    /// blend over what's behind them. `verts` come in fours, already where
+    /// they're drawn and with their tile added to their tex coords, as the
+    /// model has a single instance that doesn't move them. The quads are
+    /// drawn in the order given until `sort_back_to_front` is called.
+    pub fn sorted(device: &Device, verts: &[Vertex], bind_group: Arc<BindGroup>) -> Self {
+        let quads: Vec<Vector3<f32>> = verts
+            .chunks(4)
+            .map(|quad| {
+                let sum = quad.iter().fold(Vector3::new(0.0, 0.0, 0.0), |sum, v| {
+                    sum + Vector3::from(v.position)
+                });
+                // Positions are twice the size they're drawn at
+                sum / (quad.len() as f32 * 2.0)
+            })
+            .collect();
+        let order: Vec<usize> = (0..quads.len()).collect();
+        let indexes = quad_indexes(&order);
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: bytemuck::cast_slice(verts),
+            usage: BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Sorted Index buffer"),
+            contents: bytemuck::cast_slice::<u32, _>(&indexes),
+            usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+        });
+        let instance = InstanceRaw {
+            model: cgmath::Matrix4::identity().into(),
+            tx: [0.0, 0.0],
+        };
+        let instance_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Instance Buffer"),
+            contents: bytemuck::cast_slice(&[instance]),
+            usage: BufferUsages::VERTEX,
+        });
+        Self {
+            vertex_buffer,
+            index_buffer,
+            index_format: wgpu::IndexFormat::Uint32,
+            indexes: indexes.len() as u32,
+            quads,
+            instances: 1,
+            capacity: 1,
+            instance_buffer,
+            bind_group,
+        }
+    }
+    /// Reorders the quads of a `sorted` model so the ones furthest from
+    /// `eye` are drawn first. Does nothing to other models.
+    pub fn sort_back_to_front(&self, queue: &Queue, eye: Vector3<f32>) {
+        if self.quads.is_empty() {
+            return;
+        }
+        let indexes = quad_indexes(&back_to_front(&self.quads, eye));
+        queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&indexes));
+    }
+    /// Replaces the instances of a `dynamic` model. Returns false, leaving
+    /// the model as it was, if they don't fit.
+    pub fn write_instances(&mut self, queue: &Queue, model_data: &[ModelData]) -> bool {
+        if model_data.len() as u32 > self.capacity {
+            return false;
+        }
+        if !model_data.is_empty() {
+            queue.write_buffer(
+                &self.instance_buffer,
+                0,
+                bytemuck::cast_slice(&Self::instance_data(model_data)),
+            );
+        }
+        self.instances = model_data.len() as u32;
+        true
+    }
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
     // pub   fn render<'a> (&self, render_pass: &'a mut RenderPass<'a>, camera_bind_group: &'a mut BindGroup) {
     //     render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
     //     render_pass.set_bind_group(0, camera_bind_group, &[]);
@@ -140,8 +282,26 @@ impl Model {
 
     // }
 }
+/// Indexes of `points`, furthest from `eye` first.
+pub fn back_to_front(points: &[Vector3<f32>], eye: Vector3<f32>) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..points.len()).collect();
+    let distance = |i: &usize| (points[*i] - eye).magnitude2();
+    order.sort_by(|a, b| distance(b).total_cmp(&distance(a)));
+    order
+}
+
+/// Two triangles for each quad of four vertices, in the given order.
+fn quad_indexes(order: &[usize]) -> Vec<u32> {
+    order
+        .iter()
+        .flat_map(|&quad| [0, 1, 2, 2, 3, 0].map(|i| quad as u32 * 4 + i))
+        .collect()
+}
+
 pub trait RenderModel<'r> {
     fn render_model(&mut self, m: &'r Model);
+    /// Draws without binding the model's texture, for depth only passes.
+    fn render_model_geometry(&mut self, m: &'r Model);
     fn render_models(&mut self, m: Vec<Model>);
 }
 impl<'a, 'b> RenderModel<'b> for RenderPass<'a>
@@ -152,12 +312,19 @@ where
         self.set_bind_group(1, &m.bind_group, &[]);
         self.set_vertex_buffer(0, m.vertex_buffer.slice(..));
         self.set_vertex_buffer(1, m.instance_buffer.slice(..));
-        self.set_index_buffer(m.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        self.set_index_buffer(m.index_buffer.slice(..), m.index_format);
 
         // self.draw(0..m.vert_count, 0..1);
         self.draw_indexed(0..m.indexes, 0, 0..m.instances);
     }
 
+    fn render_model_geometry(&mut self, m: &'b Model) {
+        self.set_vertex_buffer(0, m.vertex_buffer.slice(..));
+        self.set_vertex_buffer(1, m.instance_buffer.slice(..));
+        self.set_index_buffer(m.index_buffer.slice(..), m.index_format);
+        self.draw_indexed(0..m.indexes, 0, 0..m.instances);
+    }
+
     fn render_models(&mut self, m: Vec<Model>) {
         // let i =
     }
@@ -0,0 +1,184 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::entity::Aabb;
+
+/// Handle to an OBJ model uploaded with `WorldRenderer::add_obj_model`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjModelId(pub(crate) usize);
+
+#[derive(Debug)]
+pub enum ObjError {
+    Load(tobj::LoadError),
+    /// A material's diffuse texture couldn't be read.
+    Texture(PathBuf, image::ImageError),
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Load(e) => write!(f, "failed to load OBJ: {}", e),
+            ObjError::Texture(path, e) => {
+                write!(f, "failed to load texture {}: {}", path.display(), e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Load(e) => Some(e),
+            ObjError::Texture(_, e) => Some(e),
+        }
+    }
+}
+
+impl From<tobj::LoadError> for ObjError {
+    fn from(e: tobj::LoadError) -> Self {
+        ObjError::Load(e)
+    }
+}
+
+/// The parts of an MTL material the renderer uses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjMaterial {
+    pub name: String,
+    /// `Kd`, multiplied with the texture
+    pub diffuse: [f32; 3],
+    /// `d`, fragments of materials below one half are cut out
+    pub dissolve: f32,
+    /// `map_Kd`, resolved against the directory of the OBJ file
+    pub diffuse_texture: Option<PathBuf>,
+}
+
+impl Default for ObjMaterial {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            diffuse: [1.0, 1.0, 1.0],
+            dissolve: 1.0,
+            diffuse_texture: None,
+        }
+    }
+}
+
+/// One triangulated group of an OBJ file with a single material. Every
+/// vertex has a normal and texture coordinate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjMesh {
+    pub name: String,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    /// With v pointing down, as wgpu samples textures
+    pub tex_coords: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+    /// Index into `ObjModel::materials`, `None` draws plain white
+    pub material: Option<usize>,
+}
+
+/// Meshes and materials read from an OBJ file and the MTL files it names.
+/// Loading doesn't touch the GPU or textures, see
+/// `WorldRenderer::add_obj_model` for that.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+    pub materials: Vec<ObjMaterial>,
+}
+
+impl ObjModel {
+    /// Missing or broken MTL files are logged and the meshes drawn white.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ObjError> {
+        let path = path.as_ref();
+        let (models, materials) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)?;
+        let materials = materials.unwrap_or_else(|e| {
+            log::warn!("materials of {} not loaded: {}", path.display(), e);
+            vec![]
+        });
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let materials: Vec<ObjMaterial> = materials
+            .into_iter()
+            .map(|m| ObjMaterial {
+                name: m.name,
+                diffuse: m.diffuse,
+                dissolve: m.dissolve,
+                diffuse_texture: (!m.diffuse_texture.is_empty())
+                    .then(|| dir.join(m.diffuse_texture)),
+            })
+            .collect();
+        let meshes = models
+            .into_iter()
+            .map(|m| convert_mesh(m, materials.len()))
+            .collect();
+        Ok(Self { meshes, materials })
+    }
+    /// Box around every vertex, `None` without any.
+    pub fn bounds(&self) -> Option<Aabb> {
+        let mut positions = self.meshes.iter().flat_map(|m| &m.positions);
+        let first = Vector3::from(*positions.next()?);
+        Some(positions.fold(Aabb::new(first, first), |aabb, p| {
+            Aabb::new(
+                Vector3::new(aabb.min.x.min(p[0]), aabb.min.y.min(p[1]), aabb.min.z.min(p[2])),
+                Vector3::new(aabb.max.x.max(p[0]), aabb.max.y.max(p[1]), aabb.max.z.max(p[2])),
+            )
+        }))
+    }
+}
+
+fn convert_mesh(model: tobj::Model, material_count: usize) -> ObjMesh {
+    let mesh = model.mesh;
+    let positions: Vec<[f32; 3]> = mesh
+        .positions
+        .chunks_exact(3)
+        .map(|p| [p[0], p[1], p[2]])
+        .collect();
+    let normals = if mesh.normals.len() == mesh.positions.len() {
+        mesh.normals.chunks_exact(3).map(|n| [n[0], n[1], n[2]]).collect()
+    } else {
+        smooth_normals(&positions, &mesh.indices)
+    };
+    let tex_coords = if mesh.texcoords.len() / 2 == positions.len() {
+        mesh.texcoords
+            .chunks_exact(2)
+            .map(|t| [t[0], 1.0 - t[1]])
+            .collect()
+    } else {
+        vec![[0.0, 0.0]; positions.len()]
+    };
+    ObjMesh {
+        name: model.name,
+        positions,
+        normals,
+        tex_coords,
+        indices: mesh.indices,
+        material: mesh.material_id.filter(|&m| m < material_count),
+    }
+}
+
+/// Per vertex normals for meshes without them, the sum of the surrounding
+/// face normals weighted by face area.
+fn smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut sums = vec![Vector3::new(0.0, 0.0, 0.0); positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vector3::from(positions[triangle[i] as usize]));
+        // The cross product's length is twice the area
+        let normal = (b - a).cross(c - a);
+        for &i in triangle {
+            sums[i as usize] += normal;
+        }
+    }
+    sums.into_iter()
+        .map(|n| {
+            if n.magnitude2() > 0.0 {
+                n.normalize().into()
+            } else {
+                [0.0, 1.0, 0.0]
+            }
+        })
+        .collect()
+}
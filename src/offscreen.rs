@@ -0,0 +1,94 @@
+use image::RgbaImage;
+use wgpu::{Backends, Device, Queue};
+
+use crate::{
+    camera::Camera, chunk::Chunk, entity::Entities, error::RendererError, screenshot::Screenshot,
+    world_renderer::WorldRenderer,
+};
+
+/// Offscreen targets are read back as PNGs, so render straight into RGBA.
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Renders chunks into a texture instead of a window surface, mainly for
+/// comparing frames in tests.
+pub struct OffscreenRenderer {
+    device: Device,
+    queue: Queue,
+    world: WorldRenderer,
+    width: u32,
+    height: u32,
+}
+
+impl OffscreenRenderer {
+    /// Uses the software fallback adapter so output doesn't depend on the GPU.
+    pub fn new_fallback(width: u32, height: u32) -> Result<Self, RendererError> {
+        let instance = wgpu::Instance::new(Backends::all());
+        let adapter = futures::executor::block_on(instance.request_adapter(
+            &wgpu::RequestAdapterOptions {
+                force_fallback_adapter: true,
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+            },
+        ))
+        .ok_or(RendererError::NoAdapter)?;
+        let (device, queue) = futures::executor::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("Offscreen device"),
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+            },
+            None,
+        ))?;
+        Ok(Self::new(device, queue, width, height))
+    }
+    pub fn new(device: Device, queue: Queue, width: u32, height: u32) -> Self {
+        let world = WorldRenderer::new(&device, &queue, FORMAT, width, height);
+        Self {
+            device,
+            queue,
+            world,
+            width,
+            height,
+        }
+    }
+    pub fn set_chunks(&mut self, chunks: &[Chunk]) {
+        self.world.set_chunks(&self.device, chunks);
+    }
+    pub fn set_entities(&mut self, entities: &Entities) {
+        self.world
+            .update_entities(&self.device, &self.queue, entities.iter());
+    }
+    pub fn render(&mut self, camera: &Camera) -> Option<RgbaImage> {
+        self.world.update_camera(&self.queue, camera);
+
+        let target = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen target"),
+            size: wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.world.render(&mut encoder, &view);
+        let screenshot = Screenshot::copy_from_texture(
+            &self.device,
+            &mut encoder,
+            &target,
+            self.width,
+            self.height,
+            FORMAT,
+        );
+        self.queue.submit(Some(encoder.finish()));
+        screenshot.read(&self.device)
+    }
+}
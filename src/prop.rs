@@ -0,0 +1,377 @@
+use cgmath::Matrix4;
+use wgpu::{
+    util::DeviceExt, BindGroup, BindGroupLayout, Buffer, BufferUsages, Device, Queue, RenderPass,
+    RenderPipeline, Sampler, ShaderModule, TextureFormat, VertexBufferLayout,
+};
+
+use crate::{
+    obj::{ObjError, ObjMaterial, ObjModel, ObjModelId},
+    texture::{self, Texture},
+    vertex::Vertex,
+};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct MaterialUniform {
+    diffuse: [f32; 4],
+}
+
+impl MaterialUniform {
+    fn new(material: &ObjMaterial) -> Self {
+        let [r, g, b] = material.diffuse;
+        Self {
+            diffuse: [r, g, b, material.dissolve],
+        }
+    }
+}
+
+type InstanceRaw = [[f32; 4]; 4];
+
+fn instance_buffer_layout<'a>() -> VertexBufferLayout<'a> {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] =
+        wgpu::vertex_attr_array![2 => Float32x4, 3 => Float32x4, 4 => Float32x4, 5 => Float32x4];
+    VertexBufferLayout {
+        array_stride: std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &ATTRIBUTES,
+    }
+}
+
+struct PropMesh {
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    indexes: u32,
+    /// Index into `PropModel::materials`
+    material: usize,
+}
+
+/// An uploaded OBJ model and everywhere it's drawn.
+struct PropModel {
+    meshes: Vec<PropMesh>,
+    /// One per OBJ material, then a plain white one for meshes without
+    materials: Vec<BindGroup>,
+    _textures: Vec<Texture>,
+    /// Set with `WorldRenderer::set_obj_instances`
+    placed: Vec<Matrix4<f32>>,
+    /// Entities using the model, rewritten every update
+    carried: Vec<Matrix4<f32>>,
+    instance_buffer: Buffer,
+    capacity: usize,
+    instances: u32,
+}
+
+/// Draws OBJ models with their own textures and materials in the main pass,
+/// after the opaque blocks. They are lit and shadowed like blocks but don't
+/// cast shadows themselves.
+pub struct PropRenderer {
+    pipeline: RenderPipeline,
+    /// Only made if the device supports `POLYGON_MODE_LINE`
+    wireframe_pipeline: Option<RenderPipeline>,
+    material_layout: BindGroupLayout,
+    /// Repeats, OBJ texture coordinates often go past the edges
+    sampler: Sampler,
+    white: Texture,
+    models: Vec<PropModel>,
+}
+
+impl PropRenderer {
+    /// Uses `vs_mesh` and `fs_mesh` from the block shader, with the camera
+    /// group at 0 and the shadow group at 2 just like the block pipelines.
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        shader: &ShaderModule,
+        camera_layout: &BindGroupLayout,
+        shadow_layout: &BindGroupLayout,
+        format: TextureFormat,
+    ) -> Self {
+        let material_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("material_bind_group_layout"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Prop Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, &material_layout, shadow_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = Self::create_pipeline(
+            device,
+            &pipeline_layout,
+            shader,
+            format,
+            wgpu::PolygonMode::Fill,
+        );
+        let wireframe_pipeline = if device
+            .features()
+            .contains(wgpu::Features::POLYGON_MODE_LINE)
+        {
+            Some(Self::create_pipeline(
+                device,
+                &pipeline_layout,
+                shader,
+                format,
+                wgpu::PolygonMode::Line,
+            ))
+        } else {
+            None
+        };
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Prop Sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let white = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            1,
+            1,
+            image::Rgba([255; 4]),
+        ));
+        let white = Texture::from_image(device, queue, &white, Some("White Texture")).unwrap();
+        Self {
+            pipeline,
+            wireframe_pipeline,
+            material_layout,
+            sampler,
+            white,
+            models: vec![],
+        }
+    }
+    fn create_pipeline(
+        device: &Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &ShaderModule,
+        format: TextureFormat,
+        polygon_mode: wgpu::PolygonMode,
+    ) -> RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Prop Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_mesh",
+                buffers: &[Vertex::desc(), instance_buffer_layout()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_mesh",
+                targets: &[format.into()],
+            }),
+            primitive: wgpu::PrimitiveState {
+                polygon_mode,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+    /// Reads the material textures and uploads everything. The model isn't
+    /// drawn until it has instances.
+    pub fn add(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        model: &ObjModel,
+    ) -> Result<ObjModelId, ObjError> {
+        let mut textures = vec![];
+        let mut materials = vec![];
+        for material in model.materials.iter().chain([&ObjMaterial::default()]) {
+            let texture = match &material.diffuse_texture {
+                Some(path) => {
+                    let img = image::open(path).map_err(|e| ObjError::Texture(path.clone(), e))?;
+                    textures.push(
+                        Texture::from_image(device, queue, &img, Some(&material.name)).unwrap(),
+                    );
+                    textures.last()
+                }
+                None => None,
+            };
+            materials.push(self.material_bind_group(
+                device,
+                texture.unwrap_or(&self.white),
+                material,
+            ));
+        }
+        let default_material = materials.len() - 1;
+
+        let meshes = model
+            .meshes
+            .iter()
+            .map(|mesh| {
+                let vertices: Vec<Vertex> = (0..mesh.positions.len())
+                    .map(|i| Vertex::new(mesh.positions[i], mesh.tex_coords[i], mesh.normals[i]))
+                    .collect();
+                PropMesh {
+                    vertex_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Prop Vertex Buffer"),
+                        contents: bytemuck::cast_slice(&vertices),
+                        usage: BufferUsages::VERTEX,
+                    }),
+                    index_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Prop Index Buffer"),
+                        contents: bytemuck::cast_slice(&mesh.indices),
+                        usage: BufferUsages::INDEX,
+                    }),
+                    indexes: mesh.indices.len() as u32,
+                    material: mesh.material.unwrap_or(default_material),
+                }
+            })
+            .collect();
+
+        self.models.push(PropModel {
+            meshes,
+            materials,
+            _textures: textures,
+            placed: vec![],
+            carried: vec![],
+            instance_buffer: Self::create_instance_buffer(device, 1),
+            capacity: 1,
+            instances: 0,
+        });
+        Ok(ObjModelId(self.models.len() - 1))
+    }
+    fn material_bind_group(
+        &self,
+        device: &Device,
+        texture: &Texture,
+        material: &ObjMaterial,
+    ) -> BindGroup {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Material Buffer"),
+            contents: bytemuck::bytes_of(&MaterialUniform::new(material)),
+            usage: BufferUsages::UNIFORM,
+        });
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.material_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("material_bind_group"),
+        })
+    }
+    fn create_instance_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Prop Instance Buffer"),
+            size: (capacity * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+    /// Where the model is placed in the world, replacing earlier placements.
+    pub fn set_placed(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        id: ObjModelId,
+        transforms: &[Matrix4<f32>],
+    ) {
+        if let Some(model) = self.models.get_mut(id.0) {
+            model.placed = transforms.to_vec();
+            model.upload(device, queue);
+        }
+    }
+    /// Replaces the entity instances of every model. Models missing from
+    /// `carried` lose theirs.
+    pub fn set_carried(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        mut carried: Vec<Vec<Matrix4<f32>>>,
+    ) {
+        carried.resize(self.models.len(), vec![]);
+        for (model, transforms) in self.models.iter_mut().zip(carried) {
+            if model.carried.is_empty() && transforms.is_empty() {
+                continue;
+            }
+            model.carried = transforms;
+            model.upload(device, queue);
+        }
+    }
+    /// Draws as lines if `wireframe` is set and the device supports it.
+    pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, wireframe: bool) {
+        if self.models.iter().all(|m| m.instances == 0) {
+            return;
+        }
+        match (&self.wireframe_pipeline, wireframe) {
+            (Some(pipeline), true) => render_pass.set_pipeline(pipeline),
+            _ => render_pass.set_pipeline(&self.pipeline),
+        }
+        for model in self.models.iter().filter(|m| m.instances > 0) {
+            render_pass.set_vertex_buffer(1, model.instance_buffer.slice(..));
+            for mesh in &model.meshes {
+                render_pass.set_bind_group(1, &model.materials[mesh.material], &[]);
+                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..mesh.indexes, 0, 0..model.instances);
+            }
+        }
+    }
+}
+
+impl PropModel {
+    /// Writes the placed and carried instances, growing the buffer when
+    /// they don't fit.
+    fn upload(&mut self, device: &Device, queue: &Queue) {
+        let data: Vec<InstanceRaw> = self
+            .placed
+            .iter()
+            .chain(&self.carried)
+            .map(|&m| m.into())
+            .collect();
+        if data.len() > self.capacity {
+            self.capacity = data.len().next_power_of_two();
+            self.instance_buffer = PropRenderer::create_instance_buffer(device, self.capacity);
+        }
+        if !data.is_empty() {
+            queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&data));
+        }
+        self.instances = data.len() as u32;
+    }
+}
@@ -0,0 +1,88 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use crate::chunk::{self, BlockKind, Chunk, WorldPosition};
+
+/// The first block a ray runs into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub position: WorldPosition,
+    /// Points out of the face that was hit, all zero if the ray started
+    /// inside the block
+    pub normal: (i64, i64, i64),
+    pub distance: f32,
+}
+
+impl RayHit {
+    /// The block in front of the face that was hit, where a placed block
+    /// would go.
+    pub fn adjacent(&self) -> WorldPosition {
+        (
+            self.position.0 + self.normal.0,
+            self.position.1 + self.normal.1,
+            self.position.2 + self.normal.2,
+        )
+    }
+}
+
+/// Steps through the blocks along a ray and returns the first one that isn't
+/// air, up to `max_distance` away. Blocks are centered on whole coordinates,
+/// the same way they are drawn.
+pub fn raycast(
+    chunks: &[Chunk],
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    max_distance: f32,
+) -> Option<RayHit> {
+    if direction.magnitude2() == 0.0 {
+        return None;
+    }
+    let direction = direction.normalize();
+    // Shifted so blocks span whole numbers to the next
+    let start = [origin.x + 0.5, origin.y + 0.5, origin.z + 0.5];
+    let dir = [direction.x, direction.y, direction.z];
+    let mut cell = start.map(|c| c.floor() as i64);
+    let mut step = [0; 3];
+    // Distance along the ray to the next boundary on each axis, and between
+    // boundaries
+    let mut next = [f32::INFINITY; 3];
+    let mut delta = [f32::INFINITY; 3];
+    for i in 0..3 {
+        if dir[i] > 0.0 {
+            step[i] = 1;
+            delta[i] = 1.0 / dir[i];
+            next[i] = (cell[i] as f32 + 1.0 - start[i]) * delta[i];
+        } else if dir[i] < 0.0 {
+            step[i] = -1;
+            delta[i] = -1.0 / dir[i];
+            next[i] = (start[i] - cell[i] as f32) * delta[i];
+        }
+    }
+
+    let mut normal = (0, 0, 0);
+    let mut distance = 0.0;
+    while distance <= max_distance {
+        let position = (cell[0], cell[1], cell[2]);
+        let kind = chunk::world_block(chunks, position);
+        if kind != BlockKind::Air && kind != BlockKind::None {
+            return Some(RayHit {
+                position,
+                normal,
+                distance,
+            });
+        }
+        let axis = if next[0] <= next[1] && next[0] <= next[2] {
+            0
+        } else if next[1] <= next[2] {
+            1
+        } else {
+            2
+        };
+        distance = next[axis];
+        next[axis] += delta[axis];
+        cell[axis] += step[axis];
+        let mut n = [0; 3];
+        n[axis] = -step[axis];
+        normal = (n[0], n[1], n[2]);
+    }
+    None
+}
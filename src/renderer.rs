@@ -1,39 +1,34 @@
-use std::{
-    cell::RefCell,
-    sync::{Arc, Mutex},
-};
-
-use cgmath::vec3;
-use egui::FullOutput;
-use egui_demo_lib::WrapApp;
-use egui_wgpu_backend::ScreenDescriptor;
-use egui_winit_platform::{Platform, PlatformDescriptor};
-use epi::App;
-use futures::{
-    executor::{LocalPool, LocalSpawner},
-    task::SpawnExt,
-};
-use image::Primitive;
-// use imgui::FontSource;
+use cgmath::{EuclideanSpace, InnerSpace};
 use instant::Instant;
-use wgpu::{
-    util::{BufferInitDescriptor, DeviceExt, StagingBelt},
-    Adapter, Backends, BindGroup, BindGroupLayout, Buffer, BufferUsages, Device, Face, Features,
-    Instance, Limits, PrimitiveState, Queue, RenderPassDepthStencilAttachment, RenderPipeline,
-    ShaderModule, Surface, SurfaceConfiguration,
-};
-use wgpu_glyph::{GlyphBrush, GlyphBrushBuilder, Section, Text};
+use wgpu::{Adapter, Backends, Device, Features, Instance, Limits, Queue, Surface, SurfaceConfiguration};
 use winit::{dpi::PhysicalSize, event_loop::EventLoopProxy, window::Window};
 
 use crate::{
+    blocks,
+    brush::{self, BrushMode},
     camera::{Camera, CameraController},
-    chunk::Chunk,
-    gui::{self, TropicGui},
-    model::{self, Faces, Model, RenderModel},
-    texture::{self, Texture},
-    vertex::Vertex,
+    chunk::{self, BlockData, BlockKind, Chunk},
+    editor::{self, Clipboard, Edit, Selection},
+    entity::{Entities, EntityMesh},
+    error::RendererError,
+    export,
+    gui::{self, EditCommand, GuiLayer},
+    map::{self, MapView},
+    raycast::{self, RayHit},
+    hud::HudLayer,
+    journal::{Changed, Journal},
+    schematic::Schematic,
+    state::BlockState,
+    screenshot::{self, Screenshot},
+    surface::{self, FrameOutcome, WindowSurface},
+    tick::{BlockTicks, FixedStep},
+    time_of_day::TimeOfDay,
+    world_renderer::WorldRenderer,
 };
 
+/// Furthest the editor picks blocks from the camera
+const EDITOR_REACH: f32 = 64.0;
+
 #[cfg(target_os = "android")]
 const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
 #[cfg(target_arch = "wasm32")]
@@ -49,56 +44,28 @@ impl TropicRenderer {
         #[cfg(not(target_os = "android"))]
         Some(unsafe { instance.create_surface(&window) })
     }
-    fn get_default_adapter(instance: &Instance, surface: &Option<Surface>) -> Adapter {
+    fn get_default_adapter(
+        instance: &Instance,
+        surface: &Option<Surface>,
+    ) -> Result<Adapter, RendererError> {
         futures::executor::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
             force_fallback_adapter: false,
             power_preference: wgpu::PowerPreference::default(),
             compatible_surface: surface.as_ref(),
         }))
-        .expect("Failed to find an appropriate adapter")
+        .ok_or(RendererError::NoAdapter)
     }
     fn get_device_limits(adapter: &Adapter) -> Limits {
         #[cfg(target_arch = "wasm32")]
         return Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits());
         Limits::downlevel_defaults().using_resolution(adapter.limits())
     }
-    fn create_render_pipeline(
-        device: &Device,
-        shader: &ShaderModule,
-        bind_groups_layouts: Vec<&BindGroupLayout>,
-        primitive: Option<PrimitiveState>,
-    ) -> RenderPipeline {
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &bind_groups_layouts,
-            push_constant_ranges: &[],
-        });
-        // let mut primitive = wgpu::PrimitiveState::default();
+    fn get_surface_usage() -> wgpu::TextureUsages {
+        // Screenshots copy straight out of the swapchain texture
+        #[cfg(target_arch = "wasm32")]
+        return wgpu::TextureUsages::RENDER_ATTACHMENT;
 
-        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[Vertex::desc(), model::get_instance_buffer_layout()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[FORMAT.into()],
-            }),
-            primitive: primitive.unwrap_or_default(),
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less, // 1.
-                stencil: wgpu::StencilState::default(),     // 2.
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-        })
+        wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
     }
     fn get_features() -> Features {
         #[cfg(target_arch = "wasm32")]
@@ -110,11 +77,11 @@ impl TropicRenderer {
         window: &Window,
         event_loop_proxy: EventLoopProxy<Event>,
         chunks: Vec<Chunk>,
-    ) -> Self {
+    ) -> Result<Self, RendererError> {
         let instance = wgpu::Instance::new(Backends::all());
 
         let surface = Self::create_initial_surface(window, &instance);
-        let adapter = Self::get_default_adapter(&instance, &surface);
+        let adapter = Self::get_default_adapter(&instance, &surface)?;
 
         let (device, queue) = futures::executor::block_on(adapter.request_device(
             &wgpu::DeviceDescriptor {
@@ -123,14 +90,11 @@ impl TropicRenderer {
                 limits: Self::get_device_limits(&adapter),
             },
             None,
-        ))
-        .expect("Failed to create device");
-
-        let staging_belt = wgpu::util::StagingBelt::new(1024);
+        ))?;
 
         let size = window.inner_size();
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage: Self::get_surface_usage(),
             format: FORMAT,
             width: size.width,
             height: size.height,
@@ -141,305 +105,44 @@ impl TropicRenderer {
             surface.as_ref().unwrap().configure(&device, &config);
         }
 
-        let shader = device.create_shader_module(&wgpu::include_wgsl!("./shader.wgsl"));
-
         let camera = create_camera(&config);
-        let mut camera_uniform = CameraUniform::new();
-        camera_uniform.update_view_proj(&camera);
-
-        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Camera Buffer"),
-            contents: bytemuck::cast_slice(&[camera_uniform]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-        let camera_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-                label: Some("camera_bind_group_layout"),
-            });
-        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &camera_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: camera_buffer.as_entire_binding(),
-            }],
-            label: Some("camera_bind_group"),
-        });
-
         let camera_controller = CameraController::new(0.2);
-        let demo_app = gui::TropicGui {
-            wireframe: false,
-            camera_speed: 0.2,
-        };
-
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        // This should match the filterable field of the
-                        // corresponding Texture entry above.
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-                label: Some("texture_bind_group_layout"),
-            });
-        let render_pipeline = Self::create_render_pipeline(
-            &device,
-            &shader,
-            vec![&camera_bind_group_layout, &texture_bind_group_layout],
-            None,
-        );
-        #[cfg(target_arch = "wasm32")]
-        let wire_frame_render_pipeline = None;
-
-        let wireframe_primitive = PrimitiveState {
-            polygon_mode: wgpu::PolygonMode::Line,
-            ..Default::default()
-        };
-        #[cfg(not(target_arch = "wasm32"))]
-        let wire_frame_render_pipeline = Some(Self::create_render_pipeline(
-            &device,
-            &shader,
-            vec![&camera_bind_group_layout, &texture_bind_group_layout],
-            Some(wireframe_primitive),
-        ));
 
-        let font_brush = Self::setup_fonts(&device);
-        let local_pool = futures::executor::LocalPool::new();
-        let local_spawner = local_pool.spawner();
+        let mut world = WorldRenderer::new(&device, &queue, FORMAT, size.width, size.height);
+        world.set_chunks(&device, &chunks);
+        world.update_camera(&queue, &camera);
 
-        // let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
-        //     label: Some("Vertex Buffer"),
-        //     contents: bytemuck::cast_slice(VERTICES),
-        //     usage: BufferUsages::VERTEX,
-        // });
-        // let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
-        //     label: Some("Index buffer"),
-        //     contents: bytemuck::cast_slice::<u16, _>(&[0, 1, 2, 2, 3, 0]),
-        //     usage: BufferUsages::INDEX,
-        // });
-        let platform = Self::setup_egui(window, &size);
-        let egui_rpass = egui_wgpu_backend::RenderPass::new(&device, FORMAT, 1);
-        // let demo_app = egui_demo_lib::WrapApp::default();
+        // Each chunk replays the same random ticks from one run to the next
+        let block_ticks = (0..chunks.len() as u64).map(BlockTicks::new).collect();
 
-        let start_time = Instant::now();
-        let previous_frame_time = None;
-
-        {
-
-            // let render_pipeline_layout =
-            //     device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            //         label: Some("Render Pipeline Layout"),
-            //         bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout],
-            //         push_constant_ranges: &[],
-            //     });
-        }
+        let hud = HudLayer::new(&device, FORMAT);
+        let gui = GuiLayer::new(&device, FORMAT, window, event_loop_proxy);
 
-        let atlas =
-            Texture::from_bytes(&device, &queue, include_bytes!("../atlas.png"), "stone").unwrap();
-        let atlas_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&atlas.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&atlas.sampler),
-                },
-            ],
-            label: Some("atlas_bind_group"),
-        });
-        let atlas_bind_group = Arc::new(atlas_bind_group);
-
-        // let mut textures = vec![];
-        // {
-        //     let stone = Texture::from_bytes(
-        //         &device,
-        //         &queue,
-        //         include_bytes!("../assets/stone.png"),
-        //         "stone",
-        //     )
-        //     .unwrap();
-        //     let stone_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        //         layout: &texture_bind_group_layout,
-        //         entries: &[
-        //             wgpu::BindGroupEntry {
-        //                 binding: 0,
-        //                 resource: wgpu::BindingResource::TextureView(&stone.view),
-        //             },
-        //             wgpu::BindGroupEntry {
-        //                 binding: 1,
-        //                 resource: wgpu::BindingResource::Sampler(&stone.sampler),
-        //             },
-        //         ],
-        //         label: Some("diffuse_bind_group"),
-        //     });
-        //     textures.push(Arc::new(stone_bind_group));
-        //     let dirt = Texture::from_bytes(
-        //         &device,
-        //         &queue,
-        //         include_bytes!("../assets/dirt.png"),
-        //         "dirt",
-        //     )
-        //     .unwrap();
-        //     let dirt_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        //         layout: &texture_bind_group_layout,
-        //         entries: &[
-        //             wgpu::BindGroupEntry {
-        //                 binding: 0,
-        //                 resource: wgpu::BindingResource::TextureView(&dirt.view),
-        //             },
-        //             wgpu::BindGroupEntry {
-        //                 binding: 1,
-        //                 resource: wgpu::BindingResource::Sampler(&dirt.sampler),
-        //             },
-        //         ],
-        //         label: Some("diffuse_bind_group"),
-        //     });
-        //     textures.push(Arc::new(dirt_bind_group));
-
-        //     let end_stone = Texture::from_bytes(
-        //         &device,
-        //         &queue,
-        //         include_bytes!("../assets/end_stone.png"),
-        //         "dirt",
-        //     )
-        //     .unwrap();
-        //     let end_stone_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        //         layout: &texture_bind_group_layout,
-        //         entries: &[
-        //             wgpu::BindGroupEntry {
-        //                 binding: 0,
-        //                 resource: wgpu::BindingResource::TextureView(&end_stone.view),
-        //             },
-        //             wgpu::BindGroupEntry {
-        //                 binding: 1,
-        //                 resource: wgpu::BindingResource::Sampler(&end_stone.sampler),
-        //             },
-        //         ],
-        //         label: Some("diffuse_bind_group"),
-        //     });
-        //     textures.push(Arc::new(end_stone_bind_group));
-        // }
-        let depth_texture =
-            texture::Texture::create_depth_texture(&device, &config, "depth_texture");
-        let w = 64;
-
-        // let positions = (-w..w).map(|i| (-w, ..w).map(|j| vec3(k as f32, 0.0, 0.0)).collect::<Vec<_>>()).collect::<Vec<_>>();
-        // let mut positions = vec![];
-        // for i in -w..w {
-        //     for j in -w..w {
-        //         positions.push(vec3(i as f32, 0.0, j as f32));
-        //     }
-        // }
-        // let m = Model::new(
-        //     &device,
-        //     &Faces {
-        //         top: true,
-        //         ..Default::default()
-        //     },
-        //     positions,
-        //     textures[0].clone(),
-        // );
-        // let mut positions = vec![];
-        // for i in -w..w {
-        //     for j in -w..w {
-        //         positions.push(vec3(i as f32, 1.0, j as f32));
-        //     }
-        // }
-        // let m2 = Model::new(
-        //     &device,
-        //     &Faces {
-        //         top: true,
-        //         ..Default::default()
-        //     },
-        //     positions,
-        //     textures[2].clone(),
-        // );
-
-        // let models = vec![m, m2];
-        // let mut models = vec![];
-        let models = chunks
-            .iter()
-            .map(|c| c.models(&device, atlas_bind_group.clone()))
-            .flatten()
-            .collect::<Vec<_>>();
-        // for i in -w..w {
-        //     for j in -w..w {
-        //         models.push(Model::new(
-        //             &device,
-        //             &Faces {
-        //                 top: true,
-        //                 bottom: false,
-        //                 north: j == w-1,
-        //                 south: j == -w,
-        //                 east: i == w-1,
-        //                 west: i == -w,
-        //             },
-        //             vec3(i as f32, 0.0, j as f32),
-        //             textures[2].clone(),
-        //         ));
-        //     }
-        // }
-        Self {
+        Ok(Self {
             instance,
             device,
             surface,
             config,
-            render_pipeline,
-            wire_frame_render_pipeline,
-            font_brush,
             size,
-            staging_belt,
             queue,
-            local_pool,
-            local_spawner,
             fps_smoothing: 0.9,
             fps_measurement: 0.0,
-            // vertex_buffer,
-            // index_buffer,
-            egui_rpass,
-            tropic_gui: demo_app,
-            start_time,
-            egui_platform: platform,
-            scale_factor: window.scale_factor(),
-            previous_frame_time,
-            repaint_signal: Arc::new(ExampleRepaintSignal(Mutex::new(event_loop_proxy))),
+            world,
+            hud,
+            gui,
             camera,
-            camera_uniform,
-            camera_bind_group,
-            camera_buffer,
             camera_controller,
-            // textures: textures,
-            atlas: atlas,
-            atlas_bind_group,
-            depth_texture,
-            models: models,
-        }
+            time_of_day: TimeOfDay::default(),
+            last_update: Instant::now(),
+            chunks,
+            block_ticks,
+            simulation: FixedStep::default(),
+            entities: Entities::new(),
+            screenshot_requested: false,
+            minimap_stale: true,
+            clipboard: None,
+            journal: Journal::default(),
+        })
     }
     pub fn resume(&mut self, window: &Window) {
         self.prepare_surface(window);
@@ -448,6 +151,10 @@ impl TropicRenderer {
         self.surface.take();
     }
     pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        // Minimized windows report a zero size, which can't be configured
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
         self.config.width = size.width;
         self.config.height = size.height;
         self.size = size;
@@ -459,36 +166,8 @@ impl TropicRenderer {
                 .configure(&self.device, &self.config);
             // self.request_redraw();
         }
-        self.depth_texture =
-            texture::Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
-    }
-    fn setup_fonts(device: &Device) -> GlyphBrush<()> {
-        let inconsolata = wgpu_glyph::ab_glyph::FontArc::try_from_slice(include_bytes!(
-            "Inconsolata-Regular.ttf"
-        ))
-        .unwrap();
-
-        GlyphBrushBuilder::using_font(inconsolata).build(&device, FORMAT)
-    }
-    fn draw_text(&mut self, content: &str, x: f32, y: f32, color: u32) {
-        let r = (color & 0xFF000000) >> 24;
-        let g = (color & 0x00FF0000) >> 16;
-        let b = (color & 0x000000FF) >> 8;
-        let a = color & 0x000000FF;
-
-        self.font_brush.queue(Section {
-            screen_position: (x, y),
-            bounds: (self.size.width as f32, self.size.height as f32),
-            text: vec![Text::new(content)
-                .with_color([
-                    r as f32 / 255.0,
-                    g as f32 / 255.0,
-                    b as f32 / 255.0,
-                    a as f32 / 255.0,
-                ])
-                .with_scale(20.0)],
-            ..Section::default()
-        });
+        self.world
+            .resize(&self.device, self.config.width, self.config.height);
     }
     pub fn prepare_surface(&mut self, window: &Window) {
         {
@@ -506,44 +185,355 @@ impl TropicRenderer {
         self.fps_measurement =
             (self.fps_measurement * self.fps_smoothing) + (fps * (1.0 - self.fps_smoothing))
     }
-    pub fn setup_egui(window: &Window, size: &PhysicalSize<u32>) -> Platform {
-        Platform::new(PlatformDescriptor {
-            physical_width: size.width as u32,
-            physical_height: size.height as u32,
-            scale_factor: window.scale_factor(),
-            font_definitions: egui::FontDefinitions::default(),
-            style: Default::default(),
-        })
-    }
     fn draw_hud(&mut self) {
-        self.draw_text(
-            &format!("FPS {}", self.fps_measurement * 1000.0),
-            1.0,
-            1.0,
-            0xff00ffff,
-        );
+        let bounds = (self.size.width as f32, self.size.height as f32);
+        self.hud.draw_hud(self.fps_measurement * 1000.0, bounds);
+    }
+    /// Captures the next rendered frame, including the HUD and egui.
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+    fn save_screenshot(&mut self, screenshot: Screenshot) {
+        let result = match screenshot.read(&self.device) {
+            Some(img) => screenshot::save_timestamped(&img).map_err(|e| e.to_string()),
+            None => Err("couldn't read back frame".to_string()),
+        };
+        match result {
+            Ok(path) => self.hud.show_message(format!("Saved {}", path.display())),
+            Err(e) => {
+                log::error!("Screenshot failed: {}", e);
+                self.hud.show_message(format!("Screenshot failed: {}", e));
+            }
+        }
+    }
+    fn export_world(&mut self) {
+        match export::save_timestamped(&self.chunks) {
+            Ok(path) => self.hud.show_message(format!("Exported {}", path.display())),
+            Err(e) => {
+                log::error!("Export failed: {}", e);
+                self.hud.show_message(format!("Export failed: {}", e));
+            }
+        }
     }
     fn update(&mut self) {
-        self.camera_controller.speed = self.tropic_gui.camera_speed;
+        self.world.wireframe = self.gui.tropic_gui.wireframe;
+        self.camera_controller.speed = self.gui.tropic_gui.camera_speed;
         self.camera_controller.update_camera(&mut self.camera);
-        self.camera_uniform.update_view_proj(&self.camera);
-        self.queue.write_buffer(
-            &self.camera_buffer,
-            0,
-            bytemuck::cast_slice(&[self.camera_uniform]),
+        self.world.update_camera(&self.queue, &self.camera);
+        let now = Instant::now();
+        let elapsed = (now - self.last_update).as_secs_f32();
+        self.last_update = now;
+
+        self.update_time_of_day(elapsed);
+        self.update_simulation(elapsed);
+        self.update_entities(elapsed);
+        if std::mem::take(&mut self.gui.tropic_gui.export_gltf) {
+            self.export_world();
+        }
+        if let Some(command) = self.gui.tropic_gui.editor.command.take() {
+            self.run_edit_command(command);
+        }
+        self.update_minimap();
+        self.world
+            .set_fog_settings(&self.queue, self.gui.tropic_gui.fog);
+        let shadows = self.gui.tropic_gui.shadows;
+        self.world
+            .set_shadow_settings(&self.device, &self.queue, shadows);
+        self.gui.tropic_gui.shadow_splits = self.world.shadow_splits().to_vec();
+    }
+    /// Advances the clock from wherever the GUI slider left it, then hands
+    /// the new hour back so the slider follows the day.
+    fn update_time_of_day(&mut self, elapsed: f32) {
+        let gui = &mut self.gui.tropic_gui;
+        self.time_of_day.set_hours(gui.time_of_day);
+        self.time_of_day.time_scale = gui.time_scale;
+        self.time_of_day.sun_azimuth = gui.sun_azimuth;
+        self.time_of_day.advance(elapsed);
+        gui.time_of_day = self.time_of_day.hours();
+
+        self.world
+            .update_light(&self.queue, &self.time_of_day.light());
+        self.world.update_sky(&self.queue, &self.time_of_day.sky());
+    }
+    /// Runs the block ticks due since the last frame at the fixed tick rate
+    /// and remeshes if any block changed.
+    fn update_simulation(&mut self, elapsed: f32) {
+        let steps = self.simulation.advance(elapsed);
+        let random_tick_speed = self.gui.tropic_gui.random_tick_speed;
+        let mut changed = false;
+        for _ in 0..steps {
+            for (chunk, ticks) in self.chunks.iter_mut().zip(&mut self.block_ticks) {
+                ticks.random_tick_speed = random_tick_speed;
+                changed |= !ticks.tick(chunk).is_empty();
+            }
+        }
+        if changed {
+            self.world.set_chunks(&self.device, &self.chunks);
+            self.minimap_stale = true;
+        }
+        // The last falling block landing counts as a change, which clears it
+        let falling: Vec<_> = self
+            .chunks
+            .iter()
+            .zip(&self.block_ticks)
+            .flat_map(|(c, t)| t.falling_blocks().iter().map(move |b| (c.origin(), *b)))
+            .collect();
+        if steps > 0 && (changed || !falling.is_empty()) {
+            self.world.set_falling_blocks(&self.device, &falling);
+        }
+    }
+    /// Remeshes the chunks an edit touched and queues ticks for the blocks
+    /// it changed, so placed water flows and sand falls.
+    fn remesh(&mut self, changed: &Changed) {
+        if changed.is_empty() {
+            return;
+        }
+        // Edits can add chunks, which need ticks of their own
+        for i in self.block_ticks.len()..self.chunks.len() {
+            self.block_ticks.push(BlockTicks::new(i as u64));
+        }
+        for &position in &changed.blocks {
+            let (key, local) = chunk::split_world_position(position);
+            if let Some(i) = self.chunks.iter().position(|c| c.position() == key) {
+                self.block_ticks[i].block_changed(&self.chunks[i], local);
+            }
+        }
+        let indices: Vec<usize> = self
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| changed.chunks.contains(&c.position()))
+            .map(|(i, _)| i)
+            .collect();
+        self.world.update_chunks(&self.device, &self.chunks, indices);
+        self.minimap_stale = true;
+    }
+    /// Writes edits into the world as one undoable step. Returns how many
+    /// blocks changed.
+    fn apply_edits(&mut self, name: &str, edits: &[Edit]) -> usize {
+        let changed = self.journal.apply(&mut self.chunks, name, edits);
+        self.remesh(&changed);
+        changed.blocks.len()
+    }
+    /// The block in the middle of the view, within reach.
+    fn view_hit(&self) -> Option<RayHit> {
+        raycast::raycast(
+            &self.chunks,
+            self.camera.eye,
+            self.camera.target - self.camera.eye,
+            EDITOR_REACH,
+        )
+    }
+    fn run_edit_command(&mut self, command: EditCommand) {
+        let panel = &self.gui.tropic_gui.editor;
+        let selection = Selection::new(panel.corner(0), panel.corner(1));
+        let (kind, replace_from) = (panel.kind, panel.replace_from);
+        let (paste_at, transform, paste_air) = (panel.corner(0), panel.transform, panel.paste_air);
+        let brush = panel.brush;
+        let message = match command {
+            EditCommand::PickCorner(i) => {
+                match self.view_hit() {
+                    Some(hit) => {
+                        let (x, y, z) = hit.position;
+                        self.gui.tropic_gui.editor.corners[i] = [x, y, z];
+                        return;
+                    }
+                    None => "No block in view".to_string(),
+                }
+            }
+            EditCommand::Place => match self.view_hit() {
+                Some(hit) => {
+                    let view = self.camera.target - self.camera.eye;
+                    // How far up the block's face the view hit, for blocks
+                    // that go in the top or bottom half
+                    let hit_y = self.camera.eye.y + view.normalize().y * hit.distance + 0.5;
+                    let definition = blocks::definition(kind);
+                    let hit_height = hit_y - hit_y.floor();
+                    let state =
+                        BlockState::placed(definition.properties, view, hit.normal, hit_height);
+                    let pos = hit.adjacent();
+                    let data = BlockData {
+                        kind,
+                        state,
+                        level: 0,
+                    };
+                    let changed = self.journal.apply(&mut self.chunks, "Place", &[(pos, data)]);
+                    self.remesh(&changed);
+                    format!("Placed {}", definition.name)
+                }
+                None => "No block in view".to_string(),
+            },
+            EditCommand::Fill => {
+                let changed = self.apply_edits("Fill", &editor::fill(&selection, kind));
+                format!("Filled {} blocks", changed)
+            }
+            EditCommand::Replace => {
+                let edits = editor::replace(&self.chunks, &selection, replace_from, kind);
+                format!("Replaced {} blocks", self.apply_edits("Replace", &edits))
+            }
+            EditCommand::Hollow => {
+                let edits = editor::hollow(&self.chunks, &selection);
+                format!("Cleared {} blocks", self.apply_edits("Hollow", &edits))
+            }
+            EditCommand::Copy => {
+                let clipboard = editor::copy(&self.chunks, &selection);
+                self.gui.tropic_gui.editor.clipboard_size = Some(clipboard.size);
+                self.clipboard = Some(clipboard);
+                format!("Copied {} blocks", selection.volume())
+            }
+            EditCommand::Paste => match &self.clipboard {
+                Some(clipboard) => {
+                    let edits = editor::paste(clipboard, paste_at, transform, paste_air);
+                    format!("Pasted {} blocks", self.apply_edits("Paste", &edits))
+                }
+                None => "Nothing to paste".to_string(),
+            },
+            EditCommand::SaveTemplate => {
+                let path = self.gui.tropic_gui.editor.template_path.clone();
+                match &self.clipboard {
+                    Some(clipboard) => match Schematic::new(clipboard.clone()).save(&path) {
+                        Ok(()) => format!("Saved {}", path),
+                        Err(e) => format!("Couldn't save {}: {}", path, e),
+                    },
+                    None => "Nothing to save".to_string(),
+                }
+            }
+            EditCommand::LoadTemplate => {
+                let path = self.gui.tropic_gui.editor.template_path.clone();
+                match Schematic::load(&path) {
+                    Ok(template) => {
+                        self.gui.tropic_gui.editor.clipboard_size = Some(template.blocks.size);
+                        self.clipboard = Some(template.blocks);
+                        format!("Loaded {}", path)
+                    }
+                    Err(e) => format!("Couldn't load {}: {}", path, e),
+                }
+            }
+            EditCommand::Sculpt => match self.view_hit() {
+                Some(hit) => {
+                    // Adding builds out from the face in view instead of
+                    // into the ground
+                    let center = if brush.mode == BrushMode::Add {
+                        hit.adjacent()
+                    } else {
+                        hit.position
+                    };
+                    let edits = brush::stroke(&self.chunks, &brush, center);
+                    let changed = self.apply_edits(brush.mode.name(), &edits);
+                    format!("{}: {} blocks", brush.mode.name(), changed)
+                }
+                None => "No block in view".to_string(),
+            },
+            EditCommand::Undo => match self.journal.next_undo().map(str::to_string) {
+                Some(name) => {
+                    if let Some(changed) = self.journal.undo(&mut self.chunks) {
+                        self.remesh(&changed);
+                    }
+                    format!("Undid {}", name)
+                }
+                None => "Nothing to undo".to_string(),
+            },
+            EditCommand::Redo => match self.journal.next_redo().map(str::to_string) {
+                Some(name) => {
+                    if let Some(changed) = self.journal.redo(&mut self.chunks) {
+                        self.remesh(&changed);
+                    }
+                    format!("Redid {}", name)
+                }
+                None => "Nothing to redo".to_string(),
+            },
+        };
+        let panel = &mut self.gui.tropic_gui.editor;
+        panel.next_undo = self.journal.next_undo().map(str::to_string);
+        panel.next_redo = self.journal.next_redo().map(str::to_string);
+        self.hud.show_message(message);
+    }
+    /// Moves the camera to where the minimap was clicked, keeping its view
+    /// direction and putting it above the ground there.
+    fn teleport(&mut self, x: f32, z: f32) {
+        let ground = map::Columns::new(&self.chunks)
+            .top(x.round() as i64, z.round() as i64)
+            .map_or(self.camera.eye.y, |top| top.y as f32 + 2.0);
+        let offset = cgmath::point3(x, ground, z) - self.camera.eye;
+        self.camera.eye += offset;
+        self.camera.target += offset;
+    }
+    /// Hands the camera to the minimap and redraws the map when blocks
+    /// changed or the camera moved to another block.
+    fn update_minimap(&mut self) {
+        if let Some((x, z)) = self.gui.tropic_gui.minimap.teleport.take() {
+            self.teleport(x, z);
+        }
+        let minimap = &mut self.gui.tropic_gui.minimap;
+        let eye = self.camera.eye;
+        let forward = self.camera.target - eye;
+        minimap.player = (eye.x, eye.z);
+        minimap.heading = (forward.x, forward.z);
+        if !minimap.open {
+            return;
+        }
+        let view = MapView {
+            center: (eye.x, eye.z),
+            span: minimap.span,
+        };
+        let moved = view.min() != minimap.view.min() || view.span != minimap.view.span;
+        if !moved && !self.minimap_stale {
+            return;
+        }
+        self.minimap_stale = false;
+        // One block per pixel blurs when egui scales it up, so draw it big
+        let scale = (gui::MINIMAP_SIZE as u32 / view.span).max(1);
+        let image = image::imageops::resize(
+            &view.render(&self.chunks),
+            view.span * scale,
+            view.span * scale,
+            image::imageops::FilterType::Nearest,
         );
+        let size = [image.width() as usize, image.height() as usize];
+        minimap.image = Some(egui::ColorImage::from_rgba_unmultiplied(size, &image));
+        minimap.view = view;
+    }
+    fn update_entities(&mut self, elapsed: f32) {
+        let gui = &mut self.gui.tropic_gui;
+        if std::mem::take(&mut gui.clear_entities) {
+            self.entities.clear();
+        }
+        if std::mem::take(&mut gui.spawn_entity) {
+            // Fan new entities out around the camera target
+            let angle = self.entities.len() as f32 * 2.4;
+            let velocity = cgmath::vec3(angle.cos(), 0.2, angle.sin());
+            let kind = [BlockKind::Sand, BlockKind::Gravel, BlockKind::Lava]
+                [self.entities.len() % 3];
+            self.entities.spawn(
+                self.camera.target.to_vec() + cgmath::vec3(0.0, 2.0, 0.0),
+                velocity,
+                cgmath::vec3(0.3, 0.3, 0.3),
+                EntityMesh::Block(kind),
+            );
+        }
+        gui.entity_count = self.entities.len();
+        self.entities.update(elapsed);
+        self.world
+            .update_entities(&self.device, &self.queue, self.entities.iter());
     }
-    pub fn render(&mut self, window: &Window) {
+    /// Draws a frame. Only fails if rendering can't continue, in which case
+    /// the app should shut down.
+    pub fn render(&mut self, window: &Window) -> Result<(), RendererError> {
         self.update();
-        self.egui_platform
-            .update_time(self.start_time.elapsed().as_secs_f64());
         match &self.surface {
             Some(surface) => {
                 // let start_of_frame = std::time::Instant::now();
                 let start_of_frame = Instant::now();
-                let frame = surface
-                    .get_current_texture()
-                    .expect("Failed to acquire next swap chain texture");
+                let mut window_surface = WindowSurface {
+                    surface,
+                    device: &self.device,
+                    config: &self.config,
+                };
+                let frame = match surface::acquire_frame(&mut window_surface) {
+                    FrameOutcome::Ready(frame) => frame,
+                    FrameOutcome::Skip => return Ok(()),
+                    FrameOutcome::OutOfMemory => return Err(RendererError::OutOfMemory),
+                };
                 let view = frame
                     .texture
                     .create_view(&wgpu::TextureViewDescriptor::default());
@@ -551,131 +541,53 @@ impl TropicRenderer {
                 let mut encoder = self
                     .device
                     .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-                //    if self.tropic_gui.wireframe {
-                //      self.render_pipeline = Self::create_render_pipeline(self.device, self.shader, bind_groups_layouts, primitive)
-                //     }
-                {
-                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                        label: None,
-                        color_attachments: &[wgpu::RenderPassColorAttachment {
-                            view: &view,
-                            resolve_target: None,
-                            ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(wgpu::Color {
-                                    r: 0x78 as f64 / 255.0,
-                                    g: 0xa7 as f64 / 255.0,
-                                    b: 0xff as f64 / 255.0,
-                                    a: 1.0,
-                                }),
-                                store: true,
-                            },
-                        }],
-                        depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
-                            view: &self.depth_texture.view,
-                            depth_ops: Some(wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(1.0),
-                                store: true,
-                            }),
-                            stencil_ops: None,
-                        }),
-                    });
-                    if self.tropic_gui.wireframe {
-                        render_pass
-                            .set_pipeline(&self.wire_frame_render_pipeline.as_ref().unwrap());
-                    } else {
-                        render_pass.set_pipeline(&self.render_pipeline);
-                    }
-                    render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-                    for m in &self.models {
-                        render_pass.render_model(&m);
-                    }
-                }
+                self.world.render(&mut encoder, &view);
                 self.draw_hud();
 
                 // Draw fonts
-                {
-                    self.font_brush
-                        .draw_queued(
-                            &self.device,
-                            &mut self.staging_belt,
-                            &mut encoder,
-                            &view,
-                            self.size.width,
-                            self.size.height,
-                        )
-                        .expect("Draw queued");
-                    self.staging_belt.finish();
-                }
+                self.hud.render(
+                    &self.device,
+                    &mut encoder,
+                    &view,
+                    self.size.width,
+                    self.size.height,
+                );
                 // Egui
-                // #[cfg(not(target_arch = "wasm32"))]
-                {
-                    let egui_start = Instant::now();
-                    self.egui_platform.begin_frame();
-                    let app_output = epi::backend::AppOutput::default();
-                    let mut frame = epi::Frame::new(epi::backend::FrameData {
-                        info: epi::IntegrationInfo {
-                            name: "egui_example",
-                            web_info: None,
-                            cpu_usage: self.previous_frame_time,
-                            native_pixels_per_point: Some(self.scale_factor as _),
-                            prefer_dark_mode: None,
-                        },
-                        output: app_output,
-                        repaint_signal: self.repaint_signal.clone(),
-                    });
-                    self.tropic_gui
-                        .update(&self.egui_platform.context(), &mut frame);
-
-                    // let (_output, paint_commands) = self.egui_platform.end_frame(Some(&window));
-                    let FullOutput {
-                        platform_output: _output,
-                        shapes: paint_commands,
-                        textures_delta,
-                        ..
-                    } = self.egui_platform.end_frame(Some(&window));
-
-                    let paint_jobs = self.egui_platform.context().tessellate(paint_commands);
-                    let frame_time = (Instant::now() - egui_start).as_secs_f64() as f32;
-                    self.previous_frame_time = Some(frame_time);
-
-                    let screen_descriptor = ScreenDescriptor {
-                        physical_width: self.config.width,
-                        physical_height: self.config.height,
-                        scale_factor: window.scale_factor() as f32,
-                    };
-                    // self.egui_rpass.
-                    self.egui_rpass
-                        .add_textures(&self.device, &self.queue, &textures_delta)
-                        .expect("Couldn't add textures to EGUI");
-                    // self.egui_rpass.update_user_textures(&self.device, &self.queue);
-                    self.egui_rpass.update_buffers(
+                self.gui.render(
+                    &self.device,
+                    &self.queue,
+                    &mut encoder,
+                    &view,
+                    window,
+                    self.size,
+                );
+
+                let screenshot = if self.screenshot_requested {
+                    self.screenshot_requested = false;
+                    Some(Screenshot::copy_from_texture(
                         &self.device,
-                        &self.queue,
-                        &paint_jobs,
-                        &screen_descriptor,
-                    );
-
-                    self.egui_rpass
-                        .execute(&mut encoder, &view, &paint_jobs, &screen_descriptor, None)
-                        .unwrap();
-                }
+                        &mut encoder,
+                        &frame.texture,
+                        self.config.width,
+                        self.config.height,
+                        FORMAT,
+                    ))
+                } else {
+                    None
+                };
 
                 self.queue.submit(Some(encoder.finish()));
+                if let Some(screenshot) = screenshot {
+                    self.save_screenshot(screenshot);
+                }
 
                 frame.present();
-                self.local_spawner
-                    .spawn(self.staging_belt.recall())
-                    .expect("Recall staging belt");
-
-                self.local_pool.run_until_stalled();
+                self.hud.recall();
                 self.calculate_fps(start_of_frame.elapsed().as_millis() as f32);
-
-                // println!("Presented frame, {}", );
-
-                // frames += 1;
             }
             None => {}
         }
+        Ok(())
     }
 }
 
@@ -696,75 +608,35 @@ fn create_camera(config: &SurfaceConfiguration) -> Camera {
     camera
 }
 
+/// The full app: a window surface with the world, HUD text and egui layered
+/// on top. Embedders that only want the world should use `WorldRenderer`.
 pub struct TropicRenderer {
     instance: Instance,
     device: Device,
     surface: Option<Surface>,
     config: SurfaceConfiguration,
-    render_pipeline: RenderPipeline,
-    wire_frame_render_pipeline: Option<RenderPipeline>,
-
-    font_brush: GlyphBrush<()>,
     size: PhysicalSize<u32>,
-    staging_belt: StagingBelt,
-    local_pool: LocalPool,
-    local_spawner: LocalSpawner,
     queue: Queue,
     fps_smoothing: f32,
     fps_measurement: f32,
-    // vertex_buffer: Buffer,
-    // index_buffer: Buffer,
-    egui_rpass: egui_wgpu_backend::RenderPass,
-    tropic_gui: TropicGui,
-    start_time: Instant,
-    pub egui_platform: Platform,
-    scale_factor: f64,
-    previous_frame_time: Option<f32>,
-    repaint_signal: Arc<ExampleRepaintSignal>,
+    world: WorldRenderer,
+    hud: HudLayer,
+    pub gui: GuiLayer,
     camera: Camera,
-    camera_uniform: CameraUniform,
-    camera_buffer: Buffer,
-    camera_bind_group: BindGroup,
     pub camera_controller: CameraController,
-    // textures: Vec<Arc<BindGroup>>,
-    atlas: Texture,
-    atlas_bind_group: Arc<BindGroup>,
-    depth_texture: Texture,
-    models: Vec<Model>,
+    time_of_day: TimeOfDay,
+    last_update: Instant,
+    chunks: Vec<Chunk>,
+    block_ticks: Vec<BlockTicks>,
+    simulation: FixedStep,
+    entities: Entities,
+    screenshot_requested: bool,
+    /// Blocks changed since the minimap was last drawn
+    minimap_stale: bool,
+    clipboard: Option<Clipboard>,
+    journal: Journal,
 }
 #[derive(Debug, Clone, Copy)]
 pub enum Event {
     RequestRedraw,
 }
-struct ExampleRepaintSignal(std::sync::Mutex<winit::event_loop::EventLoopProxy<Event>>);
-
-impl epi::backend::RepaintSignal for ExampleRepaintSignal {
-    fn request_repaint(&self) {
-        self.0.lock().unwrap().send_event(Event::RequestRedraw).ok();
-    }
-}
-
-unsafe impl Sync for ExampleRepaintSignal {}
-unsafe impl Send for ExampleRepaintSignal {}
-
-#[repr(C)]
-// This is so we can store this in a buffer
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct CameraUniform {
-    // We can't use cgmath with bytemuck directly so we'll have
-    // to convert the Matrix4 into a 4x4 f32 array
-    view_proj: [[f32; 4]; 4],
-}
-
-impl CameraUniform {
-    fn new() -> Self {
-        use cgmath::SquareMatrix;
-        Self {
-            view_proj: cgmath::Matrix4::identity().into(),
-        }
-    }
-
-    fn update_view_proj(&mut self, camera: &Camera) {
-        self.view_proj = camera.build_view_projection_matrix().into();
-    }
-}
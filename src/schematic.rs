@@ -0,0 +1,288 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    blocks,
+    chunk::{BlockData, BlockKind, Chunk, WorldPosition},
+    editor::{self, Clipboard, Edit, Selection, Transform},
+    state::BlockState,
+};
+
+/// The latest version written. Older versions are still read.
+pub const VERSION: u32 = 3;
+/// Templates can't be larger than this along any axis.
+pub const MAX_SIZE: i64 = u16::MAX as i64;
+/// Different blocks a template can hold, as many as a u8 palette index
+/// reaches.
+pub const MAX_PALETTE: usize = u8::MAX as usize + 1;
+
+const MAGIC: &[u8; 4] = b"VXSC";
+
+#[derive(Debug)]
+pub enum SchematicError {
+    Io(io::Error),
+    /// Not a template file or a broken one, or a template that doesn't fit
+    /// the format when writing.
+    Format(&'static str),
+    /// Written by a newer version than this one reads.
+    Version(u32),
+    /// The palette names a block this version doesn't have.
+    UnknownBlock(String),
+    /// A region bigger than `MAX_SIZE` along some axis.
+    TooLarge([i64; 3]),
+}
+
+impl fmt::Display for SchematicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchematicError::Io(e) => write!(f, "{}", e),
+            SchematicError::Format(e) => write!(f, "invalid template: {}", e),
+            SchematicError::Version(v) => {
+                write!(f, "template version {} is newer than {}", v, VERSION)
+            }
+            SchematicError::UnknownBlock(name) => write!(f, "unknown block \"{}\"", name),
+            SchematicError::TooLarge(size) => write!(
+                f,
+                "{}x{}x{} is larger than a template can be",
+                size[0], size[1], size[2]
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SchematicError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SchematicError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SchematicError {
+    fn from(e: io::Error) -> Self {
+        SchematicError::Io(e)
+    }
+}
+
+/// A region of blocks saved for placing elsewhere, with free form metadata
+/// such as a name or author.
+///
+/// On disk, all little endian: the magic `VXSC`, a u32 version, the size as
+/// three u16, the palette as a u16 count of entries, the metadata as a u16
+/// count of u16 length prefixed key and value pairs, then the blocks as a
+/// u32 count of runs, each a u16 length and a u8 palette index. Blocks run
+/// x first, then z, then y.
+///
+/// Each palette entry is a u8 length prefixed block name, then the block's
+/// state as a u8 from `BlockState::bits` and its fluid level as a u8.
+/// Version 1 had only the names, read as the default state and level 0.
+/// Versions 1 and 2 counted the palette in a u8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schematic {
+    pub blocks: Clipboard,
+    pub metadata: BTreeMap<String, String>,
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    r.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u16(r: &mut impl Read) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    r.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_string(r: &mut impl Read, len: usize) -> Result<String, SchematicError> {
+    let mut bytes = vec![0; len];
+    r.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|_| SchematicError::Format("text isn't UTF-8"))
+}
+
+/// Longer strings are turned away by `Schematic::write` before anything is
+/// written.
+fn write_string(out: &mut impl Write, s: &str) -> io::Result<()> {
+    out.write_all(&(s.len() as u16).to_le_bytes())?;
+    out.write_all(s.as_bytes())
+}
+
+impl Schematic {
+    pub fn new(blocks: Clipboard) -> Self {
+        Self {
+            blocks,
+            metadata: BTreeMap::new(),
+        }
+    }
+    /// Copies a region out of the world. Positions without a chunk are
+    /// saved as air.
+    pub fn from_chunks(chunks: &[Chunk], selection: &Selection) -> Result<Self, SchematicError> {
+        let (x, y, z) = selection.size();
+        if x > MAX_SIZE || y > MAX_SIZE || z > MAX_SIZE {
+            return Err(SchematicError::TooLarge([x, y, z]));
+        }
+        Ok(Self::new(editor::copy(chunks, selection)))
+    }
+    /// The edits placing the template with its lowest corner at `origin`.
+    /// Air is skipped unless `with_air` is set.
+    pub fn place(&self, origin: WorldPosition, transform: Transform, with_air: bool) -> Vec<Edit> {
+        editor::paste(&self.blocks, origin, transform, with_air)
+    }
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SchematicError> {
+        Self::read(&mut BufReader::new(fs::File::open(path)?))
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SchematicError> {
+        let mut out = BufWriter::new(fs::File::create(path)?);
+        self.write(&mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+    pub fn read(r: &mut impl Read) -> Result<Self, SchematicError> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(SchematicError::Format("missing VXSC header"));
+        }
+        let version = read_u32(r)?;
+        if version > VERSION {
+            return Err(SchematicError::Version(version));
+        }
+        let size = (
+            read_u16(r)? as i64,
+            read_u16(r)? as i64,
+            read_u16(r)? as i64,
+        );
+
+        let entries = if version >= 3 {
+            read_u16(r)? as usize
+        } else {
+            read_u8(r)? as usize
+        };
+        if entries > MAX_PALETTE {
+            return Err(SchematicError::Format("palette larger than 256 blocks"));
+        }
+        let mut palette = vec![];
+        for _ in 0..entries {
+            let len = read_u8(r)? as usize;
+            let name = read_string(r, len)?;
+            let kind = match blocks::by_name(&name) {
+                Some(kind) => kind,
+                None => return Err(SchematicError::UnknownBlock(name)),
+            };
+            let (state, level) = if version >= 2 {
+                let state = BlockState::from_bits(read_u8(r)?)
+                    .ok_or(SchematicError::Format("invalid block state"))?;
+                (state, read_u8(r)?)
+            } else {
+                (BlockState::default(), 0)
+            };
+            palette.push(BlockData { kind, state, level });
+        }
+
+        let mut metadata = BTreeMap::new();
+        for _ in 0..read_u16(r)? {
+            let len = read_u16(r)? as usize;
+            let key = read_string(r, len)?;
+            let len = read_u16(r)? as usize;
+            metadata.insert(key, read_string(r, len)?);
+        }
+
+        let volume = (size.0 * size.1 * size.2) as usize;
+        let mut blocks = vec![];
+        for _ in 0..read_u32(r)? {
+            let len = read_u16(r)? as usize;
+            let data = palette
+                .get(read_u8(r)? as usize)
+                .ok_or(SchematicError::Format("block outside the palette"))?;
+            if blocks.len() + len > volume {
+                return Err(SchematicError::Format("more blocks than the size holds"));
+            }
+            blocks.resize(blocks.len() + len, *data);
+        }
+        if blocks.len() != volume {
+            return Err(SchematicError::Format("fewer blocks than the size holds"));
+        }
+        Ok(Self {
+            blocks: Clipboard { size, blocks },
+            metadata,
+        })
+    }
+    pub fn write(&self, out: &mut impl Write) -> Result<(), SchematicError> {
+        let (x, y, z) = self.blocks.size;
+        if x > MAX_SIZE || y > MAX_SIZE || z > MAX_SIZE {
+            return Err(SchematicError::TooLarge([x, y, z]));
+        }
+        // Lengths and counts of metadata are written as u16
+        let max = u16::MAX as usize;
+        if self.metadata.len() > max {
+            return Err(SchematicError::Format("more than 65535 metadata entries"));
+        }
+        if self
+            .metadata
+            .iter()
+            .any(|(k, v)| k.len() > max || v.len() > max)
+        {
+            return Err(SchematicError::Format("metadata longer than 65535 bytes"));
+        }
+        // Blocks are kept in the order they first appear
+        let mut palette: Vec<BlockData> = vec![];
+        let mut runs: Vec<(u16, u8)> = vec![];
+        for data in &self.blocks.blocks {
+            // Nothing and air place the same
+            let data = match data.kind {
+                BlockKind::None => BlockKind::Air.into(),
+                _ => *data,
+            };
+            let index = match palette.iter().position(|d| *d == data) {
+                Some(i) => i,
+                None if palette.len() == MAX_PALETTE => {
+                    return Err(SchematicError::Format("palette larger than 256 blocks"));
+                }
+                None => {
+                    palette.push(data);
+                    palette.len() - 1
+                }
+            } as u8;
+            match runs.last_mut() {
+                Some((len, i)) if *i == index && *len < u16::MAX => *len += 1,
+                _ => runs.push((1, index)),
+            }
+        }
+
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        for axis in [x, y, z] {
+            out.write_all(&(axis as u16).to_le_bytes())?;
+        }
+        out.write_all(&(palette.len() as u16).to_le_bytes())?;
+        for data in palette {
+            let name = blocks::definition(data.kind).name;
+            out.write_all(&[name.len() as u8])?;
+            out.write_all(name.as_bytes())?;
+            out.write_all(&[data.state.bits(), data.level])?;
+        }
+        out.write_all(&(self.metadata.len() as u16).to_le_bytes())?;
+        for (key, value) in &self.metadata {
+            write_string(out, key)?;
+            write_string(out, value)?;
+        }
+        out.write_all(&(runs.len() as u32).to_le_bytes())?;
+        for (len, index) in runs {
+            out.write_all(&len.to_le_bytes())?;
+            out.write_all(&[index])?;
+        }
+        Ok(())
+    }
+}
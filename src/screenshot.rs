@@ -0,0 +1,119 @@
+use std::{
+    num::NonZeroU32,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use image::RgbaImage;
+use wgpu::{Buffer, CommandEncoder, Device, TextureFormat};
+
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// Rows copied out of a texture have to be a multiple of
+/// `COPY_BYTES_PER_ROW_ALIGNMENT` wide, so the buffer ends up with padding
+/// at the end of every row.
+pub fn padded_bytes_per_row(width: u32) -> u32 {
+    let unpadded = width * BYTES_PER_PIXEL;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    ((unpadded + align - 1) / align) * align
+}
+
+/// Strips row padding and swizzles BGRA into RGBA if the source format needs it.
+pub fn to_rgba(data: &[u8], width: u32, height: u32, format: TextureFormat) -> Vec<u8> {
+    let padded = padded_bytes_per_row(width) as usize;
+    let unpadded = (width * BYTES_PER_PIXEL) as usize;
+    let bgra = matches!(
+        format,
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+    );
+
+    let mut pixels = Vec::with_capacity(unpadded * height as usize);
+    for row in data.chunks(padded).take(height as usize) {
+        pixels.extend_from_slice(&row[..unpadded]);
+    }
+    if bgra {
+        for px in pixels.chunks_mut(4) {
+            px.swap(0, 2);
+        }
+    }
+    pixels
+}
+
+/// A pending copy of a frame into a mappable buffer.
+pub struct Screenshot {
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+}
+
+impl Screenshot {
+    pub fn copy_from_texture(
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        texture: &wgpu::Texture,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Self {
+        let bytes_per_row = padded_bytes_per_row(width);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Screenshot buffer"),
+            size: (bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(bytes_per_row),
+                    rows_per_image: NonZeroU32::new(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        Self {
+            buffer,
+            width,
+            height,
+            format,
+        }
+    }
+    /// Blocks until the copy has finished. Must be called after the encoder
+    /// used in `copy_from_texture` was submitted.
+    pub fn read(self, device: &Device) -> Option<RgbaImage> {
+        let slice = self.buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        futures::executor::block_on(mapping).ok()?;
+
+        let pixels = to_rgba(&slice.get_mapped_range(), self.width, self.height, self.format);
+        self.buffer.unmap();
+        RgbaImage::from_raw(self.width, self.height, pixels)
+    }
+}
+
+pub fn save_timestamped(img: &RgbaImage) -> image::ImageResult<PathBuf> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let path = PathBuf::from(format!(
+        "screenshot_{}_{:03}.png",
+        now.as_secs(),
+        now.subsec_millis()
+    ));
+    img.save(&path)?;
+    Ok(path)
+}
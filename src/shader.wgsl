@@ -4,14 +4,57 @@ struct CameraUniform {
 [[group(0), binding(0)]] // 1.
 var<uniform> camera: CameraUniform;
 
+struct LightUniform {
+    // Points towards the light
+    direction: vec3<f32>;
+    ambient: f32;
+    color: vec3<f32>;
+};
+[[group(0), binding(1)]]
+var<uniform> light: LightUniform;
+
+struct FogUniform {
+    color: vec3<f32>;
+    // 0 off, 1 linear, 2 exponential, 3 height
+    mode: u32;
+    eye: vec3<f32>;
+    start: f32;
+    end: f32;
+    density: f32;
+    height_falloff: f32;
+    base_height: f32;
+};
+[[group(0), binding(2)]]
+var<uniform> fog: FogUniform;
+
 struct VertexInput {
     [[location(0)]] position: vec3<f32>;
     [[location(1)]] tex_coords: vec2<f32>;
+    [[location(7)]] normal: vec3<f32>;
 };
 
+struct ShadowUniform {
+    light_view_proj: array<mat4x4<f32>, 4>;
+    // Far distance of each cascade
+    splits: vec4<f32>;
+    cascade_count: u32;
+    debug: u32;
+    texel_size: f32;
+};
+[[group(2), binding(0)]]
+var t_shadow: texture_depth_2d_array;
+[[group(2), binding(1)]]
+var s_shadow: sampler_comparison;
+[[group(2), binding(2)]]
+var<uniform> shadow: ShadowUniform;
+
 struct VertexOutput {
     [[builtin(position)]] clip_position: vec4<f32>;
     [[location(0)]] tex_coords: vec2<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] world_position: vec3<f32>;
+    // Distance along the view direction, used to pick a cascade
+    [[location(3)]] view_depth: f32;
 };
 struct InstanceInput {
     [[location(2)]] model_matrix_0: vec4<f32>;
@@ -21,21 +64,59 @@ struct InstanceInput {
     [[location(6)]] atlas_offsets: vec2<f32>;
 };
  
-[[stage(vertex)]]
-fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
-    let model_matrix = mat4x4<f32>(
+fn instance_matrix(instance: InstanceInput) -> mat4x4<f32> {
+    return mat4x4<f32>(
         instance.model_matrix_0,
         instance.model_matrix_1,
         instance.model_matrix_2,
         instance.model_matrix_3,
     );
+}
+
+// Everything but the normal, which blocks and entities turn differently
+fn instance_vertex(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model_matrix = instance_matrix(instance);
 
     var out: VertexOutput;
     out.tex_coords = (model.tex_coords + instance.atlas_offsets) / 16.0 ;
 
     // out.color = vec3<f32>(1.0,1.0,1.0,1.)
     // var test = vec3<f32>(0.0, 0.0, 0.0);
-    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(model.position / 2.0, 1.0);
+    let world_position = model_matrix * vec4<f32>(model.position / 2.0, 1.0);
+    out.world_position = world_position.xyz;
+    out.clip_position = camera.view_proj * world_position;
+    out.view_depth = out.clip_position.w;
+    return out;
+}
+
+[[stage(vertex)]]
+fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+    var out = instance_vertex(model, instance);
+    // Blocks are turned in quarter turns and only scaled along y, which
+    // keeps their axis aligned normals pointing the same way once
+    // normalized. Entities are scaled along every axis, see vs_entity.
+    out.normal = (instance_matrix(instance) * vec4<f32>(model.normal, 0.0)).xyz;
+    return out;
+}
+
+// Entities, whose boxes are scaled along every axis. Normals go through the
+// inverse transpose of the model matrix, which for a turn times a scale is
+// the turn times one over the scale.
+[[stage(vertex)]]
+fn vs_entity(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+    var out = instance_vertex(model, instance);
+    let model_matrix = instance_matrix(instance);
+    let turn_scale = mat3x3<f32>(
+        model_matrix[0].xyz,
+        model_matrix[1].xyz,
+        model_matrix[2].xyz,
+    );
+    let scale_squared = vec3<f32>(
+        dot(turn_scale[0], turn_scale[0]),
+        dot(turn_scale[1], turn_scale[1]),
+        dot(turn_scale[2], turn_scale[2]),
+    );
+    out.normal = turn_scale * (model.normal / scale_squared);
     return out;
 }
 [[group(1), binding(0)]]
@@ -43,8 +124,154 @@ var t_diffuse: texture_2d<f32>;
 [[group(1), binding(1)]]
 var s_diffuse: sampler;
 
+fn select_cascade(view_depth: f32) -> u32 {
+    for (var i: u32 = 0u; i < shadow.cascade_count; i = i + 1u) {
+        if (view_depth < shadow.splits[i]) {
+            return i;
+        }
+    }
+    return shadow.cascade_count;
+}
+
+// 1.0 is fully lit, 0.0 fully in shadow. 3x3 PCF.
+fn shadow_factor(cascade: u32, world_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    if (cascade >= shadow.cascade_count) {
+        return 1.0;
+    }
+    // Push the lookup off the surface a little to avoid acne
+    let offset_position = world_position + normal * 0.05;
+    let light_clip = shadow.light_view_proj[cascade] * vec4<f32>(offset_position, 1.0);
+    let ndc = light_clip.xyz / light_clip.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0 || ndc.z > 1.0) {
+        return 1.0;
+    }
+
+    var lit = 0.0;
+    for (var x: i32 = -1; x <= 1; x = x + 1) {
+        for (var y: i32 = -1; y <= 1; y = y + 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * shadow.texel_size;
+            lit = lit + textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, i32(cascade), ndc.z);
+        }
+    }
+    return lit / 9.0;
+}
+
+fn cascade_debug_color(cascade: u32) -> vec3<f32> {
+    switch (i32(cascade)) {
+        case 0: { return vec3<f32>(1.0, 0.3, 0.3); }
+        case 1: { return vec3<f32>(0.3, 1.0, 0.3); }
+        case 2: { return vec3<f32>(0.3, 0.3, 1.0); }
+        case 3: { return vec3<f32>(1.0, 1.0, 0.3); }
+        default: { return vec3<f32>(1.0, 1.0, 1.0); }
+    }
+}
+
+// 0.0 is no fog, 1.0 is only fog
+fn fog_factor(view_depth: f32, world_position: vec3<f32>) -> f32 {
+    switch (i32(fog.mode)) {
+        case 1: {
+            return clamp((view_depth - fog.start) / (fog.end - fog.start), 0.0, 1.0);
+        }
+        case 2: {
+            return 1.0 - exp(-fog.density * view_depth);
+        }
+        case 3: {
+            // Density falls off exponentially with height, integrated along
+            // the view ray from the eye to the fragment
+            let rise = (world_position.y - fog.eye.y) * fog.height_falloff;
+            var along = 1.0;
+            if (abs(rise) > 0.0001) {
+                along = (1.0 - exp(-rise)) / rise;
+            }
+            let amount = fog.density * exp(-(fog.eye.y - fog.base_height) * fog.height_falloff)
+                * view_depth * along;
+            return clamp(1.0 - exp(-amount), 0.0, 1.0);
+        }
+        default: {
+            return 0.0;
+        }
+    }
+}
+
+fn shade(in: VertexOutput, color: vec4<f32>) -> vec3<f32> {
+    let normal = normalize(in.normal);
+    let cascade = select_cascade(in.view_depth);
+    let diffuse = max(dot(normal, normalize(light.direction)), 0.0)
+        * shadow_factor(cascade, in.world_position, normal);
+    var lighting = light.ambient + diffuse * light.color;
+    if (shadow.debug != 0u) {
+        lighting = lighting * cascade_debug_color(cascade);
+    }
+
+    return mix(color.rgb * lighting, fog.color, fog_factor(in.view_depth, in.world_position));
+}
+
 [[stage(fragment)]] 
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return vec4<f32>(shade(in, color), 1.0);
+}
+
+// Leaves and other blocks with holes in their texture, and entities, which
+// can look like any block
+[[stage(fragment)]]
+fn fs_cutout(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    if (color.a < 0.5) {
+        discard;
+    }
+    return vec4<f32>(shade(in, color), 1.0);
+}
+
+// Glass and water, blended over what's already drawn
+[[stage(fragment)]]
+fn fs_translucent(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return vec4<f32>(shade(in, color), color.a);
+}
+
+// OBJ models. They share the camera and shadow groups with the blocks, but
+// group 1 is a material: its own texture and a color instead of the atlas.
+struct MaterialUniform {
+    // Kd and the dissolve in alpha
+    diffuse: vec4<f32>;
+};
+[[group(1), binding(2)]]
+var<uniform> material: MaterialUniform;
+
+struct MeshInstanceInput {
+    [[location(2)]] model_matrix_0: vec4<f32>;
+    [[location(3)]] model_matrix_1: vec4<f32>;
+    [[location(4)]] model_matrix_2: vec4<f32>;
+    [[location(5)]] model_matrix_3: vec4<f32>;
+};
 
-    return  textureSample(t_diffuse, s_diffuse, in.tex_coords);
-}
\ No newline at end of file
+[[stage(vertex)]]
+fn vs_mesh(model: VertexInput, instance: MeshInstanceInput) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    // Right for rotations and uniform scales, which is how props are placed
+    out.normal = (model_matrix * vec4<f32>(model.normal, 0.0)).xyz;
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+    out.world_position = world_position.xyz;
+    out.clip_position = camera.view_proj * world_position;
+    out.view_depth = out.clip_position.w;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_mesh(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords) * material.diffuse;
+    if (color.a < 0.5) {
+        discard;
+    }
+    return vec4<f32>(shade(in, color), 1.0);
+}
@@ -0,0 +1,416 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+use wgpu::{
+    util::DeviceExt, BindGroup, BindGroupLayout, Buffer, CommandEncoder, Device, Queue,
+    RenderPipeline,
+};
+
+use crate::{
+    camera::{Camera, OPENGL_TO_WGPU_MATRIX},
+    light::Light,
+    model::{self, Model, RenderModel},
+    texture::Texture,
+    vertex::Vertex,
+};
+
+/// The shader has room for this many cascades.
+pub const MAX_CASCADES: usize = 4;
+/// Blend between logarithmic (1.0) and uniform (0.0) cascade splits.
+const SPLIT_LAMBDA: f32 = 0.75;
+/// How far behind a cascade shadow casters are still picked up.
+const CASTER_MARGIN: f32 = 64.0;
+
+/// Tints used when `ShadowSettings::debug_cascades` is on, nearest first.
+/// Keep in sync with `cascade_debug_color` in shader.wgsl.
+pub const CASCADE_DEBUG_COLORS: [[f32; 3]; MAX_CASCADES] = [
+    [1.0, 0.3, 0.3],
+    [0.3, 1.0, 0.3],
+    [0.3, 0.3, 1.0],
+    [1.0, 1.0, 0.3],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowSettings {
+    /// Clamped to `1..=MAX_CASCADES`
+    pub cascade_count: usize,
+    /// Width and height of each cascade's depth map
+    pub resolution: u32,
+    /// View distance covered by the last cascade
+    pub distance: f32,
+    /// Tint each cascade so their extents are visible
+    pub debug_cascades: bool,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            cascade_count: 3,
+            resolution: 2048,
+            distance: 128.0,
+            debug_cascades: false,
+        }
+    }
+}
+
+/// Far distance of each cascade, using the practical split scheme.
+pub fn cascade_splits(near: f32, far: f32, count: usize) -> Vec<f32> {
+    (1..=count)
+        .map(|i| {
+            let p = i as f32 / count as f32;
+            let log = near * (far / near).powf(p);
+            let uniform = near + (far - near) * p;
+            SPLIT_LAMBDA * log + (1.0 - SPLIT_LAMBDA) * uniform
+        })
+        .collect()
+}
+
+/// Corners of the part of the camera frustum between `near` and `far`.
+fn frustum_slice_corners(camera: &Camera, near: f32, far: f32) -> [Point3<f32>; 8] {
+    let forward = (camera.target - camera.eye).normalize();
+    let right = forward.cross(camera.up).normalize();
+    let up = right.cross(forward);
+    let tan = (camera.fovy.to_radians() / 2.0).tan();
+
+    let mut corners = [camera.eye; 8];
+    for (i, d) in [near, far].iter().enumerate() {
+        let half_height = d * tan;
+        let half_width = half_height * camera.aspect;
+        let center = camera.eye + forward * *d;
+        corners[i * 4] = center + up * half_height - right * half_width;
+        corners[i * 4 + 1] = center + up * half_height + right * half_width;
+        corners[i * 4 + 2] = center - up * half_height - right * half_width;
+        corners[i * 4 + 3] = center - up * half_height + right * half_width;
+    }
+    corners
+}
+
+/// Orthographic light projection enclosing a slice of the camera frustum.
+/// The box is fit to the slice's bounding sphere and snapped to whole shadow
+/// map texels so shadows don't shimmer as the camera moves.
+pub fn cascade_view_proj(
+    camera: &Camera,
+    light_direction: Vector3<f32>,
+    near: f32,
+    far: f32,
+    resolution: u32,
+) -> Matrix4<f32> {
+    let corners = frustum_slice_corners(camera, near, far);
+    let center = Point3::centroid(&corners);
+    let radius = corners
+        .iter()
+        .map(|c| (c - center).magnitude())
+        .fold(0.0f32, f32::max)
+        .ceil();
+
+    let direction = light_direction.normalize();
+    let up = if direction.y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    };
+
+    // Snap the center to the texel grid in light space
+    let light_rotation = Matrix4::look_at_rh(Point3::from_vec(direction), Point3::origin(), up);
+    let texel = radius * 2.0 / resolution as f32;
+    let mut light_center = light_rotation * center.to_homogeneous();
+    light_center.x = (light_center.x / texel).floor() * texel;
+    light_center.y = (light_center.y / texel).floor() * texel;
+    let inverse = light_rotation.invert().unwrap_or_else(Matrix4::identity);
+    let center = Point3::from_homogeneous(inverse * Vector4::new(
+        light_center.x,
+        light_center.y,
+        light_center.z,
+        1.0,
+    ));
+
+    let eye = center + direction * (radius + CASTER_MARGIN);
+    let view = Matrix4::look_at_rh(eye, center, up);
+    let proj = cgmath::ortho(
+        -radius,
+        radius,
+        -radius,
+        radius,
+        0.0,
+        radius * 2.0 + CASTER_MARGIN,
+    );
+    OPENGL_TO_WGPU_MATRIX * proj * view
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CascadeUniform {
+    light_view_proj: [[f32; 4]; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowUniform {
+    light_view_proj: [[[f32; 4]; 4]; MAX_CASCADES],
+    /// Far distance of each cascade, unused ones are 0
+    splits: [f32; MAX_CASCADES],
+    cascade_count: u32,
+    debug: u32,
+    texel_size: f32,
+    _padding: f32,
+}
+
+struct Cascade {
+    buffer: Buffer,
+    bind_group: BindGroup,
+    view: wgpu::TextureView,
+}
+
+/// Depth maps rendered from the sun, one array layer per cascade, and the
+/// bind group the main shader samples them through.
+pub struct ShadowMap {
+    settings: ShadowSettings,
+    pipeline: RenderPipeline,
+    // Owns the texture behind the cascade views and `bind_group`
+    _map: Texture,
+    cascades: Vec<Cascade>,
+    uniform_buffer: Buffer,
+    pub bind_group: BindGroup,
+    splits: Vec<f32>,
+}
+
+impl ShadowMap {
+    /// Group 2 of the main pipeline.
+    pub fn create_bind_group_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("shadow_bind_group_layout"),
+        })
+    }
+    fn create_pipeline(device: &Device, cascade_layout: &BindGroupLayout) -> RenderPipeline {
+        let shader = device.create_shader_module(&wgpu::include_wgsl!("./shadow.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow pipeline layout"),
+            bind_group_layouts: &[cascade_layout],
+            push_constant_ranges: &[],
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc(), model::get_instance_buffer_layout()],
+            },
+            fragment: None,
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+    pub fn new(device: &Device, layout: &BindGroupLayout, settings: ShadowSettings) -> Self {
+        let settings = ShadowSettings {
+            cascade_count: settings.cascade_count.clamp(1, MAX_CASCADES),
+            ..settings
+        };
+        let cascade_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("cascade_bind_group_layout"),
+        });
+        let pipeline = Self::create_pipeline(device, &cascade_layout);
+
+        let map = Texture::create_shadow_map(
+            device,
+            settings.resolution,
+            settings.cascade_count as u32,
+            "shadow_map",
+        );
+        let cascades = (0..settings.cascade_count as u32)
+            .map(|layer| {
+                let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Cascade Buffer"),
+                    contents: bytemuck::cast_slice(&[CascadeUniform {
+                        light_view_proj: Matrix4::identity().into(),
+                    }]),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &cascade_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                    label: Some("cascade_bind_group"),
+                });
+                let view = map.texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("cascade_view"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: std::num::NonZeroU32::new(1),
+                    ..Default::default()
+                });
+                Cascade {
+                    buffer,
+                    bind_group,
+                    view,
+                }
+            })
+            .collect();
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Buffer"),
+            size: std::mem::size_of::<ShadowUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&map.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&map.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("shadow_bind_group"),
+        });
+
+        Self {
+            settings,
+            pipeline,
+            _map: map,
+            cascades,
+            uniform_buffer,
+            bind_group,
+            splits: vec![],
+        }
+    }
+    pub fn settings(&self) -> ShadowSettings {
+        self.settings
+    }
+    /// Applies new settings, only recreating the maps if the cascade count or
+    /// resolution changed.
+    pub fn set_settings(&mut self, device: &Device, layout: &BindGroupLayout, settings: ShadowSettings) {
+        let count = settings.cascade_count.clamp(1, MAX_CASCADES);
+        if count != self.settings.cascade_count || settings.resolution != self.settings.resolution {
+            *self = Self::new(device, layout, settings);
+        } else {
+            self.settings = ShadowSettings {
+                cascade_count: count,
+                ..settings
+            };
+        }
+    }
+    /// Far distance of each cascade as of the last `update`.
+    pub fn splits(&self) -> &[f32] {
+        &self.splits
+    }
+    /// Refits the cascades to the camera frustum.
+    pub fn update(&mut self, queue: &Queue, camera: &Camera, light: &Light) {
+        let count = self.settings.cascade_count;
+        let far = self.settings.distance.min(camera.zfar);
+        self.splits = cascade_splits(camera.znear, far, count);
+
+        let mut uniform = ShadowUniform {
+            light_view_proj: [Matrix4::identity().into(); MAX_CASCADES],
+            splits: [0.0; MAX_CASCADES],
+            cascade_count: count as u32,
+            debug: self.settings.debug_cascades as u32,
+            texel_size: 1.0 / self.settings.resolution as f32,
+            _padding: 0.0,
+        };
+        let mut near = camera.znear;
+        for (i, far) in self.splits.iter().enumerate() {
+            let view_proj = cascade_view_proj(
+                camera,
+                light.direction,
+                near,
+                *far,
+                self.settings.resolution,
+            );
+            uniform.light_view_proj[i] = view_proj.into();
+            uniform.splits[i] = *far;
+            queue.write_buffer(
+                &self.cascades[i].buffer,
+                0,
+                bytemuck::cast_slice(&[CascadeUniform {
+                    light_view_proj: view_proj.into(),
+                }]),
+            );
+            near = *far;
+        }
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+    /// Renders the models into every cascade.
+    pub fn render<'a, I>(&'a self, encoder: &mut CommandEncoder, models: I)
+    where
+        I: Iterator<Item = &'a Model> + Clone,
+    {
+        for cascade in &self.cascades {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &cascade.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &cascade.bind_group, &[]);
+            for m in models.clone() {
+                pass.render_model_geometry(m);
+            }
+        }
+    }
+}
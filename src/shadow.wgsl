@@ -0,0 +1,26 @@
+struct CascadeUniform {
+    light_view_proj: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> cascade: CascadeUniform;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+};
+struct InstanceInput {
+    [[location(2)]] model_matrix_0: vec4<f32>;
+    [[location(3)]] model_matrix_1: vec4<f32>;
+    [[location(4)]] model_matrix_2: vec4<f32>;
+    [[location(5)]] model_matrix_3: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(model: VertexInput, instance: InstanceInput) -> [[builtin(position)]] vec4<f32> {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    return cascade.light_view_proj * model_matrix * vec4<f32>(model.position / 2.0, 1.0);
+}
@@ -0,0 +1,378 @@
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use cgmath::{vec3, InnerSpace, Matrix3, Vector3};
+use serde_json::Value;
+
+use crate::{
+    blocks,
+    chunk::BlockKind,
+    model::Faces,
+    state::{BlockState, Facing},
+    vertex::{self, Vertex},
+};
+
+/// All of one side of a block, see `ShapeFace::area`.
+pub const WHOLE_SIDE: [f32; 4] = [-0.5, -0.5, 0.5, 0.5];
+
+const EPSILON: f32 = 1e-4;
+
+#[derive(Debug)]
+pub enum ShapeError {
+    Json(serde_json::Error),
+    /// Valid JSON that isn't a block model.
+    Format(String),
+}
+
+impl fmt::Display for ShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShapeError::Json(e) => write!(f, "{}", e),
+            ShapeError::Format(e) => write!(f, "invalid block model: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ShapeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ShapeError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for ShapeError {
+    fn from(e: serde_json::Error) -> Self {
+        ShapeError::Json(e)
+    }
+}
+
+/// A box of a block model, in sixteenths of a block from its bottom south
+/// west corner, the way resource pack models are written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelBox {
+    pub from: [f32; 3],
+    pub to: [f32; 3],
+}
+
+/// A single quad of a block model, for what boxes can't make such as the
+/// crossed planes of plants. Its front is the side `(v1 - v0) x (v3 - v0)`
+/// points to, as with the cube's faces.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelQuad {
+    /// In sixteenths of a block, like `ModelBox`
+    pub vertices: [[f32; 3]; 4],
+    /// Texel of the block's tile at each vertex, 0 to 16
+    pub uv: [[f32; 2]; 4],
+    /// Seen from behind too. Never hidden by a neighbour.
+    pub double_sided: bool,
+}
+
+/// The model of a block that isn't a full cube, made for the default state:
+/// facing north, upright and in the bottom half.
+///
+/// In JSON, `{"elements": [{"from": [x, y, z], "to": [x, y, z]}], "quads":
+/// [{"vertices": [[x, y, z], ...], "uv": [[u, v], ...], "double_sided":
+/// true}]}`, where either list can be left out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Shape {
+    pub boxes: Vec<ModelBox>,
+    pub quads: Vec<ModelQuad>,
+}
+
+/// One face of a model turned by the block's state, with positions relative
+/// to the middle of the block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapeFace {
+    pub positions: [Vector3<f32>; 4],
+    pub normal: Vector3<f32>,
+    /// Within the block's tile, 0 to 1
+    pub tex_coords: [[f32; 2]; 4],
+    /// The side of the block the face lies on, where a neighbour can hide
+    /// it. `None` for faces inside the block.
+    pub side: Option<Facing>,
+}
+
+thread_local! {
+    static BUILT_IN: RefCell<HashMap<BlockKind, Rc<Shape>>> = RefCell::new(HashMap::new());
+}
+
+/// The model of a kind of block, `None` for full cubes. Built in models are
+/// parsed the first time they're asked for.
+pub fn of(kind: BlockKind) -> Option<Rc<Shape>> {
+    let definition = blocks::definition(kind);
+    let json = definition.model?;
+    Some(BUILT_IN.with(|shapes| {
+        shapes
+            .borrow_mut()
+            .entry(kind)
+            .or_insert_with(|| match Shape::from_json(json) {
+                Ok(shape) => Rc::new(shape),
+                Err(e) => panic!("model of {}: {}", definition.name, e),
+            })
+            .clone()
+    }))
+}
+
+/// Whether a block of `kind` in `state` covers all of `area` on its `side`
+/// with its own faces, together if need be. Full cubes cover every side.
+pub fn covers(kind: BlockKind, state: BlockState, side: Facing, area: [f32; 4]) -> bool {
+    match of(kind) {
+        None => true,
+        Some(shape) => {
+            let rects: Vec<[f32; 4]> = shape
+                .faces(state)
+                .iter()
+                .filter_map(ShapeFace::area)
+                .filter(|(s, _)| *s == side)
+                .map(|(_, rect)| rect)
+                .collect();
+            rects_cover(&rects, area)
+        }
+    }
+}
+
+/// Whether the rectangles cover all of `area` between them, checked cell by
+/// cell of the grid their edges make.
+fn rects_cover(rects: &[[f32; 4]], area: [f32; 4]) -> bool {
+    let edges = |axis: usize| {
+        let mut edges = vec![area[axis], area[axis + 2]];
+        for rect in rects {
+            for edge in [rect[axis], rect[axis + 2]] {
+                if edge > area[axis] && edge < area[axis + 2] {
+                    edges.push(edge);
+                }
+            }
+        }
+        edges.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        edges.dedup_by(|a, b| (*a - *b).abs() < EPSILON);
+        edges
+    };
+    let (us, vs) = (edges(0), edges(1));
+    us.windows(2).all(|u| {
+        vs.windows(2).all(|v| {
+            let (x, y) = ((u[0] + u[1]) / 2.0, (v[0] + v[1]) / 2.0);
+            rects
+                .iter()
+                .any(|r| r[0] <= x && x <= r[2] && r[1] <= y && y <= r[3])
+        })
+    })
+}
+
+/// The shown `faces` of a cube `height` tall, turned by `rotation`, in the
+/// same form as a model's faces so both can be meshed alike. `faces` are the
+/// cube's own, before turning, see `BlockState::model_faces`.
+pub fn cube_faces(faces: &Faces, height: f32, rotation: Matrix3<f32>) -> Vec<ShapeFace> {
+    let sides = [
+        (faces.north, vertex::north()),
+        (faces.south, vertex::south()),
+        (faces.top, vertex::top()),
+        (faces.bottom, vertex::bottom()),
+        (faces.east, vertex::east()),
+        (faces.west, vertex::west()),
+    ];
+    sides
+        .into_iter()
+        .filter(|(shown, _)| *shown)
+        .map(|(_, template)| {
+            // The cube is 2 wide around the origin and grows up from its
+            // bottom face, see `ModelData`
+            let positions = template.map(|v| {
+                rotation
+                    * vec3(
+                        v.position[0] / 2.0,
+                        (v.position[1] + 1.0) / 2.0 * height - 0.5,
+                        v.position[2] / 2.0,
+                    )
+            });
+            let normal = rotation * Vector3::from(template[0].normal);
+            face(positions, normal, template.map(|v| v.tex_coord), false)
+        })
+        .collect()
+}
+
+fn list<'a>(value: &'a Value, key: &str) -> Result<&'a [Value], ShapeError> {
+    match &value[key] {
+        Value::Null => Ok(&[]),
+        Value::Array(list) => Ok(list),
+        _ => Err(ShapeError::Format(format!("\"{}\" isn't a list", key))),
+    }
+}
+
+fn numbers<const N: usize>(value: &Value, key: &str) -> Result<[f32; N], ShapeError> {
+    let wrong = || ShapeError::Format(format!("\"{}\" needs lists of {} numbers", key, N));
+    let list = value
+        .as_array()
+        .filter(|l| l.len() == N)
+        .ok_or_else(wrong)?;
+    let mut out = [0.0; N];
+    for (o, v) in out.iter_mut().zip(list) {
+        *o = v.as_f64().ok_or_else(wrong)? as f32;
+    }
+    Ok(out)
+}
+
+fn corners<const N: usize>(value: &Value, key: &str) -> Result<[[f32; N]; 4], ShapeError> {
+    let list = match &value[key] {
+        Value::Array(list) if list.len() == 4 => list,
+        _ => return Err(ShapeError::Format(format!("\"{}\" needs 4 corners", key))),
+    };
+    let mut out = [[0.0; N]; 4];
+    for (o, v) in out.iter_mut().zip(list) {
+        *o = numbers(v, key)?;
+    }
+    Ok(out)
+}
+
+/// The axis a tex coord of a cube face follows and whether it rises along
+/// it, so boxes can be textured the same way.
+fn tex_axis(template: &[Vertex; 4], coord: usize) -> (usize, bool) {
+    (0..3)
+        .find_map(|axis| {
+            let follows = |f: fn(f32) -> f32| {
+                template
+                    .iter()
+                    .all(|v| v.tex_coord[coord] == f(v.position[axis]))
+            };
+            if follows(|p| (p + 1.0) / 2.0) {
+                Some((axis, true))
+            } else if follows(|p| (1.0 - p) / 2.0) {
+                Some((axis, false))
+            } else {
+                None
+            }
+        })
+        .expect("cube faces run each tex coord along an axis")
+}
+
+fn face(
+    positions: [Vector3<f32>; 4],
+    normal: Vector3<f32>,
+    tex_coords: [[f32; 2]; 4],
+    double_sided: bool,
+) -> ShapeFace {
+    let side = Facing::ALL.into_iter().find(|side| {
+        let (x, y, z) = side.offset();
+        let out = vec3(x as f32, y as f32, z as f32);
+        !double_sided
+            && normal.dot(out) > 0.5
+            && positions.iter().all(|p| (p.dot(out) - 0.5).abs() < EPSILON)
+    });
+    ShapeFace {
+        positions,
+        normal,
+        tex_coords,
+        side,
+    }
+}
+
+impl ShapeFace {
+    /// The side the face lies on and the part of it covered, as the min and
+    /// max of the other two axes in x, y, z order.
+    pub fn area(&self) -> Option<(Facing, [f32; 4])> {
+        let side = self.side?;
+        let (x, y, _) = side.offset();
+        let (a, b) = match (x, y) {
+            (0, 0) => (0, 1),
+            (0, _) => (0, 2),
+            _ => (1, 2),
+        };
+        let mut area = [
+            f32::INFINITY,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::NEG_INFINITY,
+        ];
+        for p in &self.positions {
+            area[0] = area[0].min(p[a]);
+            area[1] = area[1].min(p[b]);
+            area[2] = area[2].max(p[a]);
+            area[3] = area[3].max(p[b]);
+        }
+        Some((side, area))
+    }
+}
+
+impl Shape {
+    pub fn from_json(json: &str) -> Result<Self, ShapeError> {
+        let value: Value = serde_json::from_str(json)?;
+        let mut shape = Shape::default();
+        for element in list(&value, "elements")? {
+            let from = numbers(&element["from"], "from")?;
+            let to = numbers(&element["to"], "to")?;
+            if (0..3).any(|i| from[i] < 0.0 || from[i] > to[i] || to[i] > 16.0) {
+                return Err(ShapeError::Format(
+                    "boxes go from their lowest corner to their highest, inside the block".into(),
+                ));
+            }
+            shape.boxes.push(ModelBox { from, to });
+        }
+        for quad in list(&value, "quads")? {
+            shape.quads.push(ModelQuad {
+                vertices: corners(quad, "vertices")?,
+                uv: corners(quad, "uv")?,
+                double_sided: quad["double_sided"].as_bool().unwrap_or(false),
+            });
+        }
+        if shape.boxes.is_empty() && shape.quads.is_empty() {
+            return Err(ShapeError::Format("no elements or quads".into()));
+        }
+        Ok(shape)
+    }
+    /// Every face of the model turned by `state`, boxes first. Box faces are
+    /// textured from where they'd be on a full cube, so a box filling the
+    /// block looks just like one.
+    pub fn faces(&self, state: BlockState) -> Vec<ShapeFace> {
+        let rotation: Matrix3<f32> = state.rotation();
+        let middle = vec3(0.5, 0.5, 0.5);
+        let mut faces = vec![];
+        for b in &self.boxes {
+            let templates = [
+                vertex::north(),
+                vertex::south(),
+                vertex::top(),
+                vertex::bottom(),
+                vertex::east(),
+                vertex::west(),
+            ];
+            for template in templates {
+                // The corner of the box each vertex of the cube stands for
+                let corners = template.map(|v| {
+                    let mut c = [0.0; 3];
+                    for (i, c) in c.iter_mut().enumerate() {
+                        *c = if v.position[i] < 0.0 {
+                            b.from[i]
+                        } else {
+                            b.to[i]
+                        } / 16.0;
+                    }
+                    c
+                });
+                let axes = [tex_axis(&template, 0), tex_axis(&template, 1)];
+                let mut tex_coords = [[0.0; 2]; 4];
+                for (tc, c) in tex_coords.iter_mut().zip(corners) {
+                    for (t, (axis, rising)) in tc.iter_mut().zip(axes) {
+                        *t = if rising { c[axis] } else { 1.0 - c[axis] };
+                    }
+                }
+                faces.push(face(
+                    corners.map(|c| rotation * (Vector3::from(c) - middle)),
+                    rotation * Vector3::from(template[0].normal),
+                    tex_coords,
+                    false,
+                ));
+            }
+        }
+        for q in &self.quads {
+            let positions = q
+                .vertices
+                .map(|v| rotation * (Vector3::from(v) / 16.0 - middle));
+            let normal = (positions[1] - positions[0])
+                .cross(positions[3] - positions[0])
+                .normalize();
+            let tex_coords = q.uv.map(|uv| [uv[0] / 16.0, uv[1] / 16.0]);
+            faces.push(face(positions, normal, tex_coords, q.double_sided));
+        }
+        faces
+    }
+}
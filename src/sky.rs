@@ -0,0 +1,132 @@
+use bytemuck::Zeroable;
+use cgmath::SquareMatrix;
+use wgpu::{util::DeviceExt, BindGroup, Buffer, Device, Queue, RenderPass, RenderPipeline, TextureFormat};
+
+use crate::{camera::Camera, texture, time_of_day::Sky};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkyUniform {
+    inv_view_proj: [[f32; 4]; 4],
+    eye: [f32; 3],
+    star_visibility: f32,
+    zenith: [f32; 3],
+    _padding0: f32,
+    horizon: [f32; 3],
+    _padding1: f32,
+    sun_direction: [f32; 3],
+    _padding2: f32,
+    moon_direction: [f32; 3],
+    _padding3: f32,
+}
+
+impl SkyUniform {
+    pub fn new(camera: &Camera, sky: &Sky) -> Self {
+        let inv_view_proj = camera
+            .build_view_projection_matrix()
+            .invert()
+            .unwrap_or_else(cgmath::Matrix4::identity);
+        Self {
+            inv_view_proj: inv_view_proj.into(),
+            eye: camera.eye.into(),
+            star_visibility: sky.star_visibility,
+            zenith: sky.zenith,
+            _padding0: 0.0,
+            horizon: sky.horizon,
+            _padding1: 0.0,
+            sun_direction: sky.sun_direction.into(),
+            _padding2: 0.0,
+            moon_direction: sky.moon_direction.into(),
+            _padding3: 0.0,
+        }
+    }
+}
+
+/// Fullscreen gradient with the sun, moon and stars, drawn behind the world
+/// at the start of the main pass.
+pub struct SkyRenderer {
+    pipeline: RenderPipeline,
+    buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl SkyRenderer {
+    /// The uniform starts zeroed, call `update` once a camera is known.
+    pub fn new(device: &Device, format: TextureFormat) -> Self {
+        let shader = device.create_shader_module(&wgpu::include_wgsl!("./sky.wgsl"));
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sky Buffer"),
+            contents: bytemuck::bytes_of(&SkyUniform::zeroed()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("sky_bind_group_layout"),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("sky_bind_group"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sky Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sky Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[format.into()],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            // Shares the main pass, so it needs a depth state but never
+            // writes depth: the world is drawn over it afterwards.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        Self {
+            pipeline,
+            buffer,
+            bind_group,
+        }
+    }
+    pub fn update(&self, queue: &Queue, camera: &Camera, sky: &Sky) {
+        queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[SkyUniform::new(camera, sky)]),
+        );
+    }
+    pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
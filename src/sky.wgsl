@@ -0,0 +1,69 @@
+struct SkyUniform {
+    inv_view_proj: mat4x4<f32>;
+    eye: vec3<f32>;
+    star_visibility: f32;
+    zenith: vec3<f32>;
+    horizon: vec3<f32>;
+    // Both point away from the camera towards the body
+    sun_direction: vec3<f32>;
+    moon_direction: vec3<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> sky: SkyUniform;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] ndc: vec2<f32>;
+};
+
+// One triangle that covers the whole screen
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+fn hash(p: vec3<f32>) -> f32 {
+    let q = fract(p * 0.3183099 + vec3<f32>(0.1, 0.2, 0.3));
+    let r = q * 17.0;
+    return fract(r.x * r.y * r.z * (r.x + r.y + r.z));
+}
+
+// Scalar smoothstep, which naga doesn't provide yet
+fn smooth(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = clamp((x - edge0) / (edge1 - edge0), 0.0, 1.0);
+    return t * t * (3.0 - 2.0 * t);
+}
+
+fn stars(dir: vec3<f32>) -> f32 {
+    let cell = floor(dir * 180.0);
+    let h = hash(cell);
+    return smooth(0.997, 1.0, h);
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let far = sky.inv_view_proj * vec4<f32>(in.ndc, 1.0, 1.0);
+    let dir = normalize(far.xyz / far.w - sky.eye);
+
+    let height = clamp(dir.y, 0.0, 1.0);
+    var color = mix(sky.horizon, sky.zenith, pow(height, 0.6));
+    // Below the horizon fades into a darker version of the horizon
+    color = mix(color, sky.horizon * 0.5, clamp(-dir.y * 4.0, 0.0, 1.0));
+
+    let sun = dot(dir, sky.sun_direction);
+    color = color + vec3<f32>(1.0, 0.9, 0.7) * smooth(0.9995, 0.9998, sun);
+    color = color + vec3<f32>(1.0, 0.8, 0.6) * pow(max(sun, 0.0), 64.0) * 0.3;
+
+    let moon = dot(dir, sky.moon_direction);
+    color = color + vec3<f32>(0.8, 0.85, 0.9) * smooth(0.9996, 0.9998, moon) * sky.star_visibility;
+
+    if (dir.y > 0.0) {
+        color = color + vec3<f32>(stars(dir) * sky.star_visibility);
+    }
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,234 @@
+use cgmath::{Deg, Matrix3, Vector3};
+
+use crate::model::Faces;
+
+/// A property a kind of block declares in its `BlockDefinition`. Properties
+/// a kind doesn't declare keep their default and are ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Property {
+    /// Which way the front of the block points
+    Facing,
+    /// Which way a log or pillar runs
+    Axis,
+    /// Whether the block sits in the top or bottom half of its space
+    Half,
+    /// Flow level of fluids, kept in `Block::level` rather than the state
+    Level,
+}
+
+/// North is +z and east is +x, the same as the chunk's neighbours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Facing {
+    North,
+    East,
+    South,
+    West,
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    Y,
+    X,
+    Z,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Half {
+    Bottom,
+    Top,
+}
+
+impl Facing {
+    pub const ALL: [Facing; 6] = [
+        Facing::North,
+        Facing::East,
+        Facing::South,
+        Facing::West,
+        Facing::Up,
+        Facing::Down,
+    ];
+    /// The horizontal facing pointing back along `view`, so a placed block
+    /// faces whoever placed it.
+    pub fn towards_viewer(view: Vector3<f32>) -> Self {
+        if view.x.abs() > view.z.abs() {
+            if view.x > 0.0 {
+                Facing::West
+            } else {
+                Facing::East
+            }
+        } else if view.z > 0.0 {
+            Facing::South
+        } else {
+            Facing::North
+        }
+    }
+    /// The step from a block to its neighbour on this side.
+    pub fn offset(self) -> (i64, i64, i64) {
+        match self {
+            Facing::North => (0, 0, 1),
+            Facing::East => (1, 0, 0),
+            Facing::South => (0, 0, -1),
+            Facing::West => (-1, 0, 0),
+            Facing::Up => (0, 1, 0),
+            Facing::Down => (0, -1, 0),
+        }
+    }
+    pub fn opposite(self) -> Self {
+        match self {
+            Facing::North => Facing::South,
+            Facing::East => Facing::West,
+            Facing::South => Facing::North,
+            Facing::West => Facing::East,
+            Facing::Up => Facing::Down,
+            Facing::Down => Facing::Up,
+        }
+    }
+}
+
+impl Axis {
+    /// The axis `direction` runs closest to.
+    pub fn along(direction: Vector3<f32>) -> Self {
+        let d = direction.map(f32::abs);
+        if d.x > d.y && d.x >= d.z {
+            Axis::X
+        } else if d.z > d.y && d.z > d.x {
+            Axis::Z
+        } else {
+            Axis::Y
+        }
+    }
+}
+
+/// The facing, axis and half of one block, packed in a byte. The default is
+/// north, upright and bottom, which draws the block unrotated.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockState(u8);
+
+const FACING_BITS: u8 = 0b111;
+const AXIS_SHIFT: u8 = 3;
+const AXIS_BITS: u8 = 0b11 << AXIS_SHIFT;
+const HALF_BIT: u8 = 1 << 5;
+
+impl BlockState {
+    pub fn facing(self) -> Facing {
+        Facing::ALL[(self.0 & FACING_BITS) as usize]
+    }
+    pub fn with_facing(self, facing: Facing) -> Self {
+        Self((self.0 & !FACING_BITS) | facing as u8)
+    }
+    pub fn axis(self) -> Axis {
+        [Axis::Y, Axis::X, Axis::Z][((self.0 & AXIS_BITS) >> AXIS_SHIFT) as usize]
+    }
+    pub fn with_axis(self, axis: Axis) -> Self {
+        Self((self.0 & !AXIS_BITS) | ((axis as u8) << AXIS_SHIFT))
+    }
+    pub fn half(self) -> Half {
+        if self.0 & HALF_BIT == 0 {
+            Half::Bottom
+        } else {
+            Half::Top
+        }
+    }
+    pub fn with_half(self, half: Half) -> Self {
+        match half {
+            Half::Bottom => Self(self.0 & !HALF_BIT),
+            Half::Top => Self(self.0 | HALF_BIT),
+        }
+    }
+    /// The packed byte, for saving.
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+    /// The state packed by `bits`, or `None` if the byte isn't one.
+    pub fn from_bits(bits: u8) -> Option<Self> {
+        let facing = bits & FACING_BITS;
+        let axis = (bits & AXIS_BITS) >> AXIS_SHIFT;
+        let known = FACING_BITS | AXIS_BITS | HALF_BIT;
+        if facing < 6 && axis < 3 && bits & !known == 0 {
+            Some(Self(bits))
+        } else {
+            None
+        }
+    }
+    /// The state of a block placed while looking along `view`, against the
+    /// face of another block pointing along `normal`. `hit_height` is how
+    /// far up that face was hit, from 0 to 1.
+    ///
+    /// The block faces the viewer, runs along the view and sits in the top
+    /// half when placed under a ceiling or high on a wall.
+    pub fn placed(
+        properties: &[Property],
+        view: Vector3<f32>,
+        normal: (i64, i64, i64),
+        hit_height: f32,
+    ) -> Self {
+        let mut state = Self::default();
+        for property in properties {
+            state = match property {
+                Property::Facing => state.with_facing(Facing::towards_viewer(view)),
+                Property::Axis => state.with_axis(Axis::along(view)),
+                Property::Half => {
+                    let top = normal.1 < 0 || (normal.1 == 0 && hit_height > 0.5);
+                    state.with_half(if top { Half::Top } else { Half::Bottom })
+                }
+                Property::Level => state,
+            };
+        }
+        state
+    }
+    /// How the block's model is turned, around the middle of the block.
+    /// Models are made facing north, upright and in the bottom half.
+    pub fn rotation(self) -> Matrix3<f32> {
+        let facing = match self.facing() {
+            Facing::North => Matrix3::from_angle_y(Deg(0.0)),
+            Facing::East => Matrix3::from_angle_y(Deg(90.0)),
+            Facing::South => Matrix3::from_angle_y(Deg(180.0)),
+            Facing::West => Matrix3::from_angle_y(Deg(-90.0)),
+            Facing::Up => Matrix3::from_angle_x(Deg(-90.0)),
+            Facing::Down => Matrix3::from_angle_x(Deg(90.0)),
+        };
+        let axis = match self.axis() {
+            Axis::Y => Matrix3::from_angle_y(Deg(0.0)),
+            Axis::X => Matrix3::from_angle_z(Deg(-90.0)),
+            Axis::Z => Matrix3::from_angle_x(Deg(90.0)),
+        };
+        // Upside down, keeping the front where it was
+        let half = match self.half() {
+            Half::Bottom => Matrix3::from_angle_z(Deg(0.0)),
+            Half::Top => Matrix3::from_angle_z(Deg(180.0)),
+        };
+        let m = facing * axis * half;
+        // Whole quarter turns, without the rounding errors of the angles
+        Matrix3::from_cols(
+            m.x.map(f32::round),
+            m.y.map(f32::round),
+            m.z.map(f32::round),
+        )
+    }
+    /// Faces of the unturned model that end up where `faces` are, to pick
+    /// which of the model's own faces to draw.
+    pub(crate) fn model_faces(self, faces: &Faces) -> Faces {
+        let rotation = self.rotation();
+        let shown = |normal: Vector3<f32>| {
+            let n = (rotation * normal).map(|c| c.round() as i8);
+            match (n.x, n.y, n.z) {
+                (0, 0, 1) => faces.north,
+                (0, 0, -1) => faces.south,
+                (1, 0, 0) => faces.east,
+                (-1, 0, 0) => faces.west,
+                (0, 1, 0) => faces.top,
+                _ => faces.bottom,
+            }
+        };
+        Faces {
+            north: shown(Vector3::unit_z()),
+            south: shown(-Vector3::unit_z()),
+            east: shown(Vector3::unit_x()),
+            west: shown(-Vector3::unit_x()),
+            top: shown(Vector3::unit_y()),
+            bottom: shown(-Vector3::unit_y()),
+        }
+    }
+}
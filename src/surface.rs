@@ -0,0 +1,63 @@
+use wgpu::{Device, Surface, SurfaceConfiguration, SurfaceError, SurfaceTexture};
+
+/// How many times a lost or outdated surface is reconfigured before the
+/// frame is given up on.
+pub const MAX_RECONFIGURE_ATTEMPTS: u32 = 1;
+
+/// The part of a swapchain surface that frame acquisition needs, so the
+/// recovery logic can run against a mock.
+pub trait FrameSurface {
+    type Frame;
+    fn acquire(&mut self) -> Result<Self::Frame, SurfaceError>;
+    fn reconfigure(&mut self);
+}
+
+#[derive(Debug)]
+pub enum FrameOutcome<F> {
+    Ready(F),
+    /// Nothing to draw into this time; try again next frame.
+    Skip,
+    /// The GPU is out of memory and rendering should stop.
+    OutOfMemory,
+}
+
+/// Acquires the next frame, reconfiguring the surface when it was lost or
+/// outdated and skipping the frame on timeouts.
+pub fn acquire_frame<S: FrameSurface>(surface: &mut S) -> FrameOutcome<S::Frame> {
+    let mut attempts = 0;
+    loop {
+        match surface.acquire() {
+            Ok(frame) => return FrameOutcome::Ready(frame),
+            Err(SurfaceError::Lost) | Err(SurfaceError::Outdated) => {
+                if attempts == MAX_RECONFIGURE_ATTEMPTS {
+                    log::warn!("Surface still unusable after reconfiguring, skipping frame");
+                    return FrameOutcome::Skip;
+                }
+                attempts += 1;
+                surface.reconfigure();
+            }
+            Err(SurfaceError::Timeout) => {
+                log::warn!("Timed out acquiring frame, skipping");
+                return FrameOutcome::Skip;
+            }
+            Err(SurfaceError::OutOfMemory) => return FrameOutcome::OutOfMemory,
+        }
+    }
+}
+
+/// A real window surface together with what it needs to be reconfigured.
+pub struct WindowSurface<'a> {
+    pub surface: &'a Surface,
+    pub device: &'a Device,
+    pub config: &'a SurfaceConfiguration,
+}
+
+impl<'a> FrameSurface for WindowSurface<'a> {
+    type Frame = SurfaceTexture;
+    fn acquire(&mut self) -> Result<SurfaceTexture, SurfaceError> {
+        self.surface.get_current_texture()
+    }
+    fn reconfigure(&mut self) {
+        self.surface.configure(self.device, self.config);
+    }
+}
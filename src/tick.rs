@@ -0,0 +1,260 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    blocks,
+    chunk::{Block, BlockKind, Chunk, HEIGHT, LENGTH, WIDTH},
+    falling::{self, FallingBlock},
+};
+
+/// Local block coordinates inside a chunk.
+pub type Position = (i64, i64, i64);
+
+/// Length of one simulation tick in seconds.
+pub const TICK_SECONDS: f32 = 0.05;
+/// Random ticks are spread over cubes of this size.
+pub const SECTION_SIZE: i64 = 16;
+/// Blocks picked per section per tick for a random tick.
+pub const DEFAULT_RANDOM_TICK_SPEED: u32 = 3;
+
+pub const NEIGHBOURS: [Position; 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+pub fn offset(pos: Position, by: Position) -> Position {
+    (pos.0 + by.0, pos.1 + by.1, pos.2 + by.2)
+}
+
+/// Small seeded generator (splitmix64) so random ticks replay exactly.
+#[derive(Debug, Clone)]
+pub struct TickRng(u64);
+
+impl TickRng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+    /// Uniform in `0..n`, `n` must not be 0.
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+}
+
+/// Turns frame times into a whole number of fixed length ticks, carrying
+/// the remainder over to the next frame.
+#[derive(Debug, Clone)]
+pub struct FixedStep {
+    pub step: f32,
+    /// Ticks run at most per call, so a long stall doesn't snowball
+    pub max_steps: u32,
+    accumulator: f32,
+}
+
+impl Default for FixedStep {
+    fn default() -> Self {
+        Self {
+            step: TICK_SECONDS,
+            max_steps: 10,
+            accumulator: 0.0,
+        }
+    }
+}
+
+impl FixedStep {
+    /// Adds `seconds` and returns how many ticks are due.
+    pub fn advance(&mut self, seconds: f32) -> u32 {
+        self.accumulator += seconds;
+        let steps = (self.accumulator / self.step) as u32;
+        self.accumulator -= steps as f32 * self.step;
+        if steps > self.max_steps {
+            self.accumulator = 0.0;
+            self.max_steps
+        } else {
+            steps
+        }
+    }
+}
+
+/// Scheduled and random block ticks for one chunk. Scheduled ticks run in
+/// position order and random ticks come from a seeded generator, so the same
+/// seed, chunk and calls always give the same result.
+#[derive(Debug, Clone)]
+pub struct BlockTicks {
+    tick: u64,
+    scheduled: BTreeMap<u64, BTreeSet<Position>>,
+    falling: Vec<FallingBlock>,
+    rng: TickRng,
+    pub random_tick_speed: u32,
+}
+
+impl BlockTicks {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            tick: 0,
+            scheduled: BTreeMap::new(),
+            falling: vec![],
+            rng: TickRng::new(seed),
+            random_tick_speed: DEFAULT_RANDOM_TICK_SPEED,
+        }
+    }
+    pub fn current_tick(&self) -> u64 {
+        self.tick
+    }
+    /// Whether nothing is scheduled and nothing is falling.
+    pub fn is_idle(&self) -> bool {
+        self.scheduled.is_empty() && self.falling.is_empty()
+    }
+    /// Blocks that left the chunk to fall and haven't landed yet.
+    pub fn falling_blocks(&self) -> &[FallingBlock] {
+        &self.falling
+    }
+    pub fn schedule(&mut self, pos: Position, delay: u64) {
+        self.scheduled
+            .entry(self.tick + delay.max(1))
+            .or_default()
+            .insert(pos);
+    }
+    /// Queues the block at and around `pos` if their definitions react to
+    /// changes. Call after placing or removing a block.
+    pub fn block_changed(&mut self, chunk: &Chunk, pos: Position) {
+        self.schedule_block(chunk, pos);
+        for n in NEIGHBOURS {
+            self.schedule_block(chunk, offset(pos, n));
+        }
+    }
+    fn schedule_block(&mut self, chunk: &Chunk, pos: Position) {
+        if let Some(delay) = blocks::definition(kind_at(chunk, pos)).tick_delay {
+            self.schedule(pos, delay);
+        }
+    }
+    /// Runs the scheduled ticks due now, moves falling blocks, then runs the
+    /// random ticks. Returns the positions that changed in the order they
+    /// changed.
+    pub fn tick(&mut self, chunk: &mut Chunk) -> Vec<Position> {
+        self.tick += 1;
+        let mut changed = vec![];
+        if let Some(due) = self.scheduled.remove(&self.tick) {
+            for pos in due {
+                if let Some(on_tick) = blocks::definition(kind_at(chunk, pos)).on_scheduled_tick {
+                    on_tick(&mut self.context(chunk, &mut changed), pos);
+                }
+            }
+        }
+        self.fall(chunk, &mut changed);
+        self.random_ticks(chunk, &mut changed);
+        changed
+    }
+    /// Oldest first, so a stack that fell apart lands in the same order.
+    fn fall(&mut self, chunk: &mut Chunk, changed: &mut Vec<Position>) {
+        let mut falling = std::mem::take(&mut self.falling);
+        falling.retain_mut(|block| !falling::step(&mut self.context(chunk, changed), block));
+        // Landing can knock more blocks loose, which fall from the next tick
+        falling.append(&mut self.falling);
+        self.falling = falling;
+    }
+    fn random_ticks(&mut self, chunk: &mut Chunk, changed: &mut Vec<Position>) {
+        for sx in (0..WIDTH).step_by(SECTION_SIZE as usize) {
+            for sy in (0..HEIGHT).step_by(SECTION_SIZE as usize) {
+                for sz in (0..LENGTH).step_by(SECTION_SIZE as usize) {
+                    for _ in 0..self.random_tick_speed {
+                        let pos = (
+                            sx + self.rng.below(SECTION_SIZE as u64) as i64,
+                            sy + self.rng.below(SECTION_SIZE as u64) as i64,
+                            sz + self.rng.below(SECTION_SIZE as u64) as i64,
+                        );
+                        if let Some(on_tick) = blocks::definition(kind_at(chunk, pos)).on_random_tick
+                        {
+                            on_tick(&mut self.context(chunk, changed), pos);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    /// Ticks until nothing is scheduled or `max_ticks` have passed, returning
+    /// the number of ticks run.
+    pub fn settle(&mut self, chunk: &mut Chunk, max_ticks: u64) -> u64 {
+        let start = self.tick;
+        while !self.is_idle() && self.tick - start < max_ticks {
+            self.tick(chunk);
+        }
+        self.tick - start
+    }
+    fn context<'a>(
+        &'a mut self,
+        chunk: &'a mut Chunk,
+        changed: &'a mut Vec<Position>,
+    ) -> TickContext<'a> {
+        TickContext {
+            chunk,
+            ticks: self,
+            changed,
+        }
+    }
+}
+
+/// What a block's tick function can see and do.
+pub struct TickContext<'a> {
+    chunk: &'a mut Chunk,
+    ticks: &'a mut BlockTicks,
+    changed: &'a mut Vec<Position>,
+}
+
+impl<'a> TickContext<'a> {
+    /// `BlockKind::None` outside the chunk.
+    pub fn kind_at(&self, pos: Position) -> BlockKind {
+        kind_at(self.chunk, pos)
+    }
+    pub fn level_at(&self, pos: Position) -> u8 {
+        if Chunk::contains(pos.0, pos.1, pos.2) {
+            self.chunk
+                .get_block(pos.0, pos.1, pos.2)
+                .map(|b| b.level)
+                .unwrap_or(0)
+        } else {
+            0
+        }
+    }
+    /// Replaces a block and queues it and its neighbours. Ignored outside
+    /// the chunk.
+    pub fn set(&mut self, pos: Position, kind: BlockKind, level: u8) {
+        if !Chunk::contains(pos.0, pos.1, pos.2) {
+            return;
+        }
+        self.chunk.set_block(Block {
+            level,
+            ..Block::new(kind, pos.0, pos.1, pos.2)
+        });
+        self.changed.push(pos);
+        self.ticks.block_changed(self.chunk, pos);
+    }
+    /// Takes the block out of the grid, `BlockTicks` moves it from now on.
+    pub fn spawn_falling(&mut self, block: FallingBlock) {
+        self.ticks.falling.push(block);
+    }
+    /// Uniform in `0..n`, from the chunk's seeded generator.
+    pub fn random(&mut self, n: u64) -> u64 {
+        self.ticks.rng.below(n)
+    }
+}
+
+fn kind_at(chunk: &Chunk, pos: Position) -> BlockKind {
+    if Chunk::contains(pos.0, pos.1, pos.2) {
+        chunk
+            .get_block(pos.0, pos.1, pos.2)
+            .map(|b| b.kind)
+            .unwrap_or(BlockKind::None)
+    } else {
+        BlockKind::None
+    }
+}
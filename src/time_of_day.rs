@@ -0,0 +1,133 @@
+use std::f32::consts::TAU;
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::light::Light;
+
+const DAY_ZENITH: [f32; 3] = [0.30, 0.52, 1.0];
+const DAY_HORIZON: [f32; 3] = [0.70, 0.82, 1.0];
+const SUNSET_ZENITH: [f32; 3] = [0.25, 0.30, 0.60];
+const SUNSET_HORIZON: [f32; 3] = [1.0, 0.55, 0.25];
+const NIGHT_ZENITH: [f32; 3] = [0.01, 0.01, 0.04];
+const NIGHT_HORIZON: [f32; 3] = [0.04, 0.05, 0.12];
+
+const SUN_COLOR: [f32; 3] = [1.0, 0.98, 0.9];
+const SUNSET_COLOR: [f32; 3] = [1.0, 0.6, 0.35];
+const MOON_COLOR: [f32; 3] = [0.25, 0.3, 0.45];
+
+const DAY_AMBIENT: f32 = 0.35;
+const NIGHT_AMBIENT: f32 = 0.08;
+
+/// Colors and lights of the sky at one moment.
+#[derive(Debug, Clone, Copy)]
+pub struct Sky {
+    pub zenith: [f32; 3],
+    pub horizon: [f32; 3],
+    /// Points towards the sun, below the horizon at night
+    pub sun_direction: Vector3<f32>,
+    pub moon_direction: Vector3<f32>,
+    /// 0 during the day, 1 in full night
+    pub star_visibility: f32,
+}
+
+/// Where the world is in the day. `time` runs from 0 to 1: 0 is midnight,
+/// 0.25 sunrise, 0.5 noon and 0.75 sunset.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeOfDay {
+    pub time: f32,
+    /// Length of a full day in real seconds
+    pub day_length: f32,
+    /// Multiplies how fast `time` advances, 0 stops the clock
+    pub time_scale: f32,
+    /// Heading of the sun's path in degrees, see `Light::from_angles`
+    pub sun_azimuth: f32,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self {
+            time: 0.35,
+            day_length: 600.0,
+            time_scale: 1.0,
+            sun_azimuth: 30.0,
+        }
+    }
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+    ]
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+impl TimeOfDay {
+    pub fn advance(&mut self, seconds: f32) {
+        self.time = (self.time + seconds * self.time_scale / self.day_length).rem_euclid(1.0);
+    }
+    pub fn hours(&self) -> f32 {
+        self.time * 24.0
+    }
+    pub fn set_hours(&mut self, hours: f32) {
+        self.time = (hours / 24.0).rem_euclid(1.0);
+    }
+    /// The sun rises along the azimuth heading and sets opposite it.
+    pub fn sun_direction(&self) -> Vector3<f32> {
+        let heading = Light::from_angles(self.sun_azimuth, 0.0);
+        let angle = (self.time - 0.25) * TAU;
+        (heading * angle.cos() + Vector3::unit_y() * angle.sin()).normalize()
+    }
+    /// Sine of the sun's elevation, -1 at midnight and 1 at noon.
+    fn sun_height(&self) -> f32 {
+        self.sun_direction().y
+    }
+    /// How much of the day light is left, 0 at night and 1 once the sun is
+    /// well above the horizon.
+    fn daylight(&self) -> f32 {
+        smoothstep(-0.1, 0.2, self.sun_height())
+    }
+    /// How orange the sky is, peaking when the sun touches the horizon.
+    fn twilight(&self) -> f32 {
+        1.0 - smoothstep(0.0, 0.25, self.sun_height().abs())
+    }
+    pub fn ambient(&self) -> f32 {
+        NIGHT_AMBIENT + (DAY_AMBIENT - NIGHT_AMBIENT) * self.daylight()
+    }
+    pub fn sky(&self) -> Sky {
+        let daylight = self.daylight();
+        let twilight = self.twilight();
+        let zenith = mix(mix(NIGHT_ZENITH, DAY_ZENITH, daylight), SUNSET_ZENITH, twilight * 0.5);
+        let horizon = mix(mix(NIGHT_HORIZON, DAY_HORIZON, daylight), SUNSET_HORIZON, twilight);
+        let sun_direction = self.sun_direction();
+        Sky {
+            zenith,
+            horizon,
+            sun_direction,
+            moon_direction: -sun_direction,
+            star_visibility: 1.0 - smoothstep(-0.2, 0.0, self.sun_height()),
+        }
+    }
+    /// The sun during the day and the moon at night.
+    pub fn light(&self) -> Light {
+        let sun_direction = self.sun_direction();
+        if sun_direction.y > 0.0 {
+            Light {
+                direction: sun_direction,
+                color: mix(SUN_COLOR, SUNSET_COLOR, self.twilight()),
+                ambient: self.ambient(),
+            }
+        } else {
+            Light {
+                direction: -sun_direction,
+                color: MOON_COLOR,
+                ambient: self.ambient(),
+            }
+        }
+    }
+}
@@ -3,10 +3,18 @@ use wgpu::VertexBufferLayout;
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
-    position: [f32; 3],
-    tex_coord: [f32; 2],
+    pub(crate) position: [f32; 3],
+    pub(crate) tex_coord: [f32; 2],
+    pub(crate) normal: [f32; 3],
 }
 impl Vertex {
+    pub fn new(position: [f32; 3], tex_coord: [f32; 2], normal: [f32; 3]) -> Self {
+        Self {
+            position,
+            tex_coord,
+            normal,
+        }
+    }
     pub fn desc<'a>() -> VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
@@ -22,6 +30,12 @@ impl Vertex {
                     shader_location: 1,
                     format: wgpu::VertexFormat::Float32x2,
                 },
+                // 2 to 6 are taken by the instance buffer
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
             ],
         }
     }
@@ -40,58 +54,65 @@ impl Vertex {
 //         color: [0.0, 0.0, 1.0],
 //     },
 // ];
-pub fn vertex(pos: [i8; 3], tc: [i8; 2]) -> Vertex {
+pub fn vertex(pos: [i8; 3], tc: [i8; 2], n: [i8; 3]) -> Vertex {
     Vertex {
         position: [pos[0] as f32, pos[1] as f32, pos[2] as f32],
         tex_coord: [tc[0] as f32, tc[1] as f32],
+        normal: [n[0] as f32, n[1] as f32, n[2] as f32],
     }
 }
 pub fn north() -> [Vertex; 4] {
+    let n = [0, 0, 1];
     [
-        vertex([-1, -1, 1], [0, 0]),
-        vertex([1, -1, 1], [1, 0]),
-        vertex([1, 1, 1], [1, 1]),
-        vertex([-1, 1, 1], [0, 1]),
+        vertex([-1, -1, 1], [0, 0], n),
+        vertex([1, -1, 1], [1, 0], n),
+        vertex([1, 1, 1], [1, 1], n),
+        vertex([-1, 1, 1], [0, 1], n),
     ]
 }
 pub fn south() -> [Vertex; 4] {
+    let n = [0, 0, -1];
     [
-        vertex([-1, 1, -1], [1, 0]),
-        vertex([1, 1, -1], [0, 0]),
-        vertex([1, -1, -1], [0, 1]),
-        vertex([-1, -1, -1], [1, 1]),
+        vertex([-1, 1, -1], [1, 0], n),
+        vertex([1, 1, -1], [0, 0], n),
+        vertex([1, -1, -1], [0, 1], n),
+        vertex([-1, -1, -1], [1, 1], n),
     ]
 }
 pub fn west() -> [Vertex; 4] {
+    let n = [-1, 0, 0];
     [
-        vertex([-1, -1, 1], [1, 0]),
-        vertex([-1, 1, 1], [0, 0]),
-        vertex([-1, 1, -1], [0, 1]),
-        vertex([-1, -1, -1], [1, 1]),
+        vertex([-1, -1, 1], [1, 0], n),
+        vertex([-1, 1, 1], [0, 0], n),
+        vertex([-1, 1, -1], [0, 1], n),
+        vertex([-1, -1, -1], [1, 1], n),
     ]
 }
 pub fn east() -> [Vertex; 4] {
+    let n = [1, 0, 0];
     [
-        vertex([1, -1, -1], [0, 0]),
-        vertex([1, 1, -1], [1, 0]),
-        vertex([1, 1, 1], [1, 1]),
-        vertex([1, -1, 1], [0, 1]),
+        vertex([1, -1, -1], [0, 0], n),
+        vertex([1, 1, -1], [1, 0], n),
+        vertex([1, 1, 1], [1, 1], n),
+        vertex([1, -1, 1], [0, 1], n),
     ]
 }
 pub fn bottom() -> [Vertex; 4] {
+    let n = [0, -1, 0];
     [
-        vertex([1, -1, 1], [0, 0]),
-        vertex([-1, -1, 1], [1, 0]),
-        vertex([-1, -1, -1], [1, 1]),
-        vertex([1, -1, -1], [0, 1]),
+        vertex([1, -1, 1], [0, 0], n),
+        vertex([-1, -1, 1], [1, 0], n),
+        vertex([-1, -1, -1], [1, 1], n),
+        vertex([1, -1, -1], [0, 1], n),
     ]
 }
 
 pub fn top() -> [Vertex; 4] {
+    let n = [0, 1, 0];
     [
-        vertex([1, 1, -1], [1, 0]),
-        vertex([-1, 1, -1], [0, 0]),
-        vertex([-1, 1, 1], [0, 1]),
-        vertex([1, 1, 1], [1, 1]),
+        vertex([1, 1, -1], [1, 0], n),
+        vertex([-1, 1, -1], [0, 0], n),
+        vertex([-1, 1, 1], [0, 1], n),
+        vertex([1, 1, 1], [1, 1], n),
     ]
 }
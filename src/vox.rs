@@ -0,0 +1,413 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use serde_json::Value;
+
+use crate::{
+    blocks,
+    chunk::{self, BlockKind, Chunk, WorldPosition},
+};
+
+/// The version MagicaVoxel writes and the only one read.
+pub const VERSION: u32 = 150;
+/// Models can't be larger than this along any axis.
+pub const MAX_SIZE: i64 = 256;
+
+#[derive(Debug)]
+pub enum VoxError {
+    Io(io::Error),
+    /// Not a .vox file or a broken one.
+    Format(&'static str),
+    /// The mapping file isn't valid.
+    Mapping(String),
+    /// A selection bigger than `MAX_SIZE` along some axis.
+    TooLarge([i64; 3]),
+}
+
+impl fmt::Display for VoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VoxError::Io(e) => write!(f, "{}", e),
+            VoxError::Format(e) => write!(f, "invalid .vox file: {}", e),
+            VoxError::Mapping(e) => write!(f, "invalid palette mapping: {}", e),
+            VoxError::TooLarge(size) => write!(
+                f,
+                "{}x{}x{} is larger than a .vox model can be",
+                size[0], size[1], size[2]
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VoxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VoxError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for VoxError {
+    fn from(e: io::Error) -> Self {
+        VoxError::Io(e)
+    }
+}
+
+/// One voxel of a model, in MagicaVoxel's z up coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Voxel {
+    pub x: u8,
+    pub y: u8,
+    pub z: u8,
+    /// Palette index, 1 to 255
+    pub color: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoxModel {
+    pub size: [u32; 3],
+    pub voxels: Vec<Voxel>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoxFile {
+    pub models: Vec<VoxModel>,
+    /// RGBA of each palette index, entry 0 is unused. Empty if the file has
+    /// no palette and uses MagicaVoxel's built in one.
+    pub palette: Vec<[u8; 4]>,
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_id(r: &mut impl Read) -> io::Result<[u8; 4]> {
+    let mut id = [0; 4];
+    r.read_exact(&mut id)?;
+    Ok(id)
+}
+
+fn write_chunk(
+    out: &mut impl Write,
+    id: &[u8; 4],
+    content: &[u8],
+    children: &[u8],
+) -> io::Result<()> {
+    out.write_all(id)?;
+    out.write_all(&(content.len() as u32).to_le_bytes())?;
+    out.write_all(&(children.len() as u32).to_le_bytes())?;
+    out.write_all(content)?;
+    out.write_all(children)
+}
+
+impl VoxFile {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, VoxError> {
+        Self::read(&mut BufReader::new(fs::File::open(path)?))
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), VoxError> {
+        let mut out = BufWriter::new(fs::File::create(path)?);
+        self.write(&mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+    /// Reads the models and palette. Scene graph, material and layer
+    /// chunks are skipped.
+    pub fn read(r: &mut impl Read) -> Result<Self, VoxError> {
+        if &read_id(r)? != b"VOX " {
+            return Err(VoxError::Format("missing VOX header"));
+        }
+        if read_u32(r)? != VERSION {
+            return Err(VoxError::Format("unsupported version"));
+        }
+        if &read_id(r)? != b"MAIN" {
+            return Err(VoxError::Format("missing MAIN chunk"));
+        }
+        let main_content = read_u32(r)?;
+        let main_children = read_u32(r)?;
+        io::copy(&mut r.by_ref().take(main_content as u64), &mut io::sink())?;
+
+        let mut children = vec![];
+        r.take(main_children as u64).read_to_end(&mut children)?;
+        let mut r = children.as_slice();
+
+        let mut file = VoxFile {
+            models: vec![],
+            palette: vec![],
+        };
+        let mut size = None;
+        while !r.is_empty() {
+            let id = read_id(&mut r)?;
+            let content_size = read_u32(&mut r)? as usize;
+            let children_size = read_u32(&mut r)? as usize;
+            if r.len() < content_size + children_size {
+                return Err(VoxError::Format("chunk runs past the end of the file"));
+            }
+            let (mut content, rest) = r.split_at(content_size);
+            r = &rest[children_size..];
+            match &id {
+                b"SIZE" => {
+                    size = Some([
+                        read_u32(&mut content)?,
+                        read_u32(&mut content)?,
+                        read_u32(&mut content)?,
+                    ]);
+                }
+                b"XYZI" => {
+                    let size = size.take().ok_or(VoxError::Format("XYZI without SIZE"))?;
+                    let count = read_u32(&mut content)? as usize;
+                    if content.len() < count * 4 {
+                        return Err(VoxError::Format("XYZI shorter than its voxel count"));
+                    }
+                    let voxels = content[..count * 4]
+                        .chunks_exact(4)
+                        .map(|v| Voxel {
+                            x: v[0],
+                            y: v[1],
+                            z: v[2],
+                            color: v[3],
+                        })
+                        .collect();
+                    file.models.push(VoxModel { size, voxels });
+                }
+                b"RGBA" => {
+                    if content.len() < 256 * 4 {
+                        return Err(VoxError::Format("short palette"));
+                    }
+                    // Entry i of the chunk is palette index i + 1
+                    file.palette = std::iter::once([0; 4])
+                        .chain(
+                            content
+                                .chunks_exact(4)
+                                .take(255)
+                                .map(|c| [c[0], c[1], c[2], c[3]]),
+                        )
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+        Ok(file)
+    }
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut children = vec![];
+        if self.models.len() > 1 {
+            write_chunk(
+                &mut children,
+                b"PACK",
+                &(self.models.len() as u32).to_le_bytes(),
+                &[],
+            )?;
+        }
+        for model in &self.models {
+            let size: Vec<u8> = model.size.iter().flat_map(|s| s.to_le_bytes()).collect();
+            write_chunk(&mut children, b"SIZE", &size, &[])?;
+            let mut xyzi = (model.voxels.len() as u32).to_le_bytes().to_vec();
+            for v in &model.voxels {
+                xyzi.extend([v.x, v.y, v.z, v.color]);
+            }
+            write_chunk(&mut children, b"XYZI", &xyzi, &[])?;
+        }
+        if !self.palette.is_empty() {
+            let mut rgba = vec![0; 256 * 4];
+            for (i, color) in self.palette.iter().enumerate().skip(1).take(255) {
+                rgba[(i - 1) * 4..i * 4].copy_from_slice(color);
+            }
+            write_chunk(&mut children, b"RGBA", &rgba, &[])?;
+        }
+
+        out.write_all(b"VOX ")?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        write_chunk(out, b"MAIN", &[], &children)
+    }
+    /// RGB of a palette index, if the file has a palette.
+    pub fn color(&self, index: u8) -> Option<[u8; 3]> {
+        self.palette.get(index as usize).map(|c| [c[0], c[1], c[2]])
+    }
+}
+
+/// Which block each palette entry becomes. Read from a JSON file like
+///
+/// ```json
+/// {
+///     "indices": {"1": "grass", "2": "dirt"},
+///     "colors": {"#dcc98d": "sand"},
+///     "default": "stone"
+/// }
+/// ```
+///
+/// Palette indices are looked up first, then exact colors, then `default`.
+/// Without a default, colors are matched to the block with the nearest
+/// average color.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VoxMapping {
+    pub indices: HashMap<u8, BlockKind>,
+    pub colors: HashMap<[u8; 3], BlockKind>,
+    pub default: Option<BlockKind>,
+}
+
+fn parse_kind(value: &Value) -> Result<BlockKind, VoxError> {
+    let name = value
+        .as_str()
+        .ok_or_else(|| VoxError::Mapping(format!("{} isn't a block name", value)))?;
+    blocks::by_name(name).ok_or_else(|| VoxError::Mapping(format!("unknown block {}", name)))
+}
+
+fn parse_color(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+impl VoxMapping {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, VoxError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+    pub fn from_json(json: &str) -> Result<Self, VoxError> {
+        let value: Value =
+            serde_json::from_str(json).map_err(|e| VoxError::Mapping(e.to_string()))?;
+        let mut mapping = VoxMapping::default();
+        if let Some(indices) = value.get("indices").and_then(Value::as_object) {
+            for (index, kind) in indices {
+                let index = index
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|i| *i > 0)
+                    .ok_or_else(|| VoxError::Mapping(format!("bad palette index {}", index)))?;
+                mapping.indices.insert(index, parse_kind(kind)?);
+            }
+        }
+        if let Some(colors) = value.get("colors").and_then(Value::as_object) {
+            for (color, kind) in colors {
+                let color = parse_color(color)
+                    .ok_or_else(|| VoxError::Mapping(format!("bad color {}", color)))?;
+                mapping.colors.insert(color, parse_kind(kind)?);
+            }
+        }
+        if let Some(default) = value.get("default") {
+            mapping.default = Some(parse_kind(default)?);
+        }
+        Ok(mapping)
+    }
+    /// The block a palette entry of `file` becomes.
+    pub fn kind_for(&self, file: &VoxFile, index: u8) -> BlockKind {
+        if let Some(kind) = self.indices.get(&index) {
+            return *kind;
+        }
+        let color = file.color(index);
+        if let Some(kind) = color.and_then(|c| self.colors.get(&c)) {
+            return *kind;
+        }
+        match (self.default, color) {
+            (Some(kind), _) => kind,
+            (None, Some(color)) => blocks::by_color(color),
+            (None, None) => BlockKind::Stone,
+        }
+    }
+    /// Palette index for writing `kind`, if the mapping names one. The
+    /// lowest wins when several map to it.
+    fn index_for(&self, kind: BlockKind) -> Option<u8> {
+        self.indices
+            .iter()
+            .filter(|(_, k)| **k == kind)
+            .map(|(i, _)| *i)
+            .min()
+    }
+}
+
+/// Builds chunks from every model of the file, each with its lowest corner
+/// at `origin`. MagicaVoxel is z up, so a model's z becomes the world's y and
+/// its y runs towards -z. Scene transforms aren't applied.
+pub fn import(file: &VoxFile, mapping: &VoxMapping, origin: WorldPosition) -> Vec<Chunk> {
+    let mut chunks = vec![];
+    let mut kinds: HashMap<u8, BlockKind> = HashMap::new();
+    for model in &file.models {
+        let depth = model.size[1] as i64;
+        for v in &model.voxels {
+            let kind = *kinds
+                .entry(v.color)
+                .or_insert_with(|| mapping.kind_for(file, v.color));
+            let pos = (
+                origin.0 + v.x as i64,
+                origin.1 + v.z as i64,
+                origin.2 + depth - 1 - v.y as i64,
+            );
+            chunk::set_world_block(&mut chunks, kind, pos);
+        }
+    }
+    chunks
+}
+
+/// Writes the blocks between two corners, both included, as a single model.
+/// Air and unloaded space are left empty. Each kind gets the palette index
+/// the mapping gives it, or a free one, colored with the block's color.
+pub fn export_selection(
+    chunks: &[Chunk],
+    a: WorldPosition,
+    b: WorldPosition,
+    mapping: &VoxMapping,
+) -> Result<VoxFile, VoxError> {
+    let min = (a.0.min(b.0), a.1.min(b.1), a.2.min(b.2));
+    let max = (a.0.max(b.0), a.1.max(b.1), a.2.max(b.2));
+    // World x, z and y become the model's x, y and z
+    let size = [max.0 - min.0 + 1, max.2 - min.2 + 1, max.1 - min.1 + 1];
+    if size.iter().any(|s| *s > MAX_SIZE) {
+        return Err(VoxError::TooLarge(size));
+    }
+
+    let mut palette = vec![[0; 4]; 256];
+    let mut assigned: Vec<(BlockKind, u8)> = vec![];
+    let mut next_free = 1;
+    let mut voxels = vec![];
+    for x in min.0..=max.0 {
+        for y in min.1..=max.1 {
+            for z in min.2..=max.2 {
+                let kind = chunk::world_block(chunks, (x, y, z));
+                if kind == BlockKind::Air || kind == BlockKind::None {
+                    continue;
+                }
+                let color = match assigned.iter().find(|(k, _)| *k == kind) {
+                    Some((_, index)) => *index,
+                    None => {
+                        let index = match mapping.index_for(kind) {
+                            Some(index) => index,
+                            None => {
+                                while mapping.indices.contains_key(&next_free) {
+                                    next_free += 1;
+                                }
+                                next_free += 1;
+                                next_free - 1
+                            }
+                        };
+                        let [r, g, b] = blocks::definition(kind).color;
+                        palette[index as usize] = [r, g, b, 255];
+                        assigned.push((kind, index));
+                        index
+                    }
+                };
+                voxels.push(Voxel {
+                    x: (x - min.0) as u8,
+                    y: (max.2 - z) as u8,
+                    z: (y - min.1) as u8,
+                    color,
+                });
+            }
+        }
+    }
+    Ok(VoxFile {
+        models: vec![VoxModel {
+            size: size.map(|s| s as u32),
+            voxels,
+        }],
+        palette,
+    })
+}
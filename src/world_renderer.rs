@@ -0,0 +1,652 @@
+use std::sync::Arc;
+
+use bytemuck::Zeroable;
+use cgmath::{EuclideanSpace, InnerSpace, Matrix3, Matrix4, SquareMatrix};
+
+use wgpu::{
+    util::DeviceExt, BindGroup, BindGroupLayout, Buffer, CommandEncoder, Device, Features,
+    PrimitiveState, Queue, RenderPassDepthStencilAttachment, RenderPipeline, ShaderModule,
+    TextureFormat, TextureView,
+};
+
+use crate::{
+    camera::Camera,
+    camera_uniform::CameraUniform,
+    chunk::{Chunk, ChunkMesh, Transparency},
+    fog::{FogSettings, FogUniform},
+    light::{Light, LightUniform},
+    entity::{Entity, EntityMesh},
+    falling::FallingBlock,
+    model::{self, Faces, Model, ModelData, RenderModel},
+    obj::{ObjError, ObjModel, ObjModelId},
+    prop::PropRenderer,
+    shadow::{ShadowMap, ShadowSettings},
+    sky::SkyRenderer,
+    texture::{self, Texture},
+    time_of_day::{Sky, TimeOfDay},
+    vertex::Vertex,
+};
+
+/// Draws chunks into any color target. Only needs a device, a queue and the
+/// target format, so it can be embedded without a window or the egui/glyph
+/// layers of `TropicRenderer`.
+pub struct WorldRenderer {
+    render_pipeline: RenderPipeline,
+    cutout_render_pipeline: RenderPipeline,
+    entity_render_pipeline: RenderPipeline,
+    translucent_render_pipeline: RenderPipeline,
+    wire_frame_render_pipeline: Option<RenderPipeline>,
+    camera_uniform: CameraUniform,
+    camera_buffer: Buffer,
+    camera_bind_group: BindGroup,
+    light_buffer: Buffer,
+    fog_buffer: Buffer,
+    atlas: Texture,
+    atlas_bind_group: Arc<BindGroup>,
+    depth_texture: Texture,
+    shadow_bind_group_layout: BindGroupLayout,
+    shadow_map: ShadowMap,
+    sky_renderer: SkyRenderer,
+    props: PropRenderer,
+    camera: Option<Camera>,
+    light: Light,
+    sky: Sky,
+    fog: FogSettings,
+    meshes: Vec<ChunkMesh>,
+    /// Where the translucent faces were last sorted from, if they still are
+    sorted_from: Option<cgmath::Point3<f32>>,
+    falling: Vec<Model>,
+    entities: Option<Model>,
+    /// Only has an effect if the device supports `POLYGON_MODE_LINE`.
+    pub wireframe: bool,
+}
+
+impl WorldRenderer {
+    /// `vertex_entry_point` is `vs_main` for blocks and `vs_entity` for
+    /// entities, see shader.wgsl.
+    pub fn create_render_pipeline(
+        device: &Device,
+        shader: &ShaderModule,
+        vertex_entry_point: &str,
+        bind_groups_layouts: Vec<&BindGroupLayout>,
+        primitive: Option<PrimitiveState>,
+        format: TextureFormat,
+        transparency: Transparency,
+    ) -> RenderPipeline {
+        // Translucent blocks blend over the rest and don't hide each other
+        let (entry_point, blend, depth_write_enabled) = match transparency {
+            Transparency::Opaque => ("fs_main", None, true),
+            Transparency::Cutout => ("fs_cutout", None, true),
+            Transparency::Translucent => {
+                ("fs_translucent", Some(wgpu::BlendState::ALPHA_BLENDING), false)
+            }
+        };
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &bind_groups_layouts,
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: vertex_entry_point,
+                buffers: &[Vertex::desc(), model::get_instance_buffer_layout()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point,
+                targets: &[wgpu::ColorTargetState {
+                    format,
+                    blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            primitive: primitive.unwrap_or_default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled,
+                depth_compare: wgpu::CompareFunction::Less, // 1.
+                stencil: wgpu::StencilState::default(),     // 2.
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+    /// Group 0: the camera at binding 0, the sun at binding 1 and fog at
+    /// binding 2.
+    pub fn create_camera_bind_group_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("camera_bind_group_layout"),
+        })
+    }
+    pub fn create_texture_bind_group_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    // This should match the filterable field of the
+                    // corresponding Texture entry above.
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("texture_bind_group_layout"),
+        })
+    }
+    fn create_atlas(
+        device: &Device,
+        queue: &Queue,
+        layout: &BindGroupLayout,
+    ) -> (Texture, Arc<BindGroup>) {
+        let atlas =
+            Texture::from_bytes(device, queue, include_bytes!("../atlas.png"), "atlas").unwrap();
+        let atlas_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&atlas.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&atlas.sampler),
+                },
+            ],
+            label: Some("atlas_bind_group"),
+        });
+        (atlas, Arc::new(atlas_bind_group))
+    }
+
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let shader = device.create_shader_module(&wgpu::include_wgsl!("./shader.wgsl"));
+        let time_of_day = TimeOfDay::default();
+
+        let camera_uniform = CameraUniform::new();
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: bytemuck::cast_slice(&[camera_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Light Buffer"),
+            contents: bytemuck::cast_slice(&[LightUniform::new(&time_of_day.light())]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let fog_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Fog Buffer"),
+            // Zeroed is fog off until the first camera update
+            contents: bytemuck::bytes_of(&FogUniform::zeroed()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let camera_bind_group_layout = Self::create_camera_bind_group_layout(device);
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &camera_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: fog_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("camera_bind_group"),
+        });
+
+        let texture_bind_group_layout = Self::create_texture_bind_group_layout(device);
+        let shadow_bind_group_layout = ShadowMap::create_bind_group_layout(device);
+        let shadow_map =
+            ShadowMap::new(device, &shadow_bind_group_layout, ShadowSettings::default());
+        let layouts = vec![
+            &camera_bind_group_layout,
+            &texture_bind_group_layout,
+            &shadow_bind_group_layout,
+        ];
+        let render_pipeline = Self::create_render_pipeline(
+            device,
+            &shader,
+            "vs_main",
+            layouts.clone(),
+            None,
+            format,
+            Transparency::Opaque,
+        );
+        let cutout_render_pipeline = Self::create_render_pipeline(
+            device,
+            &shader,
+            "vs_main",
+            layouts.clone(),
+            None,
+            format,
+            Transparency::Cutout,
+        );
+        // Entities can look like any block, so holes are cut like leaves'
+        let entity_render_pipeline = Self::create_render_pipeline(
+            device,
+            &shader,
+            "vs_entity",
+            layouts.clone(),
+            None,
+            format,
+            Transparency::Cutout,
+        );
+        let translucent_render_pipeline = Self::create_render_pipeline(
+            device,
+            &shader,
+            "vs_main",
+            layouts.clone(),
+            None,
+            format,
+            Transparency::Translucent,
+        );
+        let wire_frame_render_pipeline = if device.features().contains(Features::POLYGON_MODE_LINE)
+        {
+            let wireframe_primitive = PrimitiveState {
+                polygon_mode: wgpu::PolygonMode::Line,
+                ..Default::default()
+            };
+            Some(Self::create_render_pipeline(
+                device,
+                &shader,
+                "vs_main",
+                layouts,
+                Some(wireframe_primitive),
+                format,
+                Transparency::Opaque,
+            ))
+        } else {
+            None
+        };
+
+        let (atlas, atlas_bind_group) =
+            Self::create_atlas(device, queue, &texture_bind_group_layout);
+        let depth_texture =
+            Texture::create_depth_texture_sized(device, width, height, "depth_texture");
+        let sky_renderer = SkyRenderer::new(device, format);
+        let props = PropRenderer::new(
+            device,
+            queue,
+            &shader,
+            &camera_bind_group_layout,
+            &shadow_bind_group_layout,
+            format,
+        );
+
+        Self {
+            render_pipeline,
+            cutout_render_pipeline,
+            entity_render_pipeline,
+            translucent_render_pipeline,
+            wire_frame_render_pipeline,
+            camera_uniform,
+            camera_buffer,
+            camera_bind_group,
+            light_buffer,
+            fog_buffer,
+            atlas,
+            atlas_bind_group,
+            depth_texture,
+            shadow_bind_group_layout,
+            shadow_map,
+            sky_renderer,
+            props,
+            camera: None,
+            light: time_of_day.light(),
+            sky: time_of_day.sky(),
+            fog: FogSettings::default(),
+            meshes: vec![],
+            sorted_from: None,
+            falling: vec![],
+            entities: None,
+            wireframe: false,
+        }
+    }
+    /// Rebuilds the GPU models for the given chunks, replacing the previous ones.
+    pub fn set_chunks(&mut self, device: &Device, chunks: &[Chunk]) {
+        self.meshes = chunks
+            .iter()
+            .map(|c| c.models(device, self.atlas_bind_group.clone()))
+            .collect();
+        self.sorted_from = None;
+    }
+    /// Rebuilds the GPU models of the chunks at the given indices, after an
+    /// edit. Chunks added since the last call are built too.
+    pub fn update_chunks(
+        &mut self,
+        device: &Device,
+        chunks: &[Chunk],
+        dirty: impl IntoIterator<Item = usize>,
+    ) {
+        self.meshes.truncate(chunks.len());
+        for i in dirty {
+            if let Some(mesh) = self.meshes.get_mut(i) {
+                *mesh = chunks[i].models(device, self.atlas_bind_group.clone());
+            }
+        }
+        for chunk in &chunks[self.meshes.len()..] {
+            self.meshes
+                .push(chunk.models(device, self.atlas_bind_group.clone()));
+        }
+        self.sorted_from = None;
+    }
+    /// Replaces the falling blocks drawn alongside the chunks. Each comes
+    /// with the origin of the chunk it falls in, see `Chunk::origin`.
+    pub fn set_falling_blocks(
+        &mut self,
+        device: &Device,
+        blocks: &[(cgmath::Vector3<f32>, FallingBlock)],
+    ) {
+        self.falling.clear();
+        if blocks.is_empty() {
+            return;
+        }
+        let data = blocks
+            .iter()
+            .map(|(origin, b)| ModelData {
+                position: origin + cgmath::vec3(b.x as f32, b.y, b.z as f32),
+                kind: b.kind,
+                size: cgmath::vec3(1.0, 1.0, 1.0),
+                rotation: Matrix3::identity(),
+            })
+            .collect();
+        self.falling.push(Model::new(
+            device,
+            &Faces::all(),
+            data,
+            self.atlas_bind_group.clone(),
+        ));
+    }
+    /// Uploads the entities to draw. Block entities are one instance of a
+    /// cube each, in an instance buffer that is reused and only grows when
+    /// it runs out of room. OBJ entities are drawn with their model's origin
+    /// at the bottom middle of their box.
+    pub fn update_entities<'a>(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        entities: impl Iterator<Item = &'a Entity>,
+    ) {
+        let mut data = vec![];
+        let mut carried: Vec<Vec<Matrix4<f32>>> = vec![];
+        for e in entities {
+            let bottom = e.position.y - e.half_extents.y;
+            match e.mesh {
+                EntityMesh::Block(kind) => data.push(ModelData {
+                    // ModelData scales from the bottom of a block sized cube
+                    position: cgmath::vec3(e.position.x, bottom + 0.5, e.position.z),
+                    kind,
+                    size: e.half_extents * 2.0,
+                    rotation: Matrix3::identity(),
+                }),
+                EntityMesh::Obj(ObjModelId(id)) => {
+                    if carried.len() <= id {
+                        carried.resize(id + 1, vec![]);
+                    }
+                    carried[id].push(Matrix4::from_translation(cgmath::vec3(
+                        e.position.x,
+                        bottom,
+                        e.position.z,
+                    )));
+                }
+            }
+        }
+        self.props.set_carried(device, queue, carried);
+        let fits = match &mut self.entities {
+            Some(model) => model.write_instances(queue, &data),
+            None => false,
+        };
+        if !fits {
+            let capacity = (data.len() as u32).next_power_of_two();
+            let mut model = Model::dynamic(
+                device,
+                &Faces::all(),
+                capacity,
+                self.atlas_bind_group.clone(),
+            );
+            model.write_instances(queue, &data);
+            self.entities = Some(model);
+        }
+    }
+    /// Uploads an OBJ model and its textures. Place it with
+    /// `set_obj_instances` or give it to entities with `EntityMesh::Obj`.
+    pub fn add_obj_model(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        model: &ObjModel,
+    ) -> Result<ObjModelId, ObjError> {
+        self.props.add(device, queue, model)
+    }
+    /// Model to world transforms of every placed copy of the model,
+    /// replacing the previous ones.
+    pub fn set_obj_instances(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        id: ObjModelId,
+        transforms: &[Matrix4<f32>],
+    ) {
+        self.props.set_placed(device, queue, id, transforms);
+    }
+    pub fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        self.depth_texture =
+            Texture::create_depth_texture_sized(device, width, height, "depth_texture");
+    }
+    pub fn update_camera(&mut self, queue: &Queue, camera: &Camera) {
+        self.camera_uniform.update_view_proj(camera);
+        queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.camera_uniform]),
+        );
+        self.camera = Some(*camera);
+        self.update_shadows(queue);
+        self.update_sky_uniform(queue);
+        if self.sorted_from != Some(camera.eye) {
+            for m in self.meshes.iter().flat_map(|m| &m.translucent) {
+                m.sort_back_to_front(queue, camera.eye.to_vec());
+            }
+            self.sorted_from = Some(camera.eye);
+        }
+    }
+    pub fn update_light(&mut self, queue: &Queue, light: &Light) {
+        queue.write_buffer(
+            &self.light_buffer,
+            0,
+            bytemuck::cast_slice(&[LightUniform::new(light)]),
+        );
+        self.light = *light;
+        self.update_shadows(queue);
+    }
+    /// Sky colors, sun, moon and stars drawn behind the world.
+    pub fn update_sky(&mut self, queue: &Queue, sky: &Sky) {
+        self.sky = *sky;
+        self.update_sky_uniform(queue);
+    }
+    pub fn fog_settings(&self) -> FogSettings {
+        self.fog
+    }
+    pub fn set_fog_settings(&mut self, queue: &Queue, settings: FogSettings) {
+        self.fog = settings;
+        self.update_sky_uniform(queue);
+    }
+    /// The sky and fog share the camera and horizon color.
+    fn update_sky_uniform(&self, queue: &Queue) {
+        if let Some(camera) = &self.camera {
+            self.sky_renderer.update(queue, camera, &self.sky);
+            queue.write_buffer(
+                &self.fog_buffer,
+                0,
+                bytemuck::cast_slice(&[FogUniform::new(&self.fog, camera, &self.sky)]),
+            );
+        }
+    }
+    fn update_shadows(&mut self, queue: &Queue) {
+        if let Some(camera) = &self.camera {
+            self.shadow_map.update(queue, camera, &self.light);
+        }
+    }
+    pub fn shadow_settings(&self) -> ShadowSettings {
+        self.shadow_map.settings()
+    }
+    /// Far distance of each shadow cascade.
+    pub fn shadow_splits(&self) -> &[f32] {
+        self.shadow_map.splits()
+    }
+    pub fn set_shadow_settings(&mut self, device: &Device, queue: &Queue, settings: ShadowSettings) {
+        if settings == self.shadow_map.settings() {
+            return;
+        }
+        self.shadow_map
+            .set_settings(device, &self.shadow_bind_group_layout, settings);
+        self.update_shadows(queue);
+    }
+    pub fn atlas(&self) -> &Texture {
+        &self.atlas
+    }
+    /// Draws the sky and the world into `view`. `view` must match the size
+    /// last passed to `new` or `resize`.
+    pub fn render(&self, encoder: &mut CommandEncoder, view: &TextureView) {
+        // Translucent blocks don't cast shadows
+        self.shadow_map.render(
+            encoder,
+            self.meshes
+                .iter()
+                .flat_map(|m| m.opaque.iter().chain(&m.cutout))
+                .chain(&self.falling)
+                .chain(&self.entities),
+        );
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    // Every pixel is covered by the sky
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        self.sky_renderer.render(&mut render_pass);
+        if let (Some(wireframe), true) = (&self.wire_frame_render_pipeline, self.wireframe) {
+            render_pass.set_pipeline(wireframe);
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.shadow_map.bind_group, &[]);
+            for mesh in &self.meshes {
+                for m in mesh.opaque.iter().chain(&mesh.cutout).chain(&mesh.translucent) {
+                    render_pass.render_model(m);
+                }
+            }
+            for m in self.falling.iter().chain(&self.entities) {
+                render_pass.render_model(m);
+            }
+            self.props.render(&mut render_pass, true);
+            return;
+        }
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.shadow_map.bind_group, &[]);
+        let opaque = self.meshes.iter().flat_map(|m| &m.opaque);
+        for m in opaque.chain(&self.falling) {
+            render_pass.render_model(m);
+        }
+        self.props.render(&mut render_pass, false);
+        render_pass.set_pipeline(&self.cutout_render_pipeline);
+        for m in self.meshes.iter().flat_map(|m| &m.cutout) {
+            render_pass.render_model(m);
+        }
+        if let Some(entities) = &self.entities {
+            render_pass.set_pipeline(&self.entity_render_pipeline);
+            render_pass.render_model(entities);
+        }
+        render_pass.set_pipeline(&self.translucent_render_pipeline);
+        for m in self.translucent_back_to_front() {
+            render_pass.render_model(m);
+        }
+    }
+    /// The translucent model of each chunk, furthest from the camera first
+    /// so nearer ones blend over them. Faces within a chunk are sorted by
+    /// `update_camera`.
+    fn translucent_back_to_front(&self) -> Vec<&Model> {
+        let mut meshes: Vec<&ChunkMesh> = self
+            .meshes
+            .iter()
+            .filter(|m| m.translucent.is_some())
+            .collect();
+        if let Some(camera) = &self.camera {
+            let eye = camera.eye.to_vec();
+            let distance = |m: &ChunkMesh| (m.translucent_center - eye).magnitude2();
+            meshes.sort_by(|a, b| distance(b).total_cmp(&distance(a)));
+        }
+        meshes.iter().flat_map(|m| &m.translucent).collect()
+    }
+}
@@ -0,0 +1,60 @@
+use noise::{Fbm, NoiseFn, Seedable};
+
+use crate::{
+    chunk::{self, Block, BlockKind, Chunk},
+    editor::{self, Transform},
+    map::Columns,
+    schematic::Schematic,
+};
+
+pub const DEFAULT_SEED: u32 = 0;
+
+/// Builds the starting chunks from fractal noise. The same seed always
+/// produces the same blocks.
+pub fn generate(seed: u32) -> Vec<Chunk> {
+    let chunk1 = Chunk::new(1, 0, 0);
+    // let mut chunk2 = Chunk::new(0, 0, 10);
+    // let mut chunk3 = Chunk::new(1, 0, 1);
+    // let mut chunk4 = Chunk::new(0, 0, 0);
+    let mut chunks: Vec<Chunk> = vec![chunk1];
+    let fbm = Fbm::new().set_seed(seed);
+
+    for i in 0..chunk::WIDTH {
+        for k in 0..chunk::LENGTH {
+            let val = (fbm.get([i as f64 / 256.0, k as f64 / 256.0, 1.0]) * 16.0 + 6.0).abs();
+
+            for j in 0..(val as i64) {
+                let kind = if i % 2 == 0 {
+                    BlockKind::Dirt
+                } else {
+                    BlockKind::Stone
+                };
+                for chunk in &mut chunks {
+                    chunk.set_block(Block::new(kind, i, j, k));
+                }
+            }
+        }
+    }
+    chunks
+}
+
+/// Stands a template on the ground with its footprint centered on `x` and
+/// `z`, resting on the highest block under it. Air in the template is
+/// skipped. Returns false, placing nothing, if there's no ground there.
+pub fn place_structure(chunks: &mut Vec<Chunk>, template: &Schematic, x: i64, z: i64) -> bool {
+    let (sx, _, sz) = template.blocks.size;
+    let (min_x, min_z) = (x - sx / 2, z - sz / 2);
+    let columns = Columns::new(chunks);
+    let ground = (min_x..min_x + sx)
+        .flat_map(|x| (min_z..min_z + sz).map(move |z| (x, z)))
+        .filter_map(|(x, z)| columns.top(x, z))
+        .map(|top| top.y)
+        .max();
+    let y = match ground {
+        Some(y) => y + 1,
+        None => return false,
+    };
+    let edits = template.place((min_x, y, min_z), Transform::default(), false);
+    editor::apply(chunks, &edits);
+    true
+}
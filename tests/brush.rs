@@ -0,0 +1,165 @@
+use vox23::{
+    brush::{self, Brush, BrushMode, BrushShape},
+    chunk::{self, BlockKind, Chunk},
+    editor::{self, Selection},
+};
+
+/// Flat stone ground with its top at y = 3, reaching well past the brushes
+/// used below so its edges aren't in the way.
+fn ground() -> Vec<Chunk> {
+    let mut chunks = vec![];
+    editor::apply(
+        &mut chunks,
+        &editor::fill(&Selection::new((-8, -8, -8), (23, 3, 23)), BlockKind::Stone),
+    );
+    chunks
+}
+
+fn brush(shape: BrushShape, mode: BrushMode, radius: f32) -> Brush {
+    Brush {
+        shape,
+        mode,
+        radius,
+        ..Brush::default()
+    }
+}
+
+fn sculpt(chunks: &mut Vec<Chunk>, brush: &Brush, center: (i64, i64, i64)) -> usize {
+    let edits = brush::stroke(chunks, brush, center);
+    editor::apply(chunks, &edits)
+}
+
+#[test]
+fn shapes_cover_their_volume() {
+    let center = (0, 0, 0);
+    assert_eq!(BrushShape::Cube.positions(center, 1.0).len(), 27);
+    // The center and its six neighbours
+    assert_eq!(BrushShape::Sphere.positions(center, 1.0).len(), 7);
+    // A plus sign on three layers
+    assert_eq!(BrushShape::Cylinder.positions(center, 1.0).len(), 15);
+    assert_eq!(BrushShape::Sphere.positions(center, 0.0), vec![center]);
+
+    let sphere = BrushShape::Sphere.positions((5, 5, 5), 3.0);
+    assert!(sphere.contains(&(8, 5, 5)));
+    assert!(!sphere.contains(&(8, 6, 5)));
+    assert!(BrushShape::Cylinder.contains(3.0, (0, 3, 3)));
+    assert!(!BrushShape::Sphere.contains(3.0, (0, 3, 3)));
+}
+
+#[test]
+fn add_and_subtract_are_opposites() {
+    let mut chunks = ground();
+    let add = brush(BrushShape::Sphere, BrushMode::Add, 2.0);
+    let edits = brush::stroke(&chunks, &add, (8, 4, 8));
+    // The lower half of the sphere is already stone
+    assert_eq!(edits.len(), 13 + 9 + 1);
+    assert!(edits
+        .iter()
+        .all(|(p, k)| p.1 >= 4 && k.kind == BlockKind::Stone));
+    editor::apply(&mut chunks, &edits);
+    assert_eq!(chunk::world_block(&chunks, (8, 6, 8)), BlockKind::Stone);
+
+    let subtract = brush(BrushShape::Sphere, BrushMode::Subtract, 2.0);
+    sculpt(&mut chunks, &subtract, (8, 4, 8));
+    let above = Selection::new((0, 4, 0), (15, 8, 15));
+    assert_eq!(above.count(&chunks, BlockKind::Stone), 0);
+    // The bottom of the sphere digs into the ground
+    assert_eq!(chunk::world_block(&chunks, (8, 2, 8)), BlockKind::Air);
+    assert_eq!(chunk::world_block(&chunks, (8, 1, 8)), BlockKind::Stone);
+}
+
+#[test]
+fn subtract_leaves_fluids_alone() {
+    let mut chunks = ground();
+    chunk::set_world_block(&mut chunks, BlockKind::Water, (8, 4, 8));
+    let subtract = brush(BrushShape::Cube, BrushMode::Subtract, 1.0);
+    let edits = brush::stroke(&chunks, &subtract, (8, 4, 8));
+    assert_eq!(edits.len(), 9);
+    assert!(edits.iter().all(|(p, _)| p.1 == 3));
+}
+
+#[test]
+fn smooth_fills_pits_and_clears_spikes() {
+    let mut chunks = ground();
+    chunk::set_world_block(&mut chunks, BlockKind::Dirt, (8, 4, 8));
+    chunk::set_world_block(&mut chunks, BlockKind::Air, (4, 3, 4));
+    let smooth = brush(BrushShape::Cube, BrushMode::Smooth, 6.0);
+    let edits = brush::stroke(&chunks, &smooth, (6, 3, 6));
+    assert!(edits.contains(&((8, 4, 8), BlockKind::Air.into())));
+    assert!(edits.contains(&((4, 3, 4), BlockKind::Stone.into())));
+    assert_eq!(edits.len(), 2);
+
+    // Flat ground is already smooth
+    let mut flat = ground();
+    editor::apply(&mut flat, &edits);
+    assert!(brush::stroke(&flat, &smooth, (6, 3, 6)).is_empty());
+}
+
+#[test]
+fn weak_smoothing_needs_a_clear_majority() {
+    let mut chunks = ground();
+    chunk::set_world_block(&mut chunks, BlockKind::Air, (4, 3, 4));
+    let mut smooth = brush(BrushShape::Cube, BrushMode::Smooth, 0.0);
+    // The pit has 17 stone blocks around it out of 27, which is enough from
+    // a strength of about 0.75
+    smooth.strength = 0.8;
+    assert_eq!(brush::stroke(&chunks, &smooth, (4, 3, 4)).len(), 1);
+    smooth.strength = 0.7;
+    assert!(brush::stroke(&chunks, &smooth, (4, 3, 4)).is_empty());
+    smooth.strength = 0.0;
+    assert!(brush::stroke(&chunks, &smooth, (4, 3, 4)).is_empty());
+}
+
+#[test]
+fn flatten_levels_to_the_center() {
+    let mut chunks = ground();
+    // A hill and a hole
+    editor::apply(
+        &mut chunks,
+        &editor::fill(&Selection::new((6, 4, 6), (7, 6, 7)), BlockKind::Dirt),
+    );
+    chunk::set_world_block(&mut chunks, BlockKind::Air, (9, 3, 9));
+    let flatten = brush(BrushShape::Cylinder, BrushMode::Flatten, 4.0);
+    sculpt(&mut chunks, &flatten, (8, 3, 8));
+    let above = Selection::new((0, 4, 0), (15, 8, 15));
+    assert_eq!(
+        above.count(&chunks, BlockKind::Air),
+        above.volume() as usize
+    );
+    assert_eq!(chunk::world_block(&chunks, (9, 3, 9)), BlockKind::Stone);
+}
+
+#[test]
+fn flatten_strength_limits_its_reach() {
+    let mut chunks = ground();
+    editor::apply(
+        &mut chunks,
+        &editor::fill(&Selection::new((8, 4, 8), (8, 7, 8)), BlockKind::Dirt),
+    );
+    let mut flatten = brush(BrushShape::Cylinder, BrushMode::Flatten, 4.0);
+    flatten.strength = 0.5;
+    sculpt(&mut chunks, &flatten, (8, 3, 8));
+    assert_eq!(chunk::world_block(&chunks, (8, 5, 8)), BlockKind::Air);
+    assert_eq!(chunk::world_block(&chunks, (8, 6, 8)), BlockKind::Dirt);
+}
+
+#[test]
+fn noise_paints_only_the_surface() {
+    let chunks = ground();
+    let mut paint = brush(BrushShape::Cube, BrushMode::Noise, 4.0);
+    paint.kind = BlockKind::Grass;
+    paint.strength = 1.0;
+    let full = brush::stroke(&chunks, &paint, (8, 3, 8));
+    // The whole top layer, and nothing buried
+    assert_eq!(full.len(), 81);
+    assert!(full
+        .iter()
+        .all(|(p, k)| p.1 == 3 && k.kind == BlockKind::Grass));
+
+    paint.strength = 0.5;
+    let half = brush::stroke(&chunks, &paint, (8, 3, 8));
+    assert!(!half.is_empty() && half.len() < full.len());
+    assert_eq!(half, brush::stroke(&chunks, &paint, (8, 3, 8)));
+    paint.strength = 0.0;
+    assert!(brush::stroke(&chunks, &paint, (8, 3, 8)).is_empty());
+}
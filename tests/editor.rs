@@ -0,0 +1,319 @@
+use cgmath::{point3, vec3};
+use vox23::{
+    chunk::{self, BlockData, BlockKind, Chunk},
+    editor::{self, Clipboard, Selection, Transform},
+    raycast,
+    state::{Axis, BlockState, Facing},
+};
+
+/// A 4 x 4 x 4 stone cube from the origin.
+fn cube() -> Vec<Chunk> {
+    let mut chunks = vec![];
+    editor::apply(
+        &mut chunks,
+        &editor::fill(&Selection::new((0, 0, 0), (3, 3, 3)), BlockKind::Stone),
+    );
+    chunks
+}
+
+/// An L of three blocks on the ground: stone at the corner, dirt to the east
+/// and glass to the north.
+fn corner_piece() -> Clipboard {
+    let mut chunks = vec![];
+    chunk::set_world_block(&mut chunks, BlockKind::Stone, (0, 0, 0));
+    chunk::set_world_block(&mut chunks, BlockKind::Dirt, (1, 0, 0));
+    chunk::set_world_block(&mut chunks, BlockKind::Glass, (0, 0, 1));
+    editor::copy(&chunks, &Selection::new((0, 0, 0), (2, 0, 1)))
+}
+
+#[test]
+fn selection_orders_its_corners() {
+    let selection = Selection::new((3, -1, 5), (1, 2, 5));
+    assert_eq!(selection.min, (1, -1, 5));
+    assert_eq!(selection.max, (3, 2, 5));
+    assert_eq!(selection.size(), (3, 4, 1));
+    assert_eq!(selection.volume(), 12);
+    assert_eq!(selection.positions().count(), 12);
+    assert!(selection.positions().all(|p| selection.contains(p)));
+    assert!(!selection.contains((0, 0, 5)));
+}
+
+#[test]
+fn fill_spans_chunks() {
+    let mut chunks = vec![];
+    let selection = Selection::new((-2, 0, 0), (1, 1, 0));
+    assert_eq!(
+        editor::apply(&mut chunks, &editor::fill(&selection, BlockKind::Sand)),
+        8
+    );
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(selection.count(&chunks, BlockKind::Sand), 8);
+    // Setting the same blocks again changes nothing
+    assert_eq!(
+        editor::apply(&mut chunks, &editor::fill(&selection, BlockKind::Sand)),
+        0
+    );
+}
+
+#[test]
+fn replace_only_touches_one_kind() {
+    let mut chunks = cube();
+    chunk::set_world_block(&mut chunks, BlockKind::Dirt, (1, 1, 1));
+    chunk::set_world_block(&mut chunks, BlockKind::Dirt, (3, 3, 3));
+    let selection = Selection::new((0, 0, 0), (2, 2, 2));
+    let edits = editor::replace(&chunks, &selection, BlockKind::Dirt, BlockKind::Gravel);
+    assert_eq!(edits, vec![((1, 1, 1), BlockKind::Gravel.into())]);
+    editor::apply(&mut chunks, &edits);
+    assert_eq!(chunk::world_block(&chunks, (3, 3, 3)), BlockKind::Dirt);
+}
+
+#[test]
+fn hollow_keeps_a_shell() {
+    let mut chunks = cube();
+    let selection = Selection::new((0, 0, 0), (3, 3, 3));
+    let edits = editor::hollow(&chunks, &selection);
+    let cleared = editor::apply(&mut chunks, &edits);
+    // The 2 x 2 x 2 middle
+    assert_eq!(cleared, 8);
+    assert_eq!(selection.count(&chunks, BlockKind::Stone), 56);
+    assert_eq!(chunk::world_block(&chunks, (1, 2, 2)), BlockKind::Air);
+    assert_eq!(chunk::world_block(&chunks, (0, 2, 2)), BlockKind::Stone);
+}
+
+#[test]
+fn copy_and_paste_elsewhere() {
+    let mut chunks = cube();
+    chunk::set_world_block(&mut chunks, BlockKind::Grass, (0, 3, 0));
+    let clipboard = editor::copy(&chunks, &Selection::new((0, 0, 0), (3, 3, 3)));
+    assert_eq!(clipboard.size, (4, 4, 4));
+    assert_eq!(clipboard.get(0, 3, 0).kind, BlockKind::Grass);
+
+    let edits = editor::paste(&clipboard, (20, 0, -10), Transform::default(), false);
+    editor::apply(&mut chunks, &edits);
+    let pasted = Selection::new((20, 0, -10), (23, 3, -7));
+    assert_eq!(pasted.count(&chunks, BlockKind::Stone), 63);
+    assert_eq!(chunk::world_block(&chunks, (20, 3, -10)), BlockKind::Grass);
+}
+
+#[test]
+fn paste_skips_air_unless_asked() {
+    let clipboard = corner_piece();
+    let mut chunks = vec![];
+    editor::apply(
+        &mut chunks,
+        &editor::fill(&Selection::new((0, 0, 0), (2, 0, 1)), BlockKind::Lava),
+    );
+    let edits = editor::paste(&clipboard, (0, 0, 0), Transform::default(), false);
+    assert_eq!(edits.len(), 3);
+    editor::apply(&mut chunks, &edits);
+    assert_eq!(chunk::world_block(&chunks, (1, 0, 1)), BlockKind::Lava);
+
+    let edits = editor::paste(&clipboard, (0, 0, 0), Transform::default(), true);
+    assert_eq!(edits.len(), 6);
+    editor::apply(&mut chunks, &edits);
+    assert_eq!(chunk::world_block(&chunks, (1, 0, 1)), BlockKind::Air);
+}
+
+#[test]
+fn quarter_turn_takes_east_to_north() {
+    let turned = corner_piece().transformed(Transform {
+        quarter_turns: 1,
+        ..Transform::default()
+    });
+    assert_eq!(turned.size, (2, 1, 3));
+    // The corner stays at the south, now on the east side
+    assert_eq!(turned.get(1, 0, 0).kind, BlockKind::Stone);
+    assert_eq!(turned.get(1, 0, 1).kind, BlockKind::Dirt);
+    assert_eq!(turned.get(0, 0, 0).kind, BlockKind::Glass);
+
+    let around = |turns| {
+        corner_piece().transformed(Transform {
+            quarter_turns: turns,
+            ..Transform::default()
+        })
+    };
+    assert_eq!(around(4), corner_piece());
+    assert_eq!(
+        around(2).transformed(Transform {
+            quarter_turns: 2,
+            ..Transform::default()
+        }),
+        corner_piece()
+    );
+    assert_eq!(
+        around(1).transformed(Transform {
+            quarter_turns: 3,
+            ..Transform::default()
+        }),
+        corner_piece()
+    );
+}
+
+#[test]
+fn mirroring_flips_one_axis() {
+    let piece = corner_piece();
+    let mirrored = piece.transformed(Transform {
+        mirror_x: true,
+        ..Transform::default()
+    });
+    assert_eq!(mirrored.size, piece.size);
+    assert_eq!(mirrored.get(2, 0, 0).kind, BlockKind::Stone);
+    assert_eq!(mirrored.get(1, 0, 0).kind, BlockKind::Dirt);
+    assert_eq!(mirrored.get(2, 0, 1).kind, BlockKind::Glass);
+
+    let both = piece.transformed(Transform {
+        mirror_x: true,
+        mirror_z: true,
+        ..Transform::default()
+    });
+    let half_turn = piece.transformed(Transform {
+        quarter_turns: 2,
+        ..Transform::default()
+    });
+    assert_eq!(both, half_turn);
+}
+
+#[test]
+fn quarter_turns_are_counter_clockwise_from_above() {
+    let log = BlockData {
+        kind: BlockKind::Log,
+        state: BlockState::default().with_facing(Facing::East),
+        level: 0,
+    };
+    let clipboard = Clipboard {
+        size: (1, 1, 1),
+        blocks: vec![log],
+    };
+    for (turns, facing) in [
+        (1, Facing::North),
+        (2, Facing::West),
+        (3, Facing::South),
+        (4, Facing::East),
+    ] {
+        let mut chunks = vec![];
+        let transform = Transform {
+            quarter_turns: turns,
+            ..Transform::default()
+        };
+        editor::apply(
+            &mut chunks,
+            &editor::paste(&clipboard, (0, 0, 0), transform, false),
+        );
+        let pasted = chunk::world_block_data(&chunks, (0, 0, 0));
+        assert_eq!(pasted.state.facing(), facing, "{} turns", turns);
+    }
+}
+
+#[test]
+fn pasted_blocks_turn_with_the_clipboard() {
+    let stairs = BlockData {
+        kind: BlockKind::StoneStairs,
+        state: BlockState::default().with_facing(Facing::East),
+        level: 0,
+    };
+    let log = BlockData {
+        kind: BlockKind::Log,
+        state: BlockState::default().with_axis(Axis::X),
+        level: 0,
+    };
+    let clipboard = Clipboard {
+        size: (2, 1, 1),
+        blocks: vec![stairs, log],
+    };
+    let pasted = |transform| {
+        let mut chunks = vec![];
+        editor::apply(
+            &mut chunks,
+            &editor::paste(&clipboard, (0, 0, 0), transform, false),
+        );
+        chunks
+    };
+    let state = |chunks: &[Chunk], pos| chunk::world_block_data(chunks, pos).state;
+
+    // East turns the same way +x does, to +z
+    let chunks = pasted(Transform {
+        quarter_turns: 1,
+        ..Transform::default()
+    });
+    assert_eq!(state(&chunks, (0, 0, 0)).facing(), Facing::North);
+    assert_eq!(state(&chunks, (0, 0, 1)).axis(), Axis::Z);
+
+    let chunks = pasted(Transform {
+        quarter_turns: 2,
+        ..Transform::default()
+    });
+    assert_eq!(state(&chunks, (1, 0, 0)).facing(), Facing::West);
+    assert_eq!(state(&chunks, (0, 0, 0)).axis(), Axis::X);
+
+    let chunks = pasted(Transform {
+        mirror_z: true,
+        ..Transform::default()
+    });
+    assert_eq!(state(&chunks, (0, 0, 0)).facing(), Facing::East);
+    let chunks = pasted(Transform {
+        mirror_x: true,
+        ..Transform::default()
+    });
+    assert_eq!(state(&chunks, (1, 0, 0)).facing(), Facing::West);
+}
+
+#[test]
+fn raycast_hits_the_first_block() {
+    let chunks = cube();
+    let hit =
+        raycast::raycast(&chunks, point3(1.0, 10.0, 1.0), vec3(0.0, -1.0, 0.0), 32.0).unwrap();
+    assert_eq!(hit.position, (1, 3, 1));
+    assert_eq!(hit.normal, (0, 1, 0));
+    assert_eq!(hit.adjacent(), (1, 4, 1));
+    // The top face is half a block above the block's center
+    assert!((hit.distance - 6.5).abs() < 1e-4);
+
+    let hit = raycast::raycast(&chunks, point3(-5.0, 2.2, 2.9), vec3(1.0, 0.0, 0.0), 32.0).unwrap();
+    assert_eq!(hit.position, (0, 2, 3));
+    assert_eq!(hit.normal, (-1, 0, 0));
+
+    let diagonal =
+        raycast::raycast(&chunks, point3(6.0, 6.0, 6.0), vec3(-1.0, -1.0, -1.0), 32.0).unwrap();
+    assert_eq!(diagonal.position, (3, 3, 3));
+}
+
+#[test]
+fn raycast_gives_up_past_its_reach() {
+    let chunks = cube();
+    let down = vec3(0.0, -1.0, 0.0);
+    assert!(raycast::raycast(&chunks, point3(1.0, 10.0, 1.0), down, 5.0).is_none());
+    assert!(raycast::raycast(&chunks, point3(10.0, 10.0, 1.0), down, 100.0).is_none());
+    assert!(
+        raycast::raycast(&chunks, point3(1.0, 10.0, 1.0), vec3(0.0, 0.0, 0.0), 100.0).is_none()
+    );
+}
+
+#[test]
+fn edits_in_new_chunks_are_drawn_where_they_were_made() {
+    let mut chunks = cube();
+    // Above the first chunk, and west of it past the origin
+    editor::apply(
+        &mut chunks,
+        &editor::fill(&Selection::new((2, 17, 3), (2, 17, 3)), BlockKind::Dirt),
+    );
+    editor::apply(
+        &mut chunks,
+        &editor::fill(&Selection::new((-1, 0, 0), (-1, 0, 0)), BlockKind::Sand),
+    );
+    assert_eq!(chunks.len(), 3);
+    for (key, kind, position) in [
+        ((0, 1, 0), BlockKind::Dirt, vec3(2.0, 17.0, 3.0)),
+        ((-1, 0, 0), BlockKind::Sand, vec3(-1.0, 0.0, 0.0)),
+    ] {
+        let chunk = chunks.iter().find(|c| c.position() == key).unwrap();
+        let instances: Vec<_> = chunk
+            .geometry()
+            .instances
+            .into_values()
+            .flatten()
+            .collect();
+        assert_eq!(instances.len(), 1);
+        assert_eq!(instances[0].kind, kind);
+        assert_eq!(instances[0].position, position);
+    }
+}
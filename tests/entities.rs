@@ -0,0 +1,103 @@
+use cgmath::{vec3, Vector3};
+use vox23::{
+    chunk::BlockKind,
+    entity::{region_of, Aabb, Entities, EntityId, EntityMesh},
+};
+
+const HALF: Vector3<f32> = Vector3::new(0.5, 0.5, 0.5);
+const STILL: Vector3<f32> = Vector3::new(0.0, 0.0, 0.0);
+
+fn spawn_at(entities: &mut Entities, position: Vector3<f32>) -> EntityId {
+    entities.spawn(position, STILL, HALF, EntityMesh::Block(BlockKind::Sand))
+}
+
+fn ids(found: Vec<&vox23::entity::Entity>) -> Vec<EntityId> {
+    let mut ids: Vec<EntityId> = found.iter().map(|e| e.id).collect();
+    ids.sort();
+    ids
+}
+
+#[test]
+fn spawn_and_despawn() {
+    let mut entities = Entities::new();
+    let a = spawn_at(&mut entities, vec3(1.0, 1.0, 1.0));
+    let b = spawn_at(&mut entities, vec3(40.0, 1.0, 1.0));
+    assert_ne!(a, b);
+    assert_eq!(entities.len(), 2);
+
+    let removed = entities.despawn(a).unwrap();
+    assert_eq!(removed.position, vec3(1.0, 1.0, 1.0));
+    assert!(entities.get(a).is_none());
+    assert!(entities.despawn(a).is_none());
+    assert_eq!(entities.len(), 1);
+    assert!(entities.get(b).is_some());
+}
+
+#[test]
+fn entities_are_filed_by_region() {
+    let mut entities = Entities::new();
+    let a = spawn_at(&mut entities, vec3(1.0, 1.0, 1.0));
+    let b = spawn_at(&mut entities, vec3(-1.0, 1.0, 17.0));
+    assert_eq!(region_of(vec3(-1.0, 1.0, 17.0)), (-1, 0, 1));
+    assert_eq!(entities.region((0, 0, 0))[0].id, a);
+    assert_eq!(entities.region((-1, 0, 1))[0].id, b);
+}
+
+#[test]
+fn update_moves_and_refiles() {
+    let mut entities = Entities::new();
+    let id = entities.spawn(
+        vec3(15.0, 1.0, 1.0),
+        vec3(2.0, 0.0, 0.0),
+        HALF,
+        EntityMesh::Block(BlockKind::Gravel),
+    );
+    entities.update(1.0);
+
+    assert_eq!(entities.get(id).unwrap().position, vec3(17.0, 1.0, 1.0));
+    assert!(entities.region((0, 0, 0)).is_empty());
+    assert_eq!(entities.region((1, 0, 0))[0].id, id);
+}
+
+#[test]
+fn box_query_finds_overlapping_entities() {
+    let mut entities = Entities::new();
+    let inside = spawn_at(&mut entities, vec3(5.0, 5.0, 5.0));
+    let touching = spawn_at(&mut entities, vec3(10.5, 5.0, 5.0));
+    let _outside = spawn_at(&mut entities, vec3(20.0, 5.0, 5.0));
+
+    let found = entities.query_box(&Aabb::new(vec3(0.0, 0.0, 0.0), vec3(10.0, 10.0, 10.0)));
+    assert_eq!(ids(found), vec![inside, touching]);
+}
+
+#[test]
+fn box_query_sees_entities_sticking_out_of_their_region() {
+    let mut entities = Entities::new();
+    // Centered in region 1 but reaching back into region 0
+    let big = entities.spawn(
+        vec3(17.0, 1.0, 1.0),
+        STILL,
+        vec3(3.0, 1.0, 1.0),
+        EntityMesh::Block(BlockKind::Stone),
+    );
+    let found = entities.query_box(&Aabb::new(vec3(14.0, 0.0, 0.0), vec3(14.5, 2.0, 2.0)));
+    assert_eq!(ids(found), vec![big]);
+}
+
+#[test]
+fn radius_query_uses_distance_to_the_box() {
+    let mut entities = Entities::new();
+    let near = spawn_at(&mut entities, vec3(3.0, 0.0, 0.0));
+    let _corner = spawn_at(&mut entities, vec3(3.0, 3.0, 0.0));
+
+    // The box of `near` starts 2.5 away, the corner one is ~3.5 away
+    let found = entities.query_radius(vec3(0.0, 0.0, 0.0), 3.0);
+    assert_eq!(ids(found), vec![near]);
+}
+
+#[test]
+fn aabb_distance_is_zero_inside() {
+    let aabb = Aabb::from_center(vec3(0.0, 0.0, 0.0), HALF);
+    assert_eq!(aabb.distance2(vec3(0.2, -0.3, 0.1)), 0.0);
+    assert_eq!(aabb.distance2(vec3(2.5, 0.0, 0.0)), 4.0);
+}
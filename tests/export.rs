@@ -0,0 +1,143 @@
+use gltf::{image::Source, material::AlphaMode, Gltf};
+use vox23::{
+    chunk::{Block, BlockKind, Chunk},
+    export, mesh,
+};
+
+fn export(chunks: &[Chunk]) -> Gltf {
+    let mut bytes = vec![];
+    export::write_glb(chunks, &mut bytes).unwrap();
+    Gltf::from_slice(&bytes).expect("exported file should be valid glTF")
+}
+
+/// One stone block floating in the middle of the chunk.
+fn single_stone() -> Chunk {
+    let mut chunk = Chunk::new(2, 0, -1);
+    chunk.set_block(Block::new(BlockKind::Stone, 5, 5, 5));
+    chunk
+}
+
+#[test]
+fn lone_block_has_six_faces() {
+    let meshes = mesh::chunk_meshes(&single_stone());
+    assert_eq!(meshes.len(), 1);
+    assert_eq!(meshes[0].kind, BlockKind::Stone);
+    assert_eq!(meshes[0].positions.len(), 24);
+    assert_eq!(meshes[0].indices.len(), 36);
+    assert_eq!(meshes[0].bounds(), ([4.5, 4.5, 4.5], [5.5, 5.5, 5.5]));
+}
+
+#[test]
+fn faces_between_solid_blocks_are_left_out() {
+    let mut chunk = single_stone();
+    chunk.set_block(Block::new(BlockKind::Dirt, 6, 5, 5));
+    let meshes = mesh::chunk_meshes(&chunk);
+    let faces: usize = meshes.iter().map(|m| m.indices.len() / 6).sum();
+    assert_eq!(faces, 10);
+}
+
+#[test]
+fn shallow_water_is_shorter() {
+    let mut chunk = Chunk::new(0, 0, 0);
+    chunk.set_block(Block {
+        level: 4,
+        ..Block::new(BlockKind::Water, 5, 5, 5)
+    });
+    let (min, max) = mesh::chunk_meshes(&chunk)[0].bounds();
+    assert_eq!(min[1], 4.5);
+    assert!(max[1] < 5.5);
+}
+
+#[test]
+fn glb_has_a_node_per_chunk_at_its_origin() {
+    let gltf = export(&[single_stone(), Chunk::new(0, 0, 0)]);
+
+    // The empty chunk has nothing to draw
+    let nodes: Vec<_> = gltf.nodes().collect();
+    assert_eq!(nodes.len(), 1);
+    let (translation, _, _) = nodes[0].transform().decomposed();
+    assert_eq!(translation, [32.0, 0.0, -16.0]);
+    assert_eq!(gltf.default_scene().unwrap().nodes().count(), 1);
+}
+
+#[test]
+fn glb_geometry_matches_the_chunk_meshes() {
+    let chunk = single_stone();
+    let expected = &mesh::chunk_meshes(&chunk)[0];
+    let gltf = export(&[chunk]);
+    let blob = gltf.blob.as_deref().unwrap();
+
+    let mesh = gltf.meshes().next().unwrap();
+    let primitive = mesh.primitives().next().unwrap();
+    let reader = primitive.reader(|_| Some(blob));
+    let positions: Vec<[f32; 3]> = reader.read_positions().unwrap().collect();
+    let normals: Vec<[f32; 3]> = reader.read_normals().unwrap().collect();
+    let tex_coords: Vec<[f32; 2]> = reader.read_tex_coords(0).unwrap().into_f32().collect();
+    let indices: Vec<u32> = reader.read_indices().unwrap().into_u32().collect();
+    assert_eq!(positions, expected.positions);
+    assert_eq!(normals, expected.normals);
+    assert_eq!(tex_coords, expected.tex_coords);
+    assert_eq!(indices, expected.indices);
+
+    // Stone is the first atlas tile
+    for [u, v] in tex_coords {
+        assert!((0.0..=1.0 / 16.0).contains(&u));
+        assert!((0.0..=1.0 / 16.0).contains(&v));
+    }
+}
+
+#[test]
+fn every_block_kind_gets_its_own_material() {
+    let mut chunk = Chunk::new(0, 0, 0);
+    chunk.set_block(Block::new(BlockKind::Stone, 1, 1, 1));
+    chunk.set_block(Block::new(BlockKind::Leaves, 3, 1, 1));
+    chunk.set_block(Block::new(BlockKind::Water, 5, 1, 1));
+    chunk.set_block(Block::new(BlockKind::Stone, 7, 1, 1));
+    let gltf = export(&[chunk]);
+
+    let materials: Vec<_> = gltf
+        .materials()
+        .map(|m| (m.name().unwrap().to_string(), m.alpha_mode()))
+        .collect();
+    assert_eq!(
+        materials,
+        vec![
+            ("stone".to_string(), AlphaMode::Opaque),
+            ("leaves".to_string(), AlphaMode::Mask),
+            ("water".to_string(), AlphaMode::Blend),
+        ]
+    );
+    let mesh = gltf.meshes().next().unwrap();
+    assert_eq!(mesh.primitives().count(), 3);
+    for primitive in mesh.primitives() {
+        let material = primitive.material();
+        let texture = material.pbr_metallic_roughness().base_color_texture().unwrap();
+        assert_eq!(texture.texture().index(), 0);
+    }
+}
+
+#[test]
+fn atlas_is_embedded() {
+    let gltf = export(&[single_stone()]);
+    let blob = gltf.blob.as_deref().unwrap();
+
+    let image = gltf.images().next().unwrap();
+    let view = match image.source() {
+        Source::View { view, mime_type } => {
+            assert_eq!(mime_type, "image/png");
+            view
+        }
+        Source::Uri { .. } => panic!("atlas should be inside the file"),
+    };
+    let png = &blob[view.offset()..view.offset() + view.length()];
+    let atlas = image::load_from_memory(png).unwrap();
+    let expected = image::load_from_memory(include_bytes!("../atlas.png")).unwrap();
+    assert_eq!(atlas.to_rgba8(), expected.to_rgba8());
+}
+
+#[test]
+fn empty_world_is_still_valid() {
+    let gltf = export(&[Chunk::new(0, 0, 0)]);
+    assert_eq!(gltf.meshes().count(), 0);
+    assert_eq!(gltf.images().count(), 1);
+}
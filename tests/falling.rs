@@ -0,0 +1,111 @@
+use vox23::{
+    chunk::{Block, BlockKind, Chunk},
+    tick::{BlockTicks, Position},
+};
+
+/// A chunk with a stone floor at y = 0 and no random ticks.
+fn setup() -> (Chunk, BlockTicks) {
+    let mut chunk = Chunk::new(0, 0, 0);
+    for x in 0..16 {
+        for z in 0..16 {
+            chunk.set_block(Block::new(BlockKind::Stone, x, 0, z));
+        }
+    }
+    let mut ticks = BlockTicks::new(0);
+    ticks.random_tick_speed = 0;
+    (chunk, ticks)
+}
+
+fn place(chunk: &mut Chunk, ticks: &mut BlockTicks, kind: BlockKind, pos: Position) {
+    chunk.set_block(Block::new(kind, pos.0, pos.1, pos.2));
+    ticks.block_changed(chunk, pos);
+}
+
+fn kind(chunk: &Chunk, pos: Position) -> BlockKind {
+    chunk.get_block(pos.0, pos.1, pos.2).unwrap().kind
+}
+
+fn column(chunk: &Chunk, x: i64, z: i64) -> Vec<BlockKind> {
+    (0..16).map(|y| kind(chunk, (x, y, z))).collect()
+}
+
+#[test]
+fn sand_in_the_air_falls_and_lands() {
+    let (mut chunk, mut ticks) = setup();
+    place(&mut chunk, &mut ticks, BlockKind::Sand, (4, 12, 4));
+
+    for _ in 0..3 {
+        ticks.tick(&mut chunk);
+    }
+    assert_eq!(kind(&chunk, (4, 12, 4)), BlockKind::Air);
+    let falling = ticks.falling_blocks();
+    assert_eq!(falling.len(), 1);
+    assert_eq!(falling[0].kind, BlockKind::Sand);
+    assert!(falling[0].y < 12.0);
+
+    ticks.settle(&mut chunk, 1000);
+    assert!(ticks.falling_blocks().is_empty());
+    assert_eq!(kind(&chunk, (4, 1, 4)), BlockKind::Sand);
+    assert_eq!(kind(&chunk, (4, 2, 4)), BlockKind::Air);
+}
+
+#[test]
+fn supported_sand_stays_put() {
+    let (mut chunk, mut ticks) = setup();
+    place(&mut chunk, &mut ticks, BlockKind::Gravel, (4, 1, 4));
+    ticks.settle(&mut chunk, 1000);
+
+    assert_eq!(kind(&chunk, (4, 1, 4)), BlockKind::Gravel);
+    assert!(ticks.falling_blocks().is_empty());
+}
+
+#[test]
+fn removing_support_drops_the_whole_stack_in_order() {
+    let (mut chunk, mut ticks) = setup();
+    for y in 1..=5 {
+        chunk.set_block(Block::new(BlockKind::Stone, 4, y, 4));
+    }
+    let stack = [BlockKind::Sand, BlockKind::Gravel, BlockKind::Sand, BlockKind::Gravel];
+    for (i, kind) in stack.iter().enumerate() {
+        chunk.set_block(Block::new(*kind, 4, 6 + i as i64, 4));
+    }
+
+    // Knock out the pillar top to bottom
+    for y in (1..=5).rev() {
+        place(&mut chunk, &mut ticks, BlockKind::Air, (4, y, 4));
+    }
+    ticks.settle(&mut chunk, 1000);
+
+    let mut expected = vec![BlockKind::Stone];
+    expected.extend(stack);
+    expected.resize(16, BlockKind::Air);
+    assert_eq!(column(&chunk, 4, 4), expected);
+}
+
+#[test]
+fn falling_sand_displaces_water() {
+    let (mut chunk, mut ticks) = setup();
+    // A one block well so the water can't spread
+    for y in 1..=3 {
+        for (x, z) in [(3, 4), (5, 4), (4, 3), (4, 5)] {
+            chunk.set_block(Block::new(BlockKind::Stone, x, y, z));
+        }
+    }
+    chunk.set_block(Block::new(BlockKind::Water, 4, 1, 4));
+    place(&mut chunk, &mut ticks, BlockKind::Sand, (4, 10, 4));
+    ticks.settle(&mut chunk, 1000);
+
+    assert_eq!(kind(&chunk, (4, 1, 4)), BlockKind::Sand);
+    assert_eq!(kind(&chunk, (4, 2, 4)), BlockKind::Air);
+}
+
+#[test]
+fn chunk_bottom_holds_sand_up() {
+    let mut chunk = Chunk::new(0, 0, 0);
+    let mut ticks = BlockTicks::new(0);
+    ticks.random_tick_speed = 0;
+    place(&mut chunk, &mut ticks, BlockKind::Sand, (2, 7, 2));
+    ticks.settle(&mut chunk, 1000);
+
+    assert_eq!(kind(&chunk, (2, 0, 2)), BlockKind::Sand);
+}
@@ -0,0 +1,197 @@
+use vox23::{
+    chunk::{Block, BlockKind, Chunk},
+    fluid::{FALLING, SOURCE_LEVEL},
+    mesh,
+    tick::{BlockTicks, Position},
+};
+
+/// A chunk with a stone floor at y = 0.
+fn floor() -> Chunk {
+    let mut chunk = Chunk::new(0, 0, 0);
+    for x in 0..16 {
+        for z in 0..16 {
+            chunk.set_block(Block::new(BlockKind::Stone, x, 0, z));
+        }
+    }
+    chunk
+}
+
+fn place(chunk: &mut Chunk, sim: &mut BlockTicks, kind: BlockKind, pos: Position) {
+    chunk.set_block(Block::new(kind, pos.0, pos.1, pos.2));
+    sim.block_changed(chunk, pos);
+}
+
+fn block(chunk: &Chunk, pos: Position) -> (BlockKind, u8) {
+    let b = chunk.get_block(pos.0, pos.1, pos.2).unwrap();
+    (b.kind, b.level)
+}
+
+fn fluid_blocks(chunk: &Chunk) -> Vec<(Position, u8)> {
+    let mut found = vec![];
+    for x in 0..16 {
+        for y in 0..16 {
+            for z in 0..16 {
+                let b = chunk.get_block(x, y, z).unwrap();
+                if b.kind.is_fluid() {
+                    found.push(((x, y, z), b.level));
+                }
+            }
+        }
+    }
+    found
+}
+
+#[test]
+fn water_spreads_seven_blocks_from_a_source() {
+    let mut chunk = floor();
+    let mut sim = BlockTicks::new(0);
+    place(&mut chunk, &mut sim, BlockKind::Water, (8, 1, 8));
+    sim.settle(&mut chunk, 1000);
+
+    assert!(sim.is_idle());
+    assert_eq!(block(&chunk, (8, 1, 8)), (BlockKind::Water, SOURCE_LEVEL));
+    for distance in 1..=7 {
+        assert_eq!(
+            block(&chunk, (8 + distance, 1, 8)),
+            (BlockKind::Water, distance as u8)
+        );
+    }
+    assert_eq!(block(&chunk, (0, 1, 8)).0, BlockKind::Air);
+    // Flows around corners by manhattan distance
+    assert_eq!(block(&chunk, (10, 1, 10)), (BlockKind::Water, 4));
+    assert_eq!(block(&chunk, (8, 2, 8)).0, BlockKind::Air);
+}
+
+#[test]
+fn lava_spreads_less_far_and_slower() {
+    let mut chunk = floor();
+    let mut sim = BlockTicks::new(0);
+    place(&mut chunk, &mut sim, BlockKind::Lava, (8, 1, 8));
+
+    for _ in 0..29 {
+        sim.tick(&mut chunk);
+    }
+    assert_eq!(block(&chunk, (9, 1, 8)).0, BlockKind::Air);
+    sim.settle(&mut chunk, 1000);
+
+    assert_eq!(block(&chunk, (11, 1, 8)), (BlockKind::Lava, 3));
+    assert_eq!(block(&chunk, (12, 1, 8)).0, BlockKind::Air);
+}
+
+#[test]
+fn water_falls_off_a_ledge_and_spreads_below() {
+    let mut chunk = floor();
+    let mut sim = BlockTicks::new(0);
+    // A platform at y = 4 over x 0..=4
+    for x in 0..=4 {
+        for z in 0..16 {
+            chunk.set_block(Block::new(BlockKind::Stone, x, 4, z));
+        }
+    }
+    place(&mut chunk, &mut sim, BlockKind::Water, (4, 5, 8));
+    sim.settle(&mut chunk, 1000);
+
+    assert_eq!(block(&chunk, (5, 5, 8)), (BlockKind::Water, 1));
+    for y in 1..5 {
+        assert_eq!(block(&chunk, (5, y, 8)), (BlockKind::Water, FALLING));
+    }
+    // Lands and spreads as if it were fresh
+    assert_eq!(block(&chunk, (6, 1, 8)), (BlockKind::Water, 1));
+    assert_eq!(block(&chunk, (12, 1, 8)), (BlockKind::Water, 7));
+    assert_eq!(block(&chunk, (13, 1, 8)).0, BlockKind::Air);
+}
+
+#[test]
+fn flowing_water_dries_up_without_its_source() {
+    let mut chunk = floor();
+    let mut sim = BlockTicks::new(0);
+    place(&mut chunk, &mut sim, BlockKind::Water, (8, 1, 8));
+    sim.settle(&mut chunk, 1000);
+    assert!(!fluid_blocks(&chunk).is_empty());
+
+    place(&mut chunk, &mut sim, BlockKind::Air, (8, 1, 8));
+    sim.settle(&mut chunk, 1000);
+
+    assert!(sim.is_idle());
+    assert_eq!(fluid_blocks(&chunk), vec![]);
+}
+
+#[test]
+fn water_between_two_sources_becomes_a_source() {
+    let mut chunk = floor();
+    let mut sim = BlockTicks::new(0);
+    place(&mut chunk, &mut sim, BlockKind::Water, (7, 1, 8));
+    place(&mut chunk, &mut sim, BlockKind::Water, (9, 1, 8));
+    sim.settle(&mut chunk, 1000);
+
+    assert_eq!(block(&chunk, (8, 1, 8)), (BlockKind::Water, SOURCE_LEVEL));
+    // Lava doesn't renew
+    let mut chunk = floor();
+    let mut sim = BlockTicks::new(0);
+    place(&mut chunk, &mut sim, BlockKind::Lava, (7, 1, 8));
+    place(&mut chunk, &mut sim, BlockKind::Lava, (9, 1, 8));
+    sim.settle(&mut chunk, 1000);
+
+    assert_eq!(block(&chunk, (8, 1, 8)), (BlockKind::Lava, 1));
+}
+
+#[test]
+fn walls_stop_the_flow() {
+    let mut chunk = floor();
+    let mut sim = BlockTicks::new(0);
+    for z in 0..16 {
+        chunk.set_block(Block::new(BlockKind::Stone, 10, 1, z));
+    }
+    place(&mut chunk, &mut sim, BlockKind::Water, (8, 1, 8));
+    sim.settle(&mut chunk, 1000);
+
+    assert_eq!(block(&chunk, (9, 1, 8)), (BlockKind::Water, 1));
+    assert_eq!(block(&chunk, (10, 1, 8)).0, BlockKind::Stone);
+    assert_eq!(block(&chunk, (11, 1, 8)).0, BlockKind::Air);
+}
+
+#[test]
+fn same_setup_gives_the_same_ticks() {
+    let run = || {
+        let mut chunk = floor();
+        let mut sim = BlockTicks::new(0);
+        place(&mut chunk, &mut sim, BlockKind::Water, (3, 6, 3));
+        place(&mut chunk, &mut sim, BlockKind::Lava, (12, 1, 12));
+        let mut history = vec![];
+        for _ in 0..200 {
+            history.push(sim.tick(&mut chunk));
+        }
+        (history, fluid_blocks(&chunk))
+    };
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn lava_hides_only_the_faces_it_reaches() {
+    let faces = |chunk: &Chunk, kind: BlockKind| {
+        mesh::chunk_meshes(chunk)
+            .into_iter()
+            .find(|m| m.kind == kind)
+            .map(|m| m.indices.len() / 6)
+            .unwrap_or(0)
+    };
+    let mut chunk = Chunk::new(0, 0, 0);
+    chunk.set_block(Block::new(BlockKind::Stone, 8, 1, 8));
+    chunk.set_block(Block::new(BlockKind::Lava, 9, 1, 8));
+    // A source is lower than a block, so the stone's side still shows above
+    // it, but the stone covers the side of the lava
+    assert_eq!(faces(&chunk, BlockKind::Stone), 6);
+    assert_eq!(faces(&chunk, BlockKind::Lava), 5);
+
+    // With lava above, the lava fills its block and hides the stone's side,
+    // and each lava block hides the face of the other
+    chunk.set_block(Block::new(BlockKind::Lava, 9, 2, 8));
+    assert_eq!(faces(&chunk, BlockKind::Stone), 5);
+    assert_eq!(faces(&chunk, BlockKind::Lava), 9);
+
+    // Sources side by side are as high as each other
+    let mut chunk = Chunk::new(0, 0, 0);
+    chunk.set_block(Block::new(BlockKind::Lava, 4, 1, 4));
+    chunk.set_block(Block::new(BlockKind::Lava, 5, 1, 4));
+    assert_eq!(faces(&chunk, BlockKind::Lava), 10);
+}
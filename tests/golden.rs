@@ -0,0 +1,216 @@
+//! Renders fixed scenes on the fallback adapter and compares them against
+//! the reference images in `tests/golden`.
+//!
+//! Run with `GOLDEN_BLESS=1` to (re)write the references after an intended
+//! visual change. Failing comparisons leave the actual frame and a diff image
+//! in the cargo target tmp dir.
+use std::path::PathBuf;
+
+use image::{Rgba, RgbaImage};
+use vox23::{
+    camera::Camera,
+    chunk::{Block, BlockKind, Chunk},
+    entity::{Entities, EntityMesh},
+    offscreen::OffscreenRenderer,
+    worldgen,
+};
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 192;
+/// Largest per channel difference that still counts as the same pixel.
+const CHANNEL_TOLERANCE: u8 = 8;
+/// Fraction of pixels that may differ before the comparison fails.
+const PIXEL_TOLERANCE: f32 = 0.002;
+
+fn camera(eye: (f32, f32, f32), target: (f32, f32, f32)) -> Camera {
+    Camera {
+        eye: eye.into(),
+        target: target.into(),
+        up: cgmath::Vector3::unit_y(),
+        aspect: WIDTH as f32 / HEIGHT as f32,
+        fovy: 45.0,
+        znear: 0.1,
+        zfar: 2048.0,
+    }
+}
+
+fn renderer() -> Option<OffscreenRenderer> {
+    match OffscreenRenderer::new_fallback(WIDTH, HEIGHT) {
+        Ok(renderer) => Some(renderer),
+        Err(e) => {
+            eprintln!("Skipping golden image test: {}", e);
+            None
+        }
+    }
+}
+
+fn diff_image(expected: &RgbaImage, actual: &RgbaImage) -> (RgbaImage, usize) {
+    let mut diff = RgbaImage::new(expected.width(), expected.height());
+    let mut differing = 0;
+    for (x, y, e) in expected.enumerate_pixels() {
+        let a = actual.get_pixel(x, y);
+        let off = e
+            .0
+            .iter()
+            .zip(a.0.iter())
+            .any(|(e, a)| e.abs_diff(*a) > CHANNEL_TOLERANCE);
+        let px = if off {
+            differing += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let luma = ((e[0] as u32 + e[1] as u32 + e[2] as u32) / 3 / 3) as u8;
+            Rgba([luma, luma, luma, 255])
+        };
+        diff.put_pixel(x, y, px);
+    }
+    (diff, differing)
+}
+
+fn check_golden(name: &str, actual: &RgbaImage) {
+    let reference = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name));
+    if std::env::var_os("GOLDEN_BLESS").is_some() {
+        std::fs::create_dir_all(reference.parent().unwrap()).unwrap();
+        actual.save(&reference).unwrap();
+        return;
+    }
+    let expected = match image::open(&reference) {
+        Ok(img) => img.to_rgba8(),
+        Err(e) => panic!(
+            "Couldn't load {}: {}. Run with GOLDEN_BLESS=1 to create it",
+            reference.display(),
+            e
+        ),
+    };
+    assert_eq!(
+        expected.dimensions(),
+        actual.dimensions(),
+        "{} changed size",
+        name
+    );
+
+    let (diff, differing) = diff_image(&expected, actual);
+    let allowed = (expected.width() * expected.height()) as f32 * PIXEL_TOLERANCE;
+    if differing as f32 > allowed {
+        let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+        let actual_path = out.join(format!("{}.actual.png", name));
+        let diff_path = out.join(format!("{}.diff.png", name));
+        actual.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "{}: {} pixels differ (allowed {}), see {} and {}",
+            name,
+            differing,
+            allowed as usize,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+fn default_world_overview() {
+    let mut renderer = match renderer() {
+        Some(r) => r,
+        None => return,
+    };
+    renderer.set_chunks(&worldgen::generate(worldgen::DEFAULT_SEED));
+    let img = renderer
+        .render(&camera((10.0, 24.0, -6.0), (24.0, 4.0, 8.0)))
+        .expect("read back frame");
+    check_golden("default_world_overview", &img);
+}
+
+#[test]
+fn seeded_world_close_up() {
+    let mut renderer = match renderer() {
+        Some(r) => r,
+        None => return,
+    };
+    renderer.set_chunks(&worldgen::generate(1337));
+    let img = renderer
+        .render(&camera((32.0, 10.0, 16.0), (20.0, 4.0, 4.0)))
+        .expect("read back frame");
+    check_golden("seeded_world_close_up", &img);
+}
+
+#[test]
+fn empty_world_is_sky() {
+    let mut renderer = match renderer() {
+        Some(r) => r,
+        None => return,
+    };
+    renderer.set_chunks(&[]);
+    let img = renderer
+        .render(&camera((0.0, 1.0, 2.0), (0.0, 0.0, 0.0)))
+        .expect("read back frame");
+    check_golden("empty_world_is_sky", &img);
+}
+
+#[test]
+fn translucent_blocks_blend_back_to_front() {
+    let mut renderer = match renderer() {
+        Some(r) => r,
+        None => return,
+    };
+    // Panes of glass behind a pool of water, in one chunk, with stone behind
+    // both so the order they blend in shows
+    let mut chunk = Chunk::new(0, 0, 0);
+    for x in 0..16 {
+        for z in 0..16 {
+            chunk.set_block(Block::new(BlockKind::Stone, x, 0, z));
+        }
+        for y in 1..6 {
+            chunk.set_block(Block::new(BlockKind::Stone, x, y, 2));
+        }
+    }
+    for x in 3..13 {
+        for y in 1..5 {
+            chunk.set_block(Block::new(BlockKind::Glass, x, y, 5));
+        }
+        for z in 8..12 {
+            chunk.set_block(Block::new(BlockKind::Water, x, 1, z));
+            chunk.set_block(Block::new(BlockKind::Water, x, 2, z));
+        }
+    }
+    renderer.set_chunks(&[chunk]);
+    let img = renderer
+        .render(&camera((8.0, 7.0, 20.0), (8.0, 2.0, 4.0)))
+        .expect("read back frame");
+    check_golden("translucent_blocks_blend_back_to_front", &img);
+}
+
+#[test]
+fn stretched_entities_are_lit_like_blocks() {
+    let mut renderer = match renderer() {
+        Some(r) => r,
+        None => return,
+    };
+    let mut chunk = Chunk::new(0, 0, 0);
+    for x in 0..16 {
+        for z in 0..16 {
+            chunk.set_block(Block::new(BlockKind::Grass, x, 0, z));
+        }
+    }
+    renderer.set_chunks(&[chunk]);
+    // Boxes stretched along each axis, next to a block to compare against.
+    // Leaves have holes cut in them like the leaves blocks.
+    let still = cgmath::vec3(0.0, 0.0, 0.0);
+    let mut entities = Entities::new();
+    for (x, half_extents, kind) in [
+        (4.0, (0.5, 0.5, 0.5), BlockKind::Stone),
+        (6.5, (1.5, 0.25, 0.5), BlockKind::Log),
+        (9.0, (0.5, 2.0, 0.5), BlockKind::Sand),
+        (11.0, (0.5, 0.5, 2.0), BlockKind::Leaves),
+    ] {
+        let half_extents: cgmath::Vector3<f32> = half_extents.into();
+        let center = cgmath::vec3(x, 0.5 + half_extents.y, 8.0);
+        entities.spawn(center, still, half_extents, EntityMesh::Block(kind));
+    }
+    renderer.set_entities(&entities);
+    let img = renderer
+        .render(&camera((2.0, 7.0, 18.0), (8.0, 1.5, 8.0)))
+        .expect("read back frame");
+    check_golden("stretched_entities_are_lit_like_blocks", &img);
+}
@@ -0,0 +1,133 @@
+use image::{ImageBuffer, Luma, Rgb, RgbImage};
+use vox23::{
+    chunk::{self, BlockKind},
+    heightmap::{self, HeightImage, HeightmapError, HeightmapOptions},
+};
+
+/// Height rises by a block per pixel to the east.
+fn ramp(width: u32, length: u32) -> HeightImage {
+    ImageBuffer::from_fn(width, length, |x, _| Luma([x as u16 * 1000]))
+}
+
+fn options() -> HeightmapOptions {
+    HeightmapOptions {
+        origin: (0, 0, 0),
+        scale: u16::MAX as f32 / 1000.0,
+        sea_level: None,
+    }
+}
+
+/// Highest block other than air and water in a column.
+fn ground(chunks: &[chunk::Chunk], x: i64, z: i64) -> Option<(i64, BlockKind)> {
+    (-16..64)
+        .rev()
+        .map(|y| (y, chunk::world_block(chunks, (x, y, z))))
+        .find(|(_, k)| !matches!(k, BlockKind::None | BlockKind::Air | BlockKind::Water))
+}
+
+#[test]
+fn brightness_sets_column_height() {
+    let chunks = heightmap::build(&ramp(8, 2), None, &options()).unwrap();
+    for x in 0..8 {
+        assert_eq!(ground(&chunks, x, 1), Some((x, BlockKind::Grass)));
+    }
+    assert_eq!(chunk::world_block(&chunks, (5, 4, 0)), BlockKind::Dirt);
+    assert_eq!(chunk::world_block(&chunks, (5, 0, 0)), BlockKind::Stone);
+    // Nothing is built past the image
+    assert_eq!(ground(&chunks, 8, 0), None);
+}
+
+#[test]
+fn scale_and_origin_move_the_terrain() {
+    let white = ImageBuffer::from_pixel(1, 1, Luma([u16::MAX]));
+    let options = HeightmapOptions {
+        origin: (-3, -10, 5),
+        scale: 20.0,
+        sea_level: None,
+    };
+    let chunks = heightmap::build(&white, None, &options).unwrap();
+    assert_eq!(ground(&chunks, -3, 5), Some((10, BlockKind::Grass)));
+    assert_eq!(chunk::world_block(&chunks, (-3, -10, 5)), BlockKind::Stone);
+    assert_eq!(chunk::world_block(&chunks, (-3, -11, 5)), BlockKind::Air);
+}
+
+#[test]
+fn terrain_spans_as_many_chunks_as_needed() {
+    let chunks = heightmap::build(&ramp(40, 20), None, &options()).unwrap();
+    let mut positions: Vec<_> = chunks.iter().map(|c| c.position()).collect();
+    positions.sort_unstable();
+    positions.dedup();
+    assert_eq!(positions.len(), chunks.len());
+    // 3 chunks wide, 2 long and up to 39 blocks high
+    assert_eq!(chunks.len(), 3 * 2 + 2 * 2 + 2);
+}
+
+#[test]
+fn low_ground_is_flooded_to_sea_level() {
+    let options = HeightmapOptions {
+        sea_level: Some(3),
+        ..options()
+    };
+    let chunks = heightmap::build(&ramp(6, 1), None, &options).unwrap();
+    assert_eq!(chunk::world_block(&chunks, (1, 1, 0)), BlockKind::Sand);
+    assert_eq!(chunk::world_block(&chunks, (1, 2, 0)), BlockKind::Water);
+    assert_eq!(chunk::world_block(&chunks, (1, 3, 0)), BlockKind::Water);
+    assert_eq!(chunk::world_block(&chunks, (1, 4, 0)), BlockKind::Air);
+    assert_eq!(ground(&chunks, 4, 0), Some((4, BlockKind::Grass)));
+}
+
+#[test]
+fn material_map_picks_the_surface() {
+    let mut materials = RgbImage::from_pixel(4, 1, Rgb([80, 150, 50]));
+    materials.put_pixel(2, 0, Rgb([230, 200, 140]));
+    materials.put_pixel(3, 0, Rgb([40, 40, 40]));
+    let chunks = heightmap::build(&ramp(4, 1), Some(&materials), &options()).unwrap();
+    assert_eq!(ground(&chunks, 1, 0), Some((1, BlockKind::Grass)));
+    assert_eq!(ground(&chunks, 2, 0), Some((2, BlockKind::Sand)));
+    // Sand is kept as topsoil instead of dirt
+    assert_eq!(chunk::world_block(&chunks, (2, 0, 0)), BlockKind::Sand);
+    assert_eq!(ground(&chunks, 3, 0), Some((3, BlockKind::Stone)));
+}
+
+#[test]
+fn material_map_must_match_the_heightmap() {
+    let materials = RgbImage::new(3, 1);
+    let result = heightmap::build(&ramp(4, 1), Some(&materials), &options());
+    assert!(matches!(
+        result,
+        Err(HeightmapError::SizeMismatch {
+            heightmap: (4, 1),
+            materials: (3, 1)
+        })
+    ));
+}
+
+#[test]
+fn loads_png_files() {
+    let dir = std::env::temp_dir().join(format!("vox23-heightmap-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let heights = dir.join("height.png");
+    let materials = dir.join("materials.png");
+    // 8 bit grayscale, as most image editors save heightmaps
+    image::GrayImage::from_fn(2, 2, |x, z| Luma([((x + z) * 255 / 2) as u8]))
+        .save(&heights)
+        .unwrap();
+    RgbImage::from_pixel(2, 2, Rgb([120, 120, 115]))
+        .save(&materials)
+        .unwrap();
+
+    let options = HeightmapOptions {
+        scale: 4.0,
+        ..options()
+    };
+    let chunks = heightmap::load(&heights, Some(&materials), &options).unwrap();
+    assert_eq!(ground(&chunks, 0, 0), Some((0, BlockKind::Gravel)));
+    assert_eq!(ground(&chunks, 1, 0), Some((2, BlockKind::Gravel)));
+    assert_eq!(ground(&chunks, 1, 1), Some((4, BlockKind::Gravel)));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(matches!(
+        heightmap::load(dir.join("missing.png"), None, &options),
+        Err(HeightmapError::Image(_))
+    ));
+}
@@ -0,0 +1,228 @@
+use vox23::{
+    chunk::{self, BlockData, BlockKind},
+    editor::{self, Selection},
+    journal::Journal,
+    state::{BlockState, Facing},
+};
+
+#[test]
+fn undo_and_redo_round_trip() {
+    let mut chunks = vec![];
+    chunk::set_world_block(&mut chunks, BlockKind::Dirt, (1, 0, 0));
+    let mut journal = Journal::default();
+    let selection = Selection::new((0, 0, 0), (2, 1, 2));
+    journal.apply(
+        &mut chunks,
+        "Fill",
+        &editor::fill(&selection, BlockKind::Stone),
+    );
+    assert_eq!(selection.count(&chunks, BlockKind::Stone), 18);
+    assert_eq!(journal.next_undo(), Some("Fill"));
+    assert_eq!(journal.next_redo(), None);
+
+    assert!(journal.undo(&mut chunks).is_some());
+    assert_eq!(chunk::world_block(&chunks, (1, 0, 0)), BlockKind::Dirt);
+    assert_eq!(selection.count(&chunks, BlockKind::Air), 17);
+    assert_eq!(journal.next_undo(), None);
+    assert_eq!(journal.next_redo(), Some("Fill"));
+    assert!(journal.undo(&mut chunks).is_none());
+
+    assert!(journal.redo(&mut chunks).is_some());
+    assert_eq!(selection.count(&chunks, BlockKind::Stone), 18);
+    assert!(journal.redo(&mut chunks).is_none());
+}
+
+#[test]
+fn undo_and_redo_keep_states_and_levels() {
+    let water = BlockData {
+        kind: BlockKind::Water,
+        state: BlockState::default(),
+        level: 5,
+    };
+    let stairs = BlockData {
+        kind: BlockKind::StoneStairs,
+        state: BlockState::default().with_facing(Facing::West),
+        level: 0,
+    };
+    let mut chunks = vec![];
+    let mut journal = Journal::default();
+    journal.apply(&mut chunks, "Water", &[((0, 0, 0), water)]);
+    journal.apply(&mut chunks, "Place", &[((0, 0, 0), stairs)]);
+    assert_eq!(chunk::world_block_data(&chunks, (0, 0, 0)), stairs);
+
+    journal.undo(&mut chunks);
+    assert_eq!(chunk::world_block_data(&chunks, (0, 0, 0)), water);
+    journal.redo(&mut chunks);
+    assert_eq!(chunk::world_block_data(&chunks, (0, 0, 0)), stairs);
+
+    // Turning a block in place is a change of its own
+    let turned = BlockData {
+        state: stairs.state.with_facing(Facing::East),
+        ..stairs
+    };
+    assert!(!journal
+        .apply(&mut chunks, "Turn", &[((0, 0, 0), turned)])
+        .is_empty());
+    journal.undo(&mut chunks);
+    assert_eq!(chunk::world_block_data(&chunks, (0, 0, 0)), stairs);
+}
+
+#[test]
+fn transactions_undo_one_at_a_time() {
+    let mut chunks = vec![];
+    let mut journal = Journal::default();
+    journal.apply(&mut chunks, "Sand", &[((0, 0, 0), BlockKind::Sand.into())]);
+    // Setting a position twice in one transaction still undoes to the start
+    journal.apply(
+        &mut chunks,
+        "Twice",
+        &[
+            ((0, 0, 0), BlockKind::Glass.into()),
+            ((1, 0, 0), BlockKind::Glass.into()),
+            ((0, 0, 0), BlockKind::Gravel.into()),
+        ],
+    );
+    assert_eq!(journal.stored_changes(), 4);
+    assert_eq!(chunk::world_block(&chunks, (0, 0, 0)), BlockKind::Gravel);
+
+    journal.undo(&mut chunks);
+    assert_eq!(chunk::world_block(&chunks, (0, 0, 0)), BlockKind::Sand);
+    assert_eq!(chunk::world_block(&chunks, (1, 0, 0)), BlockKind::Air);
+    assert_eq!(journal.next_undo(), Some("Sand"));
+    journal.undo(&mut chunks);
+    assert_eq!(chunk::world_block(&chunks, (0, 0, 0)), BlockKind::Air);
+}
+
+#[test]
+fn unchanged_blocks_are_not_recorded() {
+    let mut chunks = vec![];
+    let mut journal = Journal::default();
+    let changed = journal.apply(&mut chunks, "Air", &[((0, 0, 0), BlockKind::Air.into())]);
+    assert!(changed.is_empty());
+    assert!(changed.chunks.is_empty());
+    assert_eq!(journal.next_undo(), None);
+    journal.apply(
+        &mut chunks,
+        "Stone",
+        &[((0, 0, 0), BlockKind::Stone.into())],
+    );
+    journal.apply(
+        &mut chunks,
+        "Stone",
+        &[((0, 0, 0), BlockKind::Stone.into())],
+    );
+    assert_eq!(journal.stored_changes(), 1);
+}
+
+#[test]
+fn a_new_edit_clears_redo() {
+    let mut chunks = vec![];
+    let mut journal = Journal::default();
+    journal.apply(
+        &mut chunks,
+        "First",
+        &[((0, 0, 0), BlockKind::Stone.into())],
+    );
+    journal.undo(&mut chunks);
+    assert_eq!(journal.next_redo(), Some("First"));
+    journal.apply(
+        &mut chunks,
+        "Second",
+        &[((2, 0, 0), BlockKind::Dirt.into())],
+    );
+    assert_eq!(journal.next_redo(), None);
+    assert_eq!(journal.stored_changes(), 1);
+    assert!(journal.redo(&mut chunks).is_none());
+}
+
+#[test]
+fn oldest_transactions_are_dropped_first() {
+    let mut chunks = vec![];
+    let mut journal = Journal::new(5);
+    for (i, name) in ["a", "b", "c"].into_iter().enumerate() {
+        let selection = Selection::new((0, i as i64, 0), (1, i as i64, 0));
+        journal.apply(
+            &mut chunks,
+            name,
+            &editor::fill(&selection, BlockKind::Stone),
+        );
+    }
+    // Three transactions of two changes don't fit in five
+    assert_eq!(journal.stored_changes(), 4);
+    assert!(journal.undo(&mut chunks).is_some());
+    assert!(journal.undo(&mut chunks).is_some());
+    assert!(journal.undo(&mut chunks).is_none());
+    assert_eq!(chunk::world_block(&chunks, (0, 0, 0)), BlockKind::Stone);
+    assert_eq!(chunk::world_block(&chunks, (0, 1, 0)), BlockKind::Air);
+}
+
+#[test]
+fn oversized_transactions_clear_the_history() {
+    let mut chunks = vec![];
+    let mut journal = Journal::new(5);
+    journal.apply(
+        &mut chunks,
+        "Small",
+        &[((0, 0, 0), BlockKind::Stone.into())],
+    );
+    let selection = Selection::new((0, 1, 0), (2, 1, 1));
+    journal.apply(
+        &mut chunks,
+        "Big",
+        &editor::fill(&selection, BlockKind::Sand),
+    );
+    // Applied, but nothing left to undo
+    assert_eq!(selection.count(&chunks, BlockKind::Sand), 6);
+    assert_eq!(journal.stored_changes(), 0);
+    assert_eq!(journal.next_undo(), None);
+}
+
+#[test]
+fn dirty_chunks_follow_the_edits() {
+    let mut chunks = vec![];
+    let mut journal = Journal::default();
+    let selection = Selection::new((-1, 0, 0), (0, 0, 0));
+    let changed = journal.apply(
+        &mut chunks,
+        "Fill",
+        &editor::fill(&selection, BlockKind::Stone),
+    );
+    let expected: Vec<_> = selection
+        .positions()
+        .map(|p| chunk::split_world_position(p).0)
+        .collect();
+    assert_eq!(changed.chunks.len(), 2);
+    assert!(expected.iter().all(|c| changed.chunks.contains(c)));
+
+    let undone = journal.undo(&mut chunks).unwrap();
+    assert_eq!(undone.chunks, changed.chunks);
+    let redone = journal.redo(&mut chunks).unwrap();
+    assert_eq!(redone, changed);
+}
+
+#[test]
+fn changed_blocks_are_listed_for_ticks() {
+    let mut chunks = vec![];
+    let mut journal = Journal::default();
+    journal.apply(
+        &mut chunks,
+        "Stone",
+        &[((0, 0, 0), BlockKind::Stone.into())],
+    );
+    let changed = journal.apply(
+        &mut chunks,
+        "Sand",
+        &[
+            ((0, 0, 0), BlockKind::Stone.into()),
+            ((0, 1, 0), BlockKind::Sand.into()),
+            ((0, 2, 0), BlockKind::Water.into()),
+        ],
+    );
+    // The stone was already there
+    assert_eq!(changed.blocks, vec![(0, 1, 0), (0, 2, 0)]);
+    // Undone in reverse
+    let undone = journal.undo(&mut chunks).unwrap();
+    assert_eq!(undone.blocks, vec![(0, 2, 0), (0, 1, 0)]);
+    let redone = journal.redo(&mut chunks).unwrap();
+    assert_eq!(redone.blocks, changed.blocks);
+}
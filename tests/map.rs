@@ -0,0 +1,173 @@
+use image::Rgba;
+use vox23::{
+    blocks,
+    chunk::{self, BlockKind, Chunk},
+    map::{self, ColumnTop, Columns, MapView},
+};
+
+fn color(kind: BlockKind) -> [u8; 3] {
+    blocks::definition(kind).color
+}
+
+fn rgb(pixel: &Rgba<u8>) -> [u8; 3] {
+    [pixel[0], pixel[1], pixel[2]]
+}
+
+fn brightness(pixel: &Rgba<u8>) -> u32 {
+    pixel.0[..3].iter().map(|c| *c as u32).sum()
+}
+
+/// A flat stone floor at y 2 with a grass step up along x 8.
+fn terrace() -> Vec<Chunk> {
+    let mut chunks = vec![];
+    for x in 0..16 {
+        for z in 0..16 {
+            for y in 0..3 {
+                chunk::set_world_block(&mut chunks, BlockKind::Stone, (x, y, z));
+            }
+        }
+    }
+    for z in 0..16 {
+        chunk::set_world_block(&mut chunks, BlockKind::Grass, (8, 3, z));
+    }
+    chunks
+}
+
+#[test]
+fn finds_the_highest_block_across_chunks() {
+    let mut chunks = terrace();
+    chunk::set_world_block(&mut chunks, BlockKind::Leaves, (4, 20, 4));
+    let columns = Columns::new(&chunks);
+
+    assert_eq!(
+        columns.top(4, 4),
+        Some(ColumnTop {
+            y: 20,
+            kind: BlockKind::Leaves,
+            depth: 1
+        })
+    );
+    assert_eq!(
+        columns.top(5, 4),
+        Some(ColumnTop {
+            y: 2,
+            kind: BlockKind::Stone,
+            depth: 3
+        })
+    );
+    // Outside every chunk
+    assert_eq!(columns.top(-1, 4), None);
+    assert_eq!(columns.bounds(), Some(((0, 0), (15, 15))));
+}
+
+#[test]
+fn map_covers_every_chunk() {
+    let mut chunks = terrace();
+    chunk::set_world_block(&mut chunks, BlockKind::Sand, (-20, 0, 17));
+    let image = map::render(&chunks).unwrap();
+    // From chunk x -2 to 0 and z 0 to 1
+    assert_eq!(image.dimensions(), (48, 32));
+
+    // (0, 0) is at pixel (32, 0)
+    assert_eq!(image.get_pixel(32, 0)[3], 255);
+    // Sand is at (12, 17), the rest of its chunk is empty
+    assert_eq!(image.get_pixel(12, 17)[3], 255);
+    assert_eq!(image.get_pixel(13, 17)[3], 0);
+    assert!(map::render(&[]).is_none());
+}
+
+#[test]
+fn pixels_take_the_color_of_the_top_block() {
+    let image = map::render_area(&terrace(), (0, 0), 16, 16);
+    let stone = rgb(image.get_pixel(2, 5));
+    let grass = rgb(image.get_pixel(8, 5));
+    // Same height as the column to the north, so both get the level shade
+    for (shaded, kind) in [(stone, BlockKind::Stone), (grass, BlockKind::Grass)] {
+        let base = color(kind);
+        assert!((0..3).all(|i| shaded[i] <= base[i]));
+        assert!((0..3).all(|i| shaded[i] as f32 >= base[i] as f32 * 0.8));
+    }
+    assert!(grass[1] > grass[0] && grass[1] > grass[2]);
+}
+
+#[test]
+fn slopes_facing_north_are_lighter() {
+    let mut chunks = vec![];
+    // A ridge running east to west with a flat top from z 3 to 5
+    for x in 0..4 {
+        for z in 0..9 {
+            let height = (4 - (z - 4i64).abs()).min(3);
+            for y in 0..=height {
+                chunk::set_world_block(&mut chunks, BlockKind::Dirt, (x, y, z));
+            }
+        }
+    }
+    let image = map::render_area(&chunks, (0, 0), 4, 9);
+    let rising = brightness(image.get_pixel(1, 2));
+    let falling = brightness(image.get_pixel(1, 6));
+    let level = brightness(image.get_pixel(1, 4));
+    assert!(rising > level && level > falling);
+}
+
+#[test]
+fn deep_water_is_darker() {
+    let mut chunks = vec![];
+    for z in 0..4 {
+        chunk::set_world_block(&mut chunks, BlockKind::Stone, (0, 0, z));
+        for y in 1..=(z * 3 + 1) {
+            chunk::set_world_block(&mut chunks, BlockKind::Water, (0, 12 - y, z));
+        }
+    }
+    let image = map::render_area(&chunks, (0, 0), 1, 4);
+    let shallow = brightness(image.get_pixel(0, 0));
+    let deep = brightness(image.get_pixel(0, 3));
+    assert!(shallow > deep);
+    assert_eq!(rgb(image.get_pixel(0, 0)), color(BlockKind::Water));
+}
+
+#[test]
+fn saves_a_png() {
+    let path = std::env::temp_dir().join(format!("vox23-map-{}.png", std::process::id()));
+    map::save_png(&terrace(), &path).unwrap();
+    let image = image::open(&path).unwrap().to_rgba8();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(image, map::render(&terrace()).unwrap());
+}
+
+#[test]
+fn map_view_is_centered_on_the_player() {
+    let view = MapView {
+        center: (10.3, -4.6),
+        span: 32,
+    };
+    assert_eq!(view.min(), (-6, -21));
+    // The player's block is column 16 of 32
+    let (u, v) = view.to_map(10.0, -5.0);
+    assert!((u - 16.5 / 32.0).abs() < 1e-6 && (v - 16.5 / 32.0).abs() < 1e-6);
+
+    let (x, z) = view.to_world(0.25, 0.75);
+    let (u, v) = view.to_map(x, z);
+    assert!((u - 0.25).abs() < 1e-6 && (v - 0.75).abs() < 1e-6);
+    // The top left corner is the edge of the first block
+    assert_eq!(view.to_world(0.0, 0.0), (-6.5, -21.5));
+}
+
+#[test]
+fn map_view_only_moves_between_blocks() {
+    let view = |x| MapView {
+        center: (x, 0.0),
+        span: 16,
+    };
+    assert_eq!(view(3.1).min(), view(3.4).min());
+    assert_ne!(view(3.4).min(), view(3.6).min());
+
+    let image = view(8.0).render(&terrace());
+    assert_eq!(image.dimensions(), (16, 16));
+    // Column 8 of the world is the grass step
+    let grass = image.get_pixel(8, 12);
+    assert!(grass[1] > grass[0]);
+    assert_eq!(
+        image.get_pixel(15, 12),
+        &map::render_area(&terrace(), (15, 4), 1, 1)[(0, 0)]
+    );
+}
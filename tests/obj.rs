@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+
+use cgmath::vec3;
+use vox23::obj::{ObjError, ObjModel};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/obj")
+        .join(name)
+}
+
+#[test]
+fn loads_a_textured_cube() {
+    let model = ObjModel::load(fixture("crate.obj")).unwrap();
+
+    assert_eq!(model.meshes.len(), 1);
+    let mesh = &model.meshes[0];
+    // Six quads, each split in two
+    assert_eq!(mesh.indices.len(), 36);
+    // Corners don't share normals, so every face has its own four
+    assert_eq!(mesh.positions.len(), 24);
+    assert_eq!(mesh.normals.len(), 24);
+    assert_eq!(mesh.tex_coords.len(), 24);
+
+    assert_eq!(model.materials.len(), 1);
+    let material = &model.materials[mesh.material.unwrap()];
+    assert_eq!(material.name, "wood");
+    assert_eq!(material.diffuse, [0.8, 0.6, 0.4]);
+    assert_eq!(
+        material.diffuse_texture,
+        Some(fixture("textures/wood.png"))
+    );
+}
+
+#[test]
+fn texture_coordinates_are_flipped_for_wgpu() {
+    let model = ObjModel::load(fixture("crate.obj")).unwrap();
+    let mesh = &model.meshes[0];
+    for t in &mesh.tex_coords {
+        assert!(*t == [0.0, 1.0] || *t == [1.0, 1.0] || *t == [1.0, 0.0] || *t == [0.0, 0.0]);
+    }
+    // The first corner of the first face is `vt 0 0`, the bottom left
+    let first = mesh.indices[0] as usize;
+    assert_eq!(mesh.tex_coords[first], [0.0, 1.0]);
+}
+
+#[test]
+fn missing_normals_are_computed_and_missing_materials_ignored() {
+    let model = ObjModel::load(fixture("bare.obj")).unwrap();
+
+    assert!(model.materials.is_empty());
+    let mesh = &model.meshes[0];
+    assert_eq!(mesh.material, None);
+    assert_eq!(mesh.tex_coords, vec![[0.0, 0.0]; mesh.positions.len()]);
+    for n in &mesh.normals {
+        assert_eq!(*n, [0.0, 1.0, 0.0]);
+    }
+}
+
+#[test]
+fn bounds_cover_every_vertex() {
+    let model = ObjModel::load(fixture("crate.obj")).unwrap();
+    let bounds = model.bounds().unwrap();
+    assert_eq!(bounds.min, vec3(-0.5, 0.0, -0.5));
+    assert_eq!(bounds.max, vec3(0.5, 1.0, 0.5));
+
+    assert!(ObjModel::default().bounds().is_none());
+}
+
+#[test]
+fn missing_file_is_an_error() {
+    match ObjModel::load(fixture("nowhere.obj")) {
+        Err(ObjError::Load(tobj::LoadError::OpenFileFailed)) => {}
+        other => panic!("expected an open error, got {:?}", other),
+    }
+}
@@ -0,0 +1,311 @@
+use vox23::{
+    chunk::{self, BlockData, BlockKind, Chunk},
+    editor::{self, Clipboard, Selection, Transform},
+    map::Columns,
+    schematic::{Schematic, SchematicError, VERSION},
+    state::{Axis, BlockState, Facing, Half},
+    worldgen,
+};
+
+/// A small hut: a stone floor, glass walls with a gap for a door, and a
+/// leaf roof, 5 x 4 x 5 from the origin.
+fn hut() -> Vec<Chunk> {
+    let mut chunks = vec![];
+    let mut edits = editor::fill(&Selection::new((0, 0, 0), (4, 0, 4)), BlockKind::Stone);
+    edits.extend(editor::fill(
+        &Selection::new((0, 1, 0), (4, 2, 4)),
+        BlockKind::Glass,
+    ));
+    edits.extend(editor::fill(
+        &Selection::new((1, 1, 1), (3, 2, 3)),
+        BlockKind::Air,
+    ));
+    edits.extend(editor::fill(
+        &Selection::new((2, 1, 0), (2, 2, 0)),
+        BlockKind::Air,
+    ));
+    edits.extend(editor::fill(
+        &Selection::new((0, 3, 0), (4, 3, 4)),
+        BlockKind::Leaves,
+    ));
+    editor::apply(&mut chunks, &edits);
+    chunks
+}
+
+fn round_trip(template: &Schematic) -> Schematic {
+    let mut bytes = vec![];
+    template.write(&mut bytes).unwrap();
+    Schematic::read(&mut bytes.as_slice()).unwrap()
+}
+
+#[test]
+fn round_trips_through_chunks() {
+    let chunks = hut();
+    let region = Selection::new((0, 0, 0), (4, 3, 4));
+    let template = Schematic::from_chunks(&chunks, &region).unwrap();
+    let read = round_trip(&template);
+    assert_eq!(read, template);
+
+    // Placed across a chunk border, it matches the original block for block
+    let mut placed = vec![];
+    editor::apply(
+        &mut placed,
+        &read.place((-2, 5, 30), Transform::default(), true),
+    );
+    for (x, y, z) in region.positions() {
+        assert_eq!(
+            chunk::world_block(&placed, (x - 2, y + 5, z + 30)),
+            chunk::world_block(&chunks, (x, y, z)),
+        );
+    }
+}
+
+#[test]
+fn keeps_metadata() {
+    let mut template =
+        Schematic::from_chunks(&hut(), &Selection::new((0, 0, 0), (4, 3, 4))).unwrap();
+    template.metadata.insert("name".into(), "Hut".into());
+    template.metadata.insert("author".into(), "Ünal".into());
+    let read = round_trip(&template);
+    assert_eq!(read.metadata.get("name").map(String::as_str), Some("Hut"));
+    assert_eq!(
+        read.metadata.get("author").map(String::as_str),
+        Some("Ünal")
+    );
+}
+
+#[test]
+fn runs_keep_files_small() {
+    let mut chunks = vec![];
+    let region = Selection::new((0, 0, 0), (63, 15, 63));
+    editor::apply(&mut chunks, &editor::fill(&region, BlockKind::Stone));
+    let template = Schematic::from_chunks(&chunks, &region).unwrap();
+    let mut bytes = vec![];
+    template.write(&mut bytes).unwrap();
+    // 65536 blocks in two runs
+    assert!(bytes.len() < 64, "{} bytes", bytes.len());
+    assert_eq!(round_trip(&template), template);
+}
+
+#[test]
+fn missing_chunks_are_saved_as_air() {
+    let template = Schematic::new(Clipboard {
+        size: (2, 1, 1),
+        blocks: vec![BlockKind::None.into(), BlockKind::Sand.into()],
+    });
+    assert_eq!(
+        round_trip(&template).blocks.blocks,
+        vec![BlockKind::Air.into(), BlockKind::Sand.into()]
+    );
+}
+
+#[test]
+fn keeps_block_states_and_levels() {
+    let stairs = BlockData {
+        kind: BlockKind::StoneStairs,
+        state: BlockState::default()
+            .with_facing(Facing::East)
+            .with_half(Half::Top),
+        level: 0,
+    };
+    let log = BlockData {
+        kind: BlockKind::Log,
+        state: BlockState::default().with_axis(Axis::X),
+        level: 0,
+    };
+    let water = BlockData {
+        kind: BlockKind::Water,
+        state: BlockState::default(),
+        level: 3,
+    };
+    let mut chunks = vec![];
+    editor::apply(
+        &mut chunks,
+        &[((0, 0, 0), stairs), ((1, 0, 0), log), ((2, 0, 0), water)],
+    );
+    let region = Selection::new((0, 0, 0), (2, 0, 0));
+    let read = round_trip(&Schematic::from_chunks(&chunks, &region).unwrap());
+    assert_eq!(read.blocks.blocks, vec![stairs, log, water]);
+
+    let mut placed = vec![];
+    editor::apply(
+        &mut placed,
+        &read.place((5, 5, 5), Transform::default(), false),
+    );
+    assert_eq!(chunk::world_block_data(&placed, (5, 5, 5)), stairs);
+    assert_eq!(chunk::world_block_data(&placed, (6, 5, 5)), log);
+    assert_eq!(chunk::world_block_data(&placed, (7, 5, 5)), water);
+}
+
+/// `count` different blocks, water of every level, facing, axis and half.
+fn distinct_blocks(count: usize) -> Clipboard {
+    let mut blocks = vec![];
+    for level in 0..8 {
+        for facing in Facing::ALL {
+            for axis in [Axis::Y, Axis::X, Axis::Z] {
+                for half in [Half::Bottom, Half::Top] {
+                    let state = BlockState::default()
+                        .with_facing(facing)
+                        .with_axis(axis)
+                        .with_half(half);
+                    blocks.push(BlockData {
+                        kind: BlockKind::Water,
+                        state,
+                        level,
+                    });
+                }
+            }
+        }
+    }
+    blocks.truncate(count);
+    Clipboard {
+        size: (count as i64, 1, 1),
+        blocks,
+    }
+}
+
+#[test]
+fn palettes_hold_256_blocks() {
+    let full = Schematic::new(distinct_blocks(256));
+    assert_eq!(round_trip(&full), full);
+
+    let mut bytes = vec![];
+    assert!(matches!(
+        Schematic::new(distinct_blocks(257)).write(&mut bytes),
+        Err(SchematicError::Format(_))
+    ));
+}
+
+#[test]
+fn rejects_metadata_too_long_to_write() {
+    let mut template = Schematic::new(distinct_blocks(1));
+    template
+        .metadata
+        .insert("notes".into(), "a".repeat(u16::MAX as usize + 1));
+    let mut bytes = vec![];
+    assert!(matches!(
+        template.write(&mut bytes),
+        Err(SchematicError::Format(_))
+    ));
+    assert!(bytes.is_empty());
+
+    let mut template = Schematic::new(distinct_blocks(1));
+    for i in 0..=u16::MAX as usize {
+        template.metadata.insert(i.to_string(), String::new());
+    }
+    assert!(matches!(
+        template.write(&mut bytes),
+        Err(SchematicError::Format(_))
+    ));
+}
+
+/// A file of an older version holding two sand blocks, with the palette
+/// entry written by `entry`.
+fn older_file(version: u32, entry: &[u8]) -> Vec<u8> {
+    let mut bytes = b"VXSC".to_vec();
+    bytes.extend(version.to_le_bytes());
+    for axis in [2u16, 1, 1] {
+        bytes.extend(axis.to_le_bytes());
+    }
+    // A u8 count of palette entries
+    bytes.push(1);
+    bytes.push(4);
+    bytes.extend(b"sand");
+    bytes.extend(entry);
+    bytes.extend(0u16.to_le_bytes());
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend(2u16.to_le_bytes());
+    bytes.push(0);
+    bytes
+}
+
+#[test]
+fn reads_older_versions() {
+    // Version 1 has only the name
+    let read = Schematic::read(&mut older_file(1, &[]).as_slice()).unwrap();
+    assert_eq!(read.blocks.size, (2, 1, 1));
+    assert_eq!(read.blocks.blocks, vec![BlockKind::Sand.into(); 2]);
+
+    let state = BlockState::default().with_facing(Facing::West);
+    let read = Schematic::read(&mut older_file(2, &[state.bits(), 0]).as_slice()).unwrap();
+    let sand = BlockData {
+        kind: BlockKind::Sand,
+        state,
+        level: 0,
+    };
+    assert_eq!(read.blocks.blocks, vec![sand; 2]);
+}
+
+#[test]
+fn rejects_newer_versions_and_broken_files() {
+    let template = Schematic::from_chunks(&hut(), &Selection::new((0, 0, 0), (4, 3, 4))).unwrap();
+    let mut bytes = vec![];
+    template.write(&mut bytes).unwrap();
+
+    let mut newer = bytes.clone();
+    newer[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+    assert!(matches!(
+        Schematic::read(&mut newer.as_slice()),
+        Err(SchematicError::Version(v)) if v == VERSION + 1
+    ));
+
+    let mut magic = bytes.clone();
+    magic[0] = b'X';
+    assert!(matches!(
+        Schematic::read(&mut magic.as_slice()),
+        Err(SchematicError::Format(_))
+    ));
+
+    let truncated = &bytes[..bytes.len() - 2];
+    assert!(matches!(
+        Schematic::read(&mut &truncated[..]),
+        Err(SchematicError::Io(_))
+    ));
+
+    // The first palette entry, stone, renamed
+    let stone = bytes.windows(5).position(|w| w == b"stone").unwrap();
+    let mut unknown = bytes.clone();
+    unknown[stone..stone + 5].copy_from_slice(b"stony");
+    assert!(matches!(
+        Schematic::read(&mut unknown.as_slice()),
+        Err(SchematicError::UnknownBlock(name)) if name == "stony"
+    ));
+
+    // Stone's state, right after its name, facing past down
+    let mut state = bytes.clone();
+    state[stone + 5] = 6;
+    assert!(matches!(
+        Schematic::read(&mut state.as_slice()),
+        Err(SchematicError::Format(_))
+    ));
+}
+
+#[test]
+fn structures_stand_on_the_ground() {
+    let template = Schematic::from_chunks(&hut(), &Selection::new((0, 0, 0), (4, 3, 4))).unwrap();
+    let mut chunks = worldgen::generate(worldgen::DEFAULT_SEED);
+    let columns = Columns::new(&chunks);
+    // The generated chunk is the one east of the origin
+    let ground = (22..27)
+        .flat_map(|x| (6..11).map(move |z| (x, z)))
+        .map(|(x, z)| columns.top(x, z).unwrap().y)
+        .max()
+        .unwrap();
+    assert!(worldgen::place_structure(&mut chunks, &template, 24, 8));
+    assert_eq!(
+        chunk::world_block(&chunks, (22, ground + 1, 6)),
+        BlockKind::Stone
+    );
+    assert_eq!(
+        chunk::world_block(&chunks, (26, ground + 4, 10)),
+        BlockKind::Leaves
+    );
+
+    // Nothing to stand on far from the world
+    assert!(!worldgen::place_structure(
+        &mut chunks,
+        &template,
+        1000,
+        1000
+    ));
+}
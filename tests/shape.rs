@@ -0,0 +1,216 @@
+use vox23::{
+    blocks,
+    chunk::{Block, BlockKind, Chunk},
+    mesh::{self, BlockMesh},
+    shape::{self, Shape, ShapeError},
+    state::{BlockState, Facing, Half},
+};
+
+fn mesh_of(chunk: &Chunk, kind: BlockKind) -> Option<BlockMesh> {
+    mesh::chunk_meshes(chunk)
+        .into_iter()
+        .find(|m| m.kind == kind)
+}
+
+fn faces(mesh: &BlockMesh) -> usize {
+    mesh.indices.len() / 6
+}
+
+#[test]
+fn parses_boxes_and_quads() {
+    let shape = Shape::from_json(
+        r#"{
+            "elements": [{"from": [0, 0, 0], "to": [16, 4, 16]}],
+            "quads": [{
+                "vertices": [[0, 0, 8], [16, 0, 8], [16, 16, 8], [0, 16, 8]],
+                "uv": [[0, 16], [16, 16], [16, 0], [0, 0]]
+            }]
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(shape.boxes.len(), 1);
+    assert_eq!(shape.boxes[0].to, [16.0, 4.0, 16.0]);
+    assert_eq!(shape.quads.len(), 1);
+    assert!(!shape.quads[0].double_sided);
+    // Six faces for the box and one for the quad
+    assert_eq!(shape.faces(BlockState::default()).len(), 7);
+
+    assert!(matches!(Shape::from_json("{"), Err(ShapeError::Json(_))));
+    assert!(matches!(Shape::from_json("{}"), Err(ShapeError::Format(_))));
+    assert!(matches!(
+        Shape::from_json(r#"{"elements": [{"from": [0, 0, 0], "to": [16, 20, 16]}]}"#),
+        Err(ShapeError::Format(_))
+    ));
+    assert!(matches!(
+        Shape::from_json(r#"{"quads": [{"vertices": [[0, 0, 0]], "uv": []}]}"#),
+        Err(ShapeError::Format(_))
+    ));
+}
+
+#[test]
+fn built_in_models_parse() {
+    for kind in blocks::KINDS {
+        let has_model = blocks::definition(kind).model.is_some();
+        assert_eq!(shape::of(kind).is_some(), has_model, "{:?}", kind);
+    }
+    assert!(shape::of(BlockKind::Stone).is_none());
+    assert_eq!(shape::of(BlockKind::Flower).unwrap().quads.len(), 2);
+    // Shaped blocks aren't picked for colors
+    assert_eq!(blocks::by_color([39, 41, 42]), BlockKind::Stone);
+}
+
+#[test]
+fn a_full_box_matches_the_cube() {
+    let shape =
+        Shape::from_json(r#"{"elements": [{"from": [0, 0, 0], "to": [16, 16, 16]}]}"#).unwrap();
+    let mut cube = Chunk::new(0, 0, 0);
+    cube.set_block(Block::new(BlockKind::Stone, 5, 5, 5));
+    let cube = mesh_of(&cube, BlockKind::Stone).unwrap();
+    for (i, face) in shape.faces(BlockState::default()).iter().enumerate() {
+        for (j, p) in face.positions.iter().enumerate() {
+            let v = i * 4 + j;
+            let position: [f32; 3] = (p + cgmath::vec3(5.0, 5.0, 5.0)).into();
+            assert_eq!(position, cube.positions[v]);
+            assert_eq!(
+                [face.tex_coords[j][0] / 16.0, face.tex_coords[j][1] / 16.0],
+                cube.tex_coords[v]
+            );
+            let normal: [f32; 3] = face.normal.into();
+            assert_eq!(normal, cube.normals[v]);
+        }
+    }
+}
+
+#[test]
+fn slabs_fill_half_a_block() {
+    let mut chunk = Chunk::new(0, 0, 0);
+    chunk.set_block(Block::new(BlockKind::StoneSlab, 5, 5, 5));
+    chunk.set_block(Block {
+        state: BlockState::default().with_half(Half::Top),
+        ..Block::new(BlockKind::StoneSlab, 8, 5, 5)
+    });
+    let slabs = mesh_of(&chunk, BlockKind::StoneSlab).unwrap();
+    assert_eq!(faces(&slabs), 12);
+    let (min, max) = slabs.bounds();
+    assert_eq!(min, [4.5, 4.5, 4.5]);
+    assert_eq!(max, [8.5, 5.5, 5.5]);
+    let bottom_slab = slabs.positions[..24].iter().map(|p| p[1]);
+    assert!(bottom_slab.clone().all(|y| y <= 5.0));
+    assert!(bottom_slab.clone().any(|y| y == 5.0));
+    let top_slab = slabs.positions[24..].iter().map(|p| p[1]);
+    assert!(top_slab.clone().all(|y| y >= 5.0));
+}
+
+#[test]
+fn partial_faces_hide_only_what_they_cover() {
+    let mut chunk = Chunk::new(0, 0, 0);
+    chunk.set_block(Block::new(BlockKind::StoneSlab, 5, 5, 5));
+    chunk.set_block(Block::new(BlockKind::Stone, 6, 5, 5));
+    // The stone can be seen above the slab, but the slab's side against the
+    // stone is hidden
+    let stone = mesh_of(&chunk, BlockKind::Stone).unwrap();
+    assert_eq!(faces(&stone), 6);
+    let slab = mesh_of(&chunk, BlockKind::StoneSlab).unwrap();
+    assert_eq!(faces(&slab), 5);
+    assert!(!slab.normals.contains(&[1.0, 0.0, 0.0]));
+
+    // Slabs side by side hide the sides between them
+    chunk.set_block(Block::new(BlockKind::StoneSlab, 6, 5, 5));
+    assert_eq!(faces(&mesh_of(&chunk, BlockKind::StoneSlab).unwrap()), 10);
+    // But a top slab doesn't cover a bottom one
+    chunk.set_block(Block {
+        state: BlockState::default().with_half(Half::Top),
+        ..Block::new(BlockKind::StoneSlab, 6, 5, 5)
+    });
+    assert_eq!(faces(&mesh_of(&chunk, BlockKind::StoneSlab).unwrap()), 12);
+
+    // A slab on stone hides the stone's top, and the slab's bottom
+    let mut chunk = Chunk::new(0, 0, 0);
+    chunk.set_block(Block::new(BlockKind::Stone, 5, 4, 5));
+    chunk.set_block(Block::new(BlockKind::StoneSlab, 5, 5, 5));
+    let stone = mesh_of(&chunk, BlockKind::Stone).unwrap();
+    assert!(!stone.normals.contains(&[0.0, 1.0, 0.0]));
+    let slab = mesh_of(&chunk, BlockKind::StoneSlab).unwrap();
+    assert!(!slab.normals.contains(&[0.0, -1.0, 0.0]));
+}
+
+#[test]
+fn blocks_on_the_edge_do_not_see_into_the_next_row() {
+    // Past x = 15 and y = 15 the block index runs on into the air of the
+    // next row, but the side facing out of the chunk stays hidden like on
+    // every other edge
+    let mut chunk = Chunk::new(0, 0, 0);
+    chunk.set_block(Block::new(BlockKind::Stone, 15, 5, 5));
+    chunk.set_block(Block::new(BlockKind::Stone, 5, 15, 5));
+    assert_eq!(faces(&mesh_of(&chunk, BlockKind::Stone).unwrap()), 10);
+
+    // Nor is fluid at the top of the chunk filled up by it
+    let mut chunk = Chunk::new(0, 0, 0);
+    chunk.set_block(Block::new(BlockKind::Water, 5, 15, 5));
+    chunk.set_block(Block::new(BlockKind::Water, 5, 0, 6));
+    let top = *chunk.get_block(5, 15, 5).unwrap();
+    assert!(chunk.block_height(&top) < 1.0);
+}
+
+#[test]
+fn stairs_turn_to_face_the_viewer() {
+    let covers = |state: BlockState, side: Facing| {
+        shape::covers(BlockKind::StoneStairs, state, side, shape::WHOLE_SIDE)
+    };
+    // The tall back, made of both boxes, is opposite the front, which faces
+    // the viewer
+    let north = BlockState::default();
+    assert!(covers(north, Facing::South));
+    assert!(!covers(north, Facing::North));
+    assert!(covers(north, Facing::Down));
+    assert!(!covers(north, Facing::Up));
+    let east = north.with_facing(Facing::East);
+    assert!(covers(east, Facing::West));
+    assert!(!covers(east, Facing::East));
+    // Upside down, the full side is on top
+    let top = north.with_half(Half::Top);
+    assert!(covers(top, Facing::Up));
+    assert!(!covers(top, Facing::Down));
+    assert!(covers(top, Facing::South));
+
+    // The front still covers its lower half
+    assert!(shape::covers(
+        BlockKind::StoneStairs,
+        east,
+        Facing::East,
+        [-0.5, -0.5, 0.0, 0.5]
+    ));
+    assert!(!shape::covers(
+        BlockKind::StoneStairs,
+        east,
+        Facing::East,
+        [0.0, -0.5, 0.5, 0.5]
+    ));
+}
+
+#[test]
+fn plants_are_crossed_quads_that_are_never_hidden() {
+    let mut chunk = Chunk::new(0, 0, 0);
+    chunk.set_block(Block::new(BlockKind::Flower, 5, 5, 5));
+    for (x, y, z) in [
+        (4, 5, 5),
+        (6, 5, 5),
+        (5, 4, 5),
+        (5, 6, 5),
+        (5, 5, 4),
+        (5, 5, 6),
+    ] {
+        chunk.set_block(Block::new(BlockKind::Stone, x, y, z));
+    }
+    let flower = mesh_of(&chunk, BlockKind::Flower).unwrap();
+    assert_eq!(faces(&flower), 2);
+    assert_eq!(flower.bounds(), ([4.5, 4.5, 4.5], [5.5, 5.5, 5.5]));
+    // Neither quad lies along an axis
+    assert!(flower
+        .normals
+        .iter()
+        .all(|n| n[0] != 0.0 && n[1] == 0.0 && n[2] != 0.0));
+    // And the flower hides none of the stone around it
+    let stone = mesh_of(&chunk, BlockKind::Stone).unwrap();
+    assert_eq!(faces(&stone), 36);
+}
@@ -0,0 +1,143 @@
+use cgmath::{vec3, Matrix3, SquareMatrix, Vector3};
+use vox23::{
+    blocks,
+    chunk::{self, Block, BlockKind, Chunk},
+    mesh,
+    state::{Axis, BlockState, Facing, Half, Property},
+};
+
+const HALVES: [Half; 2] = [Half::Bottom, Half::Top];
+const AXES: [Axis; 3] = [Axis::Y, Axis::X, Axis::Z];
+
+#[test]
+fn properties_are_packed_independently() {
+    for facing in Facing::ALL {
+        for axis in AXES {
+            for half in HALVES {
+                let state = BlockState::default()
+                    .with_facing(facing)
+                    .with_axis(axis)
+                    .with_half(half);
+                assert_eq!(state.facing(), facing);
+                assert_eq!(state.axis(), axis);
+                assert_eq!(state.half(), half);
+                assert_eq!(state.with_facing(Facing::North).axis(), axis);
+            }
+        }
+    }
+    assert_eq!(std::mem::size_of::<BlockState>(), 1);
+}
+
+#[test]
+fn definitions_declare_their_properties() {
+    assert_eq!(
+        blocks::definition(BlockKind::Log).properties,
+        &[Property::Axis]
+    );
+    assert_eq!(
+        blocks::definition(BlockKind::Water).properties,
+        &[Property::Level]
+    );
+    assert!(blocks::definition(BlockKind::Stone).properties.is_empty());
+}
+
+#[test]
+fn placement_follows_the_view() {
+    let all = [Property::Facing, Property::Axis, Property::Half];
+    let east = vec3(1.0, -0.2, 0.1);
+    let state = BlockState::placed(&all, east, (-1, 0, 0), 0.8);
+    // Facing back at the viewer, along the view, high on a wall
+    assert_eq!(state.facing(), Facing::West);
+    assert_eq!(state.axis(), Axis::X);
+    assert_eq!(state.half(), Half::Top);
+
+    let down = vec3(0.1, -1.0, -0.3);
+    let state = BlockState::placed(&all, down, (0, 1, 0), 0.0);
+    assert_eq!(state.facing(), Facing::North);
+    assert_eq!(state.axis(), Axis::Y);
+    assert_eq!(state.half(), Half::Bottom);
+    // Under a ceiling
+    let state = BlockState::placed(&all, vec3(0.0, 1.0, 1.0), (0, -1, 0), 0.0);
+    assert_eq!(state.half(), Half::Top);
+
+    // Undeclared properties stay at their default
+    let log = BlockState::placed(&[Property::Axis], east, (-1, 0, 0), 0.8);
+    assert_eq!(log, BlockState::default().with_axis(Axis::X));
+}
+
+#[test]
+fn rotations_turn_the_front_and_top() {
+    assert_eq!(BlockState::default().rotation(), Matrix3::identity());
+    let front = |state: BlockState| state.rotation() * Vector3::unit_z();
+    let up = |state: BlockState| state.rotation() * Vector3::unit_y();
+    let state = BlockState::default();
+    assert_eq!(front(state.with_facing(Facing::East)), Vector3::unit_x());
+    assert_eq!(front(state.with_facing(Facing::South)), -Vector3::unit_z());
+    assert_eq!(front(state.with_facing(Facing::West)), -Vector3::unit_x());
+    assert_eq!(front(state.with_facing(Facing::Up)), Vector3::unit_y());
+    assert_eq!(front(state.with_facing(Facing::Down)), -Vector3::unit_y());
+    assert_eq!(up(state.with_axis(Axis::X)), Vector3::unit_x());
+    assert_eq!(up(state.with_axis(Axis::Z)), Vector3::unit_z());
+    let upside_down = state.with_facing(Facing::East).with_half(Half::Top);
+    assert_eq!(up(upside_down), -Vector3::unit_y());
+    assert_eq!(front(upside_down), Vector3::unit_x());
+}
+
+#[test]
+fn meshes_turn_with_the_state() {
+    let mut chunk = Chunk::new(0, 0, 0);
+    chunk.set_block(Block {
+        state: BlockState::default().with_axis(Axis::X),
+        ..Block::new(BlockKind::Log, 5, 5, 5)
+    });
+    chunk.set_block(Block::new(BlockKind::Stone, 6, 5, 5));
+    let meshes = mesh::chunk_meshes(&chunk);
+    let log = meshes.iter().find(|m| m.kind == BlockKind::Log).unwrap();
+    // The face against the stone is still the one left out
+    assert_eq!(log.indices.len() / 6, 5);
+    assert!(!log.normals.contains(&[1.0, 0.0, 0.0]));
+    assert!(log.normals.contains(&[-1.0, 0.0, 0.0]));
+    assert_eq!(log.bounds(), ([4.5, 4.5, 4.5], [5.5, 5.5, 5.5]));
+
+    // The log's bottom end when upright now points west, and its top end
+    // east into the stone
+    let upright = {
+        let mut chunk = Chunk::new(0, 0, 0);
+        chunk.set_block(Block::new(BlockKind::Log, 5, 5, 5));
+        mesh::chunk_meshes(&chunk).remove(0)
+    };
+    let bottom = upright
+        .normals
+        .iter()
+        .position(|n| *n == [0.0, -1.0, 0.0])
+        .unwrap();
+    let end = log
+        .normals
+        .iter()
+        .position(|n| *n == [-1.0, 0.0, 0.0])
+        .unwrap();
+    assert_eq!(
+        log.tex_coords[end..end + 4],
+        upright.tex_coords[bottom..bottom + 4]
+    );
+}
+
+#[test]
+fn state_is_kept_per_block() {
+    let mut chunks = vec![];
+    chunk::set_world_block(&mut chunks, BlockKind::Log, (-3, 2, 7));
+    let turned = BlockState::default().with_axis(Axis::Z);
+    chunk::set_world_state(&mut chunks, turned, (-3, 2, 7));
+    assert_eq!(chunk::world_state(&chunks, (-3, 2, 7)), turned);
+    assert_eq!(chunk::world_block(&chunks, (-3, 2, 7)), BlockKind::Log);
+    assert_eq!(
+        chunk::world_state(&chunks, (-2, 2, 7)),
+        BlockState::default()
+    );
+    // Replacing the block resets its state
+    chunk::set_world_block(&mut chunks, BlockKind::Log, (-3, 2, 7));
+    assert_eq!(
+        chunk::world_state(&chunks, (-3, 2, 7)),
+        BlockState::default()
+    );
+}
@@ -0,0 +1,89 @@
+use std::collections::VecDeque;
+
+use vox23::surface::{acquire_frame, FrameOutcome, FrameSurface};
+use wgpu::SurfaceError;
+
+/// Hands out scripted acquire results, like a swapchain that keeps getting
+/// lost until it is reconfigured.
+struct MockSurface {
+    results: VecDeque<Result<u32, SurfaceError>>,
+    reconfigured: u32,
+}
+
+impl MockSurface {
+    fn new(results: Vec<Result<u32, SurfaceError>>) -> Self {
+        Self {
+            results: results.into(),
+            reconfigured: 0,
+        }
+    }
+}
+
+impl FrameSurface for MockSurface {
+    type Frame = u32;
+    fn acquire(&mut self) -> Result<u32, SurfaceError> {
+        self.results.pop_front().expect("acquired too many times")
+    }
+    fn reconfigure(&mut self) {
+        self.reconfigured += 1;
+    }
+}
+
+#[test]
+fn ready_frame_is_returned() {
+    let mut surface = MockSurface::new(vec![Ok(1)]);
+    assert!(matches!(acquire_frame(&mut surface), FrameOutcome::Ready(1)));
+    assert_eq!(surface.reconfigured, 0);
+}
+
+#[test]
+fn lost_surface_is_reconfigured_and_retried() {
+    let mut surface = MockSurface::new(vec![Err(SurfaceError::Lost), Ok(2)]);
+    assert!(matches!(acquire_frame(&mut surface), FrameOutcome::Ready(2)));
+    assert_eq!(surface.reconfigured, 1);
+}
+
+#[test]
+fn outdated_surface_is_reconfigured_and_retried() {
+    let mut surface = MockSurface::new(vec![Err(SurfaceError::Outdated), Ok(3)]);
+    assert!(matches!(acquire_frame(&mut surface), FrameOutcome::Ready(3)));
+    assert_eq!(surface.reconfigured, 1);
+}
+
+#[test]
+fn surface_that_stays_lost_skips_the_frame() {
+    let mut surface = MockSurface::new(vec![Err(SurfaceError::Lost), Err(SurfaceError::Lost)]);
+    assert!(matches!(acquire_frame(&mut surface), FrameOutcome::Skip));
+    assert_eq!(surface.reconfigured, 1);
+    assert!(surface.results.is_empty());
+}
+
+#[test]
+fn timeout_skips_without_reconfiguring() {
+    let mut surface = MockSurface::new(vec![Err(SurfaceError::Timeout)]);
+    assert!(matches!(acquire_frame(&mut surface), FrameOutcome::Skip));
+    assert_eq!(surface.reconfigured, 0);
+}
+
+#[test]
+fn out_of_memory_is_reported() {
+    let mut surface = MockSurface::new(vec![Err(SurfaceError::OutOfMemory)]);
+    assert!(matches!(
+        acquire_frame(&mut surface),
+        FrameOutcome::OutOfMemory
+    ));
+    assert_eq!(surface.reconfigured, 0);
+}
+
+#[test]
+fn out_of_memory_after_reconfigure_is_reported() {
+    let mut surface = MockSurface::new(vec![
+        Err(SurfaceError::Outdated),
+        Err(SurfaceError::OutOfMemory),
+    ]);
+    assert!(matches!(
+        acquire_frame(&mut surface),
+        FrameOutcome::OutOfMemory
+    ));
+    assert_eq!(surface.reconfigured, 1);
+}
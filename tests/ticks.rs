@@ -0,0 +1,111 @@
+use vox23::{
+    chunk::{Block, BlockKind, Chunk},
+    tick::{BlockTicks, FixedStep, Position, TickRng},
+};
+
+/// Dirt at y = 0 with one grass block in the middle.
+fn meadow() -> Chunk {
+    let mut chunk = Chunk::new(0, 0, 0);
+    for x in 0..16 {
+        for z in 0..16 {
+            chunk.set_block(Block::new(BlockKind::Dirt, x, 0, z));
+        }
+    }
+    chunk.set_block(Block::new(BlockKind::Grass, 8, 0, 8));
+    chunk
+}
+
+fn count(chunk: &Chunk, kind: BlockKind) -> usize {
+    chunk
+        .get_block_kinds()
+        .into_iter()
+        .filter(|k| *k == kind)
+        .count()
+}
+
+fn replay(seed: u64, ticks: u32) -> Vec<Vec<Position>> {
+    let mut chunk = meadow();
+    let mut block_ticks = BlockTicks::new(seed);
+    // Plenty of random ticks so grass spreads within a short test
+    block_ticks.random_tick_speed = 512;
+    (0..ticks).map(|_| block_ticks.tick(&mut chunk)).collect()
+}
+
+#[test]
+fn grass_spreads_over_dirt() {
+    let mut chunk = meadow();
+    let mut ticks = BlockTicks::new(7);
+    ticks.random_tick_speed = 512;
+    for _ in 0..200 {
+        ticks.tick(&mut chunk);
+    }
+    assert!(count(&chunk, BlockKind::Grass) > 1);
+    assert_eq!(count(&chunk, BlockKind::Grass) + count(&chunk, BlockKind::Dirt), 256);
+}
+
+#[test]
+fn covered_grass_turns_to_dirt() {
+    let mut chunk = meadow();
+    chunk.set_block(Block::new(BlockKind::Stone, 8, 1, 8));
+    let mut ticks = BlockTicks::new(7);
+    ticks.random_tick_speed = 512;
+    for _ in 0..200 {
+        ticks.tick(&mut chunk);
+    }
+    assert_eq!(count(&chunk, BlockKind::Grass), 0);
+}
+
+#[test]
+fn no_random_ticks_at_speed_zero() {
+    let mut chunk = meadow();
+    let mut ticks = BlockTicks::new(7);
+    ticks.random_tick_speed = 0;
+    for _ in 0..200 {
+        assert_eq!(ticks.tick(&mut chunk), vec![]);
+    }
+}
+
+#[test]
+fn same_seed_replays_the_same_ticks() {
+    let first = replay(42, 400);
+    assert!(first.iter().any(|changed| !changed.is_empty()));
+    assert_eq!(first, replay(42, 400));
+    assert_ne!(first, replay(43, 400));
+}
+
+#[test]
+fn scheduled_ticks_wait_for_their_delay() {
+    let mut chunk = meadow();
+    let mut ticks = BlockTicks::new(0);
+    ticks.random_tick_speed = 0;
+    chunk.set_block(Block::new(BlockKind::Water, 3, 1, 3));
+    ticks.block_changed(&chunk, (3, 1, 3));
+
+    // Water reacts 5 ticks after a change
+    for _ in 0..4 {
+        assert_eq!(ticks.tick(&mut chunk), vec![]);
+    }
+    assert!(ticks.tick(&mut chunk).contains(&(4, 1, 3)));
+    assert_eq!(ticks.current_tick(), 5);
+}
+
+#[test]
+fn fixed_step_carries_the_remainder() {
+    let mut step = FixedStep::default();
+    assert_eq!(step.advance(0.12), 2);
+    assert_eq!(step.advance(0.04), 1);
+    assert_eq!(step.advance(0.01), 0);
+    // Long stalls are capped instead of catching up
+    assert_eq!(step.advance(60.0), step.max_steps);
+    assert_eq!(step.advance(0.0), 0);
+}
+
+#[test]
+fn rng_is_seeded() {
+    let take = |seed| {
+        let mut rng = TickRng::new(seed);
+        (0..8).map(|_| rng.below(100)).collect::<Vec<_>>()
+    };
+    assert_eq!(take(1), take(1));
+    assert_ne!(take(1), take(2));
+}
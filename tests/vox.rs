@@ -0,0 +1,149 @@
+use std::path::PathBuf;
+
+use vox23::{
+    chunk::{self, BlockKind},
+    vox::{self, VoxError, VoxFile, VoxMapping, VoxModel, Voxel},
+};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/vox")
+        .join(name)
+}
+
+fn round_trip(file: &VoxFile) -> VoxFile {
+    let mut bytes = vec![];
+    file.write(&mut bytes).unwrap();
+    VoxFile::read(&mut bytes.as_slice()).unwrap()
+}
+
+#[test]
+fn reads_the_fixture() {
+    let file = VoxFile::load(fixture("small.vox")).unwrap();
+    assert_eq!(file.models.len(), 1);
+    assert_eq!(file.models[0].size, [4, 3, 2]);
+    assert_eq!(file.models[0].voxels.len(), 15);
+    assert_eq!(file.color(1), Some([78, 59, 49]));
+    assert_eq!(file.color(9), Some([40, 90, 200]));
+}
+
+#[test]
+fn rejects_other_files() {
+    let err = VoxFile::read(&mut &b"glTF\x02\0\0\0"[..]).unwrap_err();
+    assert!(matches!(err, VoxError::Format(_)));
+}
+
+#[test]
+fn mapping_uses_indices_then_nearest_color() {
+    let file = VoxFile::load(fixture("small.vox")).unwrap();
+    let mapping = VoxMapping::load(fixture("small.json")).unwrap();
+    assert_eq!(mapping.kind_for(&file, 1), BlockKind::Dirt);
+    assert_eq!(mapping.kind_for(&file, 2), BlockKind::Grass);
+    // Not in the mapping, but blue like water
+    assert_eq!(mapping.kind_for(&file, 9), BlockKind::Water);
+
+    let mapping =
+        VoxMapping::from_json(r##"{"colors": {"#285ac8": "glass"}, "default": "sand"}"##).unwrap();
+    assert_eq!(mapping.kind_for(&file, 9), BlockKind::Glass);
+    assert_eq!(mapping.kind_for(&file, 1), BlockKind::Sand);
+}
+
+#[test]
+fn mapping_rejects_unknown_blocks() {
+    let err = VoxMapping::from_json(r#"{"indices": {"1": "cheese"}}"#).unwrap_err();
+    assert!(matches!(err, VoxError::Mapping(_)));
+    let err = VoxMapping::from_json(r#"{"indices": {"0": "stone"}}"#).unwrap_err();
+    assert!(matches!(err, VoxError::Mapping(_)));
+}
+
+#[test]
+fn import_turns_z_up_into_y_up() {
+    let file = VoxFile::load(fixture("small.vox")).unwrap();
+    let mapping = VoxMapping::load(fixture("small.json")).unwrap();
+    let chunks = vox::import(&file, &mapping, (0, 10, 0));
+    assert_eq!(chunks.len(), 1);
+
+    // Voxel (0, 0, 1) sits on the floor at the far end of the model's y
+    assert_eq!(chunk::world_block(&chunks, (0, 11, 2)), BlockKind::Grass);
+    assert_eq!(chunk::world_block(&chunks, (3, 11, 0)), BlockKind::Grass);
+    assert_eq!(chunk::world_block(&chunks, (1, 11, 1)), BlockKind::Water);
+    assert_eq!(chunk::world_block(&chunks, (2, 10, 1)), BlockKind::Dirt);
+    assert_eq!(chunk::world_block(&chunks, (2, 11, 1)), BlockKind::Air);
+}
+
+#[test]
+fn large_models_are_split_across_chunks() {
+    let voxels = (0..40)
+        .map(|x| Voxel {
+            x,
+            y: 0,
+            z: 0,
+            color: 1,
+        })
+        .collect();
+    let file = VoxFile {
+        models: vec![VoxModel {
+            size: [40, 1, 1],
+            voxels,
+        }],
+        palette: vec![],
+    };
+    let chunks = vox::import(&file, &VoxMapping::default(), (-8, 0, -1));
+
+    let mut positions: Vec<_> = chunks.iter().map(|c| c.position()).collect();
+    positions.sort_unstable();
+    assert_eq!(positions, vec![(-1, 0, -1), (0, 0, -1), (1, 0, -1)]);
+    // Without a palette or mapping every voxel is stone
+    for x in -8..32 {
+        assert_eq!(chunk::world_block(&chunks, (x, 0, -1)), BlockKind::Stone);
+    }
+}
+
+#[test]
+fn bytes_survive_a_round_trip() {
+    let file = VoxFile::load(fixture("small.vox")).unwrap();
+    assert_eq!(round_trip(&file), file);
+
+    let two = VoxFile {
+        models: vec![file.models[0].clone(), file.models[0].clone()],
+        ..file
+    };
+    assert_eq!(round_trip(&two), two);
+}
+
+#[test]
+fn selection_survives_a_round_trip() {
+    let file = VoxFile::load(fixture("small.vox")).unwrap();
+    let mapping = VoxMapping::load(fixture("small.json")).unwrap();
+    let chunks = vox::import(&file, &mapping, (14, 3, 14));
+
+    let exported = vox::export_selection(&chunks, (17, 4, 16), (14, 3, 14), &mapping).unwrap();
+    let exported = round_trip(&exported);
+    assert_eq!(exported.models[0].size, [4, 3, 2]);
+    // Kinds named in the mapping keep their palette index
+    let mut voxels = exported.models[0].voxels.clone();
+    let mut expected = file.models[0].voxels.clone();
+    voxels.retain(|v| v.color != 9 && v.color <= 2);
+    expected.retain(|v| v.color != 9);
+    voxels.sort_unstable();
+    expected.sort_unstable();
+    assert_eq!(voxels, expected);
+
+    let back = vox::import(&exported, &mapping, (14, 3, 14));
+    for x in 14..18 {
+        for y in 3..5 {
+            for z in 14..17 {
+                assert_eq!(
+                    chunk::world_block(&back, (x, y, z)),
+                    chunk::world_block(&chunks, (x, y, z))
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn oversized_selection_is_refused() {
+    let err = vox::export_selection(&[], (0, 0, 0), (300, 1, 1), &VoxMapping::default());
+    assert!(matches!(err, Err(VoxError::TooLarge([301, 2, 2]))));
+}